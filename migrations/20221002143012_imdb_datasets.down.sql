@@ -0,0 +1,5 @@
+DROP TABLE IF EXISTS imdb_dataset_imports;
+DROP TABLE IF EXISTS name_basics;
+DROP TABLE IF EXISTS title_principals;
+DROP TABLE IF EXISTS title_ratings;
+DROP TABLE IF EXISTS title_basics
\ No newline at end of file
@@ -0,0 +1,45 @@
+CREATE TABLE IF NOT EXISTS title_basics
+(
+    tconst TEXT PRIMARY KEY,
+    title_type TEXT NOT NULL,
+    primary_title TEXT NOT NULL,
+    original_title TEXT NOT NULL,
+    is_adult BOOLEAN DEFAULT FALSE NOT NULL,
+    start_year INTEGER,
+    end_year INTEGER,
+    runtime_minutes INTEGER,
+    genres TEXT[] DEFAULT '{}' NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS title_basics_primary_title_idx
+    ON title_basics (lower(primary_title) text_pattern_ops);
+
+CREATE TABLE IF NOT EXISTS title_ratings
+(
+    tconst TEXT PRIMARY KEY,
+    average_rating DOUBLE PRECISION NOT NULL,
+    num_votes INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS title_principals
+(
+    tconst TEXT NOT NULL,
+    ordering INTEGER NOT NULL,
+    nconst TEXT NOT NULL,
+    category TEXT NOT NULL,
+    characters TEXT[] DEFAULT '{}' NOT NULL,
+    PRIMARY KEY (tconst, ordering)
+);
+
+CREATE TABLE IF NOT EXISTS name_basics
+(
+    nconst TEXT PRIMARY KEY,
+    primary_name TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS imdb_dataset_imports
+(
+    dataset TEXT PRIMARY KEY,
+    file_modified BIGINT NOT NULL,
+    file_size BIGINT NOT NULL
+)
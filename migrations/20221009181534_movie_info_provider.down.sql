@@ -0,0 +1,4 @@
+ALTER TABLE user_tokens
+    DROP COLUMN IF EXISTS movie_info_provider,
+    DROP COLUMN IF EXISTS omdb_token,
+    DROP COLUMN IF EXISTS tmdb_token
\ No newline at end of file
@@ -0,0 +1,4 @@
+ALTER TABLE user_tokens
+    ADD COLUMN IF NOT EXISTS tmdb_token TEXT DEFAULT '' NOT NULL,
+    ADD COLUMN IF NOT EXISTS omdb_token TEXT DEFAULT '' NOT NULL,
+    ADD COLUMN IF NOT EXISTS movie_info_provider TEXT DEFAULT '' NOT NULL
\ No newline at end of file
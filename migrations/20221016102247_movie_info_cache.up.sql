@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS movie_info_cache
+(
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS movie_info_cache_expires_at_idx ON movie_info_cache (expires_at)
\ No newline at end of file
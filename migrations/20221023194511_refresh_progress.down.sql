@@ -0,0 +1 @@
+DROP TABLE IF EXISTS refresh_progress
\ No newline at end of file
@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS refresh_progress
+(
+    id BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (id),
+    user_id NUMERIC,
+    start_cursor TEXT,
+    finished_at TIMESTAMPTZ
+)
\ No newline at end of file
@@ -0,0 +1 @@
+DROP TABLE IF EXISTS property_mappings
\ No newline at end of file
@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS property_mappings
+(
+    user_id NUMERIC NOT NULL REFERENCES user_tokens (user_id) ON DELETE CASCADE,
+    field TEXT NOT NULL,
+    property_name TEXT,
+    property_type TEXT,
+    PRIMARY KEY (user_id, field)
+)
\ No newline at end of file
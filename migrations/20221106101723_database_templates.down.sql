@@ -0,0 +1 @@
+DROP TABLE IF EXISTS database_templates
\ No newline at end of file
@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS database_templates
+(
+    user_id NUMERIC PRIMARY KEY REFERENCES user_tokens (user_id) ON DELETE CASCADE,
+    page_id TEXT NOT NULL,
+    title TEXT NOT NULL,
+    icon TEXT NOT NULL,
+    fields TEXT NOT NULL
+)
\ No newline at end of file
@@ -0,0 +1 @@
+DROP TABLE IF EXISTS notion_oauth_states
\ No newline at end of file
@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS notion_oauth_states
+(
+    state TEXT PRIMARY KEY,
+    user_id NUMERIC NOT NULL REFERENCES user_tokens (user_id) ON DELETE CASCADE,
+    chat_id BIGINT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+)
\ No newline at end of file
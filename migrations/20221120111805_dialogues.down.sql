@@ -0,0 +1 @@
+DROP TABLE IF EXISTS dialogues
\ No newline at end of file
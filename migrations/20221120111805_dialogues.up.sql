@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS dialogues
+(
+    chat_id BIGINT PRIMARY KEY,
+    dialogue TEXT NOT NULL
+)
\ No newline at end of file
@@ -0,0 +1,4 @@
+DROP INDEX IF EXISTS title_basics_primary_title_trgm_idx;
+
+CREATE INDEX IF NOT EXISTS title_basics_primary_title_idx
+    ON title_basics (lower(primary_title) text_pattern_ops)
\ No newline at end of file
@@ -0,0 +1,7 @@
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+-- searches match anywhere in the title, not only its beginning
+DROP INDEX IF EXISTS title_basics_primary_title_idx;
+
+CREATE INDEX IF NOT EXISTS title_basics_primary_title_trgm_idx
+    ON title_basics USING gin (lower(primary_title) gin_trgm_ops)
\ No newline at end of file
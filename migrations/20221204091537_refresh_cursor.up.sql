@@ -0,0 +1,2 @@
+ALTER TABLE user_tokens
+    ADD COLUMN IF NOT EXISTS refresh_cursor TEXT
\ No newline at end of file
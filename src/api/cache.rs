@@ -0,0 +1,507 @@
+use crate::api::{MovieInfo, Provider, RequestMovieInfo, SearchResult};
+use crate::error::BotError;
+use crate::Language;
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::PgPool;
+
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+type Api = Arc<dyn RequestMovieInfo + Send + Sync>;
+pub type MovieInfoCache = Arc<dyn CacheStore + Send + Sync>;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CacheBackend {
+    Postgres,
+    Memory,
+    Off,
+}
+
+impl FromStr for CacheBackend {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "postgres" => Ok(CacheBackend::Postgres),
+            "memory" => Ok(CacheBackend::Memory),
+            "off" | "none" => Ok(CacheBackend::Off),
+            _ => Err(anyhow!("unknown cache backend `{}`", s)),
+        }
+    }
+}
+
+#[async_trait]
+pub trait CacheStore {
+    async fn get(&self, key: &str) -> anyhow::Result<Option<Value>>;
+
+    async fn put(&self, key: &str, value: Value, ttl: Duration) -> anyhow::Result<()>;
+
+    /// Drops the expired entries, `get` already leaves them out. Returns how many there were.
+    async fn delete_expired(&self) -> anyhow::Result<u64>;
+}
+
+/// Drops the expired entries of `store` every `interval`, instead of on every write.
+pub async fn clean_up_movie_info_cache(store: MovieInfoCache, interval: Duration) {
+    let mut interval = tokio::time::interval(interval);
+
+    loop {
+        interval.tick().await;
+
+        match store.delete_expired().await {
+            Ok(0) => {}
+            Ok(deleted) => log::info!("Deleted {} expired movie info cache entries", deleted),
+            Err(e) => log::warn!("failed to clean up movie info cache: {:?}", e),
+        }
+    }
+}
+
+/// Remembers search results and title information of the wrapped provider for `ttl`, so that
+/// popular titles don't burn the provider's quota over and over again.
+pub struct CachedApi {
+    inner: Api,
+    store: MovieInfoCache,
+    ttl: Duration,
+}
+
+impl CachedApi {
+    pub fn new(inner: Api, store: MovieInfoCache, ttl: Duration) -> Self {
+        Self { inner, store, ttl }
+    }
+
+    fn search_key(&self, keyword: &str, limits: usize, lang: Language) -> String {
+        format!(
+            "search:{}:{}:{}:{}",
+            self.inner.provider().code(),
+            language_code(lang),
+            limits,
+            keyword.trim().to_lowercase()
+        )
+    }
+
+    fn title_key(&self, id: &str, lang: Language) -> String {
+        format!(
+            "title:{}:{}:{}",
+            self.inner.provider().code(),
+            language_code(lang),
+            id
+        )
+    }
+
+    // a broken cache must never break the bot, so failures are only logged
+    async fn cached<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        match self.store.get(key).await {
+            Ok(value) => value.and_then(|value| serde_json::from_value(value).ok()),
+            Err(e) => {
+                log::warn!("failed to read movie info cache: {:?}", e);
+                None
+            }
+        }
+    }
+
+    async fn cache<T: Serialize>(&self, key: &str, value: &T) {
+        let result = match serde_json::to_value(value) {
+            Ok(value) => self.store.put(key, value, self.ttl).await,
+            Err(e) => Err(e.into()),
+        };
+
+        if let Err(e) = result {
+            log::warn!("failed to write movie info cache: {:?}", e);
+        }
+    }
+}
+
+#[async_trait]
+impl RequestMovieInfo for CachedApi {
+    async fn search(
+        &self,
+        client: &Client,
+        api_key: &str,
+        keyword: &str,
+        limits: usize,
+        lang: Language,
+    ) -> Result<Vec<SearchResult>, BotError> {
+        let key = self.search_key(keyword, limits, lang);
+
+        if let Some(search_results) = self.cached(&key).await {
+            return Ok(search_results);
+        }
+
+        let search_results = self
+            .inner
+            .search(client, api_key, keyword, limits, lang)
+            .await?;
+        // the title may just not be on the provider yet
+        if !search_results.is_empty() {
+            self.cache(&key, &search_results).await;
+        }
+
+        Ok(search_results)
+    }
+
+    async fn request_movie_information(
+        &self,
+        client: &Client,
+        api_key: &str,
+        id: &str,
+        lang: Language,
+    ) -> Result<MovieInfo, BotError> {
+        let key = self.title_key(id, lang);
+
+        if let Some(movie_info) = self.cached(&key).await {
+            return Ok(movie_info);
+        }
+
+        let movie_info = self
+            .inner
+            .request_movie_information(client, api_key, id, lang)
+            .await?;
+        self.cache(&key, &movie_info).await;
+
+        Ok(movie_info)
+    }
+
+    async fn refresh_movie_information(
+        &self,
+        client: &Client,
+        api_key: &str,
+        id: &str,
+        lang: Language,
+    ) -> Result<MovieInfo, BotError> {
+        let movie_info = self
+            .inner
+            .refresh_movie_information(client, api_key, id, lang)
+            .await?;
+        // later requests get the fresh information too
+        self.cache(&self.title_key(id, lang), &movie_info).await;
+
+        Ok(movie_info)
+    }
+
+    fn provider(&self) -> Provider {
+        self.inner.provider()
+    }
+
+    fn accepts_id(&self, id: &str) -> bool {
+        self.inner.accepts_id(id)
+    }
+
+    fn has_api_key(&self, api_key: &str) -> bool {
+        self.inner.has_api_key(api_key)
+    }
+}
+
+fn language_code(lang: Language) -> &'static str {
+    match lang {
+        Language::En => "en",
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PgCacheStore {
+    pg_pool: PgPool,
+}
+
+impl PgCacheStore {
+    pub fn new(pg_pool: PgPool) -> Self {
+        Self { pg_pool }
+    }
+}
+
+#[async_trait]
+impl CacheStore for PgCacheStore {
+    async fn get(&self, key: &str) -> anyhow::Result<Option<Value>> {
+        let value: Option<Value> = sqlx::query_as::<_, (String,)>(
+            r#"
+SELECT value
+FROM movie_info_cache
+WHERE key = $1 AND expires_at > now()
+        "#,
+        )
+        .bind(key)
+        .fetch_optional(&self.pg_pool)
+        .await?
+        .map(|v| serde_json::from_str(&v.0))
+        .transpose()?;
+
+        Ok(value)
+    }
+
+    async fn put(&self, key: &str, value: Value, ttl: Duration) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+INSERT INTO movie_info_cache ( key, value, expires_at )
+VALUES ( $1, $2, now() + make_interval(secs => $3) )
+ON CONFLICT (key) DO UPDATE
+SET value = EXCLUDED.value, expires_at = EXCLUDED.expires_at
+        "#,
+        )
+        .bind(key)
+        .bind(value.to_string())
+        .bind(ttl.as_secs_f64())
+        .execute(&self.pg_pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn delete_expired(&self) -> anyhow::Result<u64> {
+        let deleted = sqlx::query(
+            r#"
+DELETE FROM movie_info_cache
+WHERE expires_at <= now()
+        "#,
+        )
+        .execute(&self.pg_pool)
+        .await?
+        .rows_affected();
+
+        Ok(deleted)
+    }
+}
+
+/// In-memory cache evicting the least recently used entry once `capacity` is reached.
+pub struct MemoryCacheStore {
+    capacity: usize,
+    cache: Mutex<MemoryCache>,
+}
+
+#[derive(Default)]
+struct MemoryCache {
+    entries: HashMap<String, MemoryCacheEntry>,
+    clock: u64,
+}
+
+struct MemoryCacheEntry {
+    value: Value,
+    expires_at: Instant,
+    last_used: u64,
+}
+
+impl MemoryCacheStore {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            cache: Mutex::new(MemoryCache::default()),
+        }
+    }
+}
+
+#[async_trait]
+impl CacheStore for MemoryCacheStore {
+    async fn get(&self, key: &str) -> anyhow::Result<Option<Value>> {
+        let mut guard = self.cache.lock().map_err(|e| anyhow!(e.to_string()))?;
+        let cache = &mut *guard;
+        cache.clock += 1;
+
+        let expired = match cache.entries.get_mut(key) {
+            Some(entry) if entry.expires_at > Instant::now() => {
+                entry.last_used = cache.clock;
+                return Ok(Some(entry.value.clone()));
+            }
+            Some(_) => true,
+            None => false,
+        };
+
+        if expired {
+            cache.entries.remove(key);
+        }
+
+        Ok(None)
+    }
+
+    async fn put(&self, key: &str, value: Value, ttl: Duration) -> anyhow::Result<()> {
+        let mut guard = self.cache.lock().map_err(|e| anyhow!(e.to_string()))?;
+        let cache = &mut *guard;
+        cache.clock += 1;
+        let entry = MemoryCacheEntry {
+            value,
+            expires_at: Instant::now() + ttl,
+            last_used: cache.clock,
+        };
+        cache.entries.insert(key.to_string(), entry);
+
+        if cache.entries.len() > self.capacity {
+            let least_recently_used = cache
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone());
+
+            if let Some(key) = least_recently_used {
+                cache.entries.remove(&key);
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn delete_expired(&self) -> anyhow::Result<u64> {
+        let mut cache = self.cache.lock().map_err(|e| anyhow!(e.to_string()))?;
+        let now = Instant::now();
+        let len = cache.entries.len();
+        cache.entries.retain(|_, entry| entry.expires_at > now);
+
+        Ok((len - cache.entries.len()) as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct CountingApi {
+        calls: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl RequestMovieInfo for CountingApi {
+        async fn search(
+            &self,
+            _client: &Client,
+            _api_key: &str,
+            keyword: &str,
+            _limits: usize,
+            _lang: Language,
+        ) -> Result<Vec<SearchResult>, BotError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+
+            if keyword == "nothing" {
+                return Ok(Vec::new());
+            }
+
+            Ok(vec![SearchResult {
+                id: "tt0133093".to_string(),
+                title: keyword.to_string(),
+                description: "(1999)".to_string(),
+                link: "https://www.imdb.com/title/tt0133093".to_string(),
+            }])
+        }
+
+        async fn request_movie_information(
+            &self,
+            _client: &Client,
+            _api_key: &str,
+            id: &str,
+            _lang: Language,
+        ) -> Result<MovieInfo, BotError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+
+            Ok(MovieInfo {
+                imdb_link: format!("https://www.imdb.com/title/{}", id),
+                ..MovieInfo::the_matrix()
+            })
+        }
+
+        fn provider(&self) -> Provider {
+            Provider::ImdbApi
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cached_search() {
+        let inner = Arc::new(CountingApi {
+            calls: AtomicUsize::new(0),
+        });
+        let store = Arc::new(MemoryCacheStore::new(10));
+        let api = CachedApi::new(inner.clone(), store, Duration::from_secs(60));
+        let client = Client::new();
+
+        let first = api
+            .search(&client, "", "Matrix", 5, Language::En)
+            .await
+            .unwrap();
+        let second = api
+            .search(&client, "", " matrix", 5, Language::En)
+            .await
+            .unwrap();
+        assert_eq!(first[0].title, "Matrix");
+        assert_eq!(second[0].title, "Matrix");
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 1);
+
+        api.search(&client, "", "matrix", 3, Language::En)
+            .await
+            .unwrap();
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 2);
+
+        // nothing found isn't remembered, the title may show up later
+        for _ in 0..2 {
+            api.search(&client, "", "nothing", 5, Language::En)
+                .await
+                .unwrap();
+        }
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn test_cached_movie_information() {
+        let inner = Arc::new(CountingApi {
+            calls: AtomicUsize::new(0),
+        });
+        let store = Arc::new(MemoryCacheStore::new(10));
+        let api = CachedApi::new(inner.clone(), store, Duration::from_secs(60));
+        let client = Client::new();
+
+        for _ in 0..2 {
+            let movie_info = api
+                .request_movie_information(&client, "", "tt0133093", Language::En)
+                .await
+                .unwrap();
+            assert_eq!(movie_info.title, "The Matrix");
+            assert_eq!(movie_info.imdb_link, "https://www.imdb.com/title/tt0133093");
+        }
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 1);
+
+        api.request_movie_information(&client, "", "tt0234215", Language::En)
+            .await
+            .unwrap();
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 2);
+
+        // refreshing always asks the provider
+        api.refresh_movie_information(&client, "", "tt0133093", Language::En)
+            .await
+            .unwrap();
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_memory_cache_store_evicts_least_recently_used() {
+        let store = MemoryCacheStore::new(2);
+        let ttl = Duration::from_secs(60);
+
+        store.put("a", json!(1), ttl).await.unwrap();
+        store.put("b", json!(2), ttl).await.unwrap();
+        assert_eq!(store.get("a").await.unwrap(), Some(json!(1)));
+
+        store.put("c", json!(3), ttl).await.unwrap();
+        assert_eq!(store.get("a").await.unwrap(), Some(json!(1)));
+        assert!(store.get("b").await.unwrap().is_none());
+        assert_eq!(store.get("c").await.unwrap(), Some(json!(3)));
+    }
+
+    #[tokio::test]
+    async fn test_memory_cache_store_expires_entries() {
+        let store = MemoryCacheStore::new(2);
+
+        store.put("a", json!(1), Duration::ZERO).await.unwrap();
+        assert!(store.get("a").await.unwrap().is_none());
+
+        store.put("b", json!(2), Duration::ZERO).await.unwrap();
+        store
+            .put("c", json!(3), Duration::from_secs(60))
+            .await
+            .unwrap();
+        assert_eq!(store.delete_expired().await.unwrap(), 1);
+        assert_eq!(store.get("c").await.unwrap(), Some(json!(3)));
+    }
+}
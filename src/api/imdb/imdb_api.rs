@@ -1,4 +1,4 @@
-use crate::api::{MovieInfo, RequestMovieInfo, SearchResult};
+use crate::api::{CastMember, MovieInfo, Provider, QuotaExceeded, RequestMovieInfo, SearchResult};
 use crate::error::{feedback_error, feedback_propagate_error, BotError};
 use crate::{transcripts, Language};
 
@@ -12,7 +12,7 @@ use std::fmt::{Display, Formatter};
 
 #[derive(Debug, Clone)]
 pub struct ImdbApi {
-    url: &'static str,
+    url: String,
     default_key: String,
 }
 
@@ -24,7 +24,11 @@ impl ImdbApi {
         }
     }
 
-    #[allow(dead_code)]
+    pub fn with_url(mut self, url: impl Into<String>) -> Self {
+        self.url = url.into().trim_end_matches('/').to_string();
+        self
+    }
+
     fn usage_api(&self, api_key: &str) -> String {
         format!("{}/Usage/{}", self.url, self.api_key_or_default(api_key))
     }
@@ -51,8 +55,9 @@ impl ImdbApi {
             Language::En => "en",
         };
 
+        // the trailer comes with the title at no extra cost
         format!(
-            "{}/{}/API/Title/{}/{}",
+            "{}/{}/API/Title/{}/{}/Trailer",
             self.url,
             lang,
             self.api_key_or_default(api_key),
@@ -60,26 +65,42 @@ impl ImdbApi {
         )
     }
 
-    #[allow(dead_code)]
     pub async fn request_api_usage(
         &self,
         client: &Client,
         api_key: &str,
-    ) -> anyhow::Result<UsageData> {
-        let data =
-            request_data_from_imdb_api(client, &self.usage_api(api_key), Language::En).await?;
+        fb_lang: Language,
+    ) -> Result<UsageData, BotError> {
+        let data = request_data_from_imdb_api(client, &self.usage_api(api_key), fb_lang).await?;
 
         let count = data["count"].as_u64().unwrap_or_default();
         let maximum = data["maximum"].as_u64().unwrap_or_default();
 
         Ok(UsageData::new(count, maximum))
     }
+
+    /// Checks that IMDb-API knows `api_key` before it's stored, a key without quota left today is still good.
+    pub async fn validate_api_key(
+        &self,
+        client: &Client,
+        api_key: &str,
+        fb_lang: Language,
+    ) -> Result<(), BotError> {
+        match self.request_api_usage(client, api_key, fb_lang).await {
+            Ok(_) => Ok(()),
+            Err(e) if e.inner().chain().any(|e| e.is::<QuotaExceeded>()) => Ok(()),
+            Err(BotError::FeedBack(e)) => Err(feedback_error(anyhow!(
+                transcripts::imdb_token_rejected(fb_lang, &e.to_string())
+            ))),
+            Err(e) => Err(e),
+        }
+    }
 }
 
 impl Default for ImdbApi {
     fn default() -> Self {
         Self {
-            url: "https://imdb-api.com",
+            url: "https://imdb-api.com".to_string(),
             default_key: "".to_string(),
         }
     }
@@ -93,23 +114,19 @@ impl RequestMovieInfo for ImdbApi {
         api_key: &str,
         keyword: &str,
         limits: usize,
+        lang: Language,
     ) -> Result<Vec<SearchResult>, BotError> {
         let url = self.search_api(api_key, keyword);
 
-        let data = request_data_from_imdb_api(client, &url, Language::En).await?;
+        let data = request_data_from_imdb_api(client, &url, lang).await?;
         let search_results = match data["results"].as_array() {
             Some(results) => results
                 .iter()
                 .take(limits)
                 .cloned()
                 .filter_map(|result| {
-                    let mut search_result: SearchResult = serde_json::from_value(result).ok()?;
-                    search_result.description = search_result
-                        .description
-                        .split_inclusive(')')
-                        .take(1)
-                        .collect();
-                    Some(search_result)
+                    let search_result: ImdbApiSearchResult = serde_json::from_value(result).ok()?;
+                    Some(search_result.into())
                 })
                 .collect(),
             None => Vec::new(),
@@ -136,6 +153,14 @@ impl RequestMovieInfo for ImdbApi {
 
         Ok(movie_info.into())
     }
+
+    fn provider(&self) -> Provider {
+        Provider::ImdbApi
+    }
+
+    fn has_api_key(&self, api_key: &str) -> bool {
+        !self.api_key_or_default(api_key).is_empty()
+    }
 }
 
 async fn request_data_from_imdb_api(
@@ -156,6 +181,10 @@ async fn request_data_from_imdb_api(
     })?;
 
     if let Some(err) = data["errorMessage"].as_str() {
+        if err.starts_with("Maximum usage") {
+            return Err(feedback_error(anyhow!(QuotaExceeded(err.to_string()))));
+        }
+
         if !err.is_empty() {
             return Err(feedback_error(anyhow!(err.to_string())));
         }
@@ -164,6 +193,27 @@ async fn request_data_from_imdb_api(
     Ok(data)
 }
 
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ImdbApiSearchResult {
+    pub id: String,
+    pub title: String,
+    pub description: String,
+}
+
+impl From<ImdbApiSearchResult> for SearchResult {
+    fn from(result: ImdbApiSearchResult) -> Self {
+        let description = result.description.split_inclusive(')').take(1).collect();
+        let link = format!("https://www.imdb.com/title/{}", result.id);
+
+        Self {
+            id: result.id,
+            title: result.title,
+            description,
+            link,
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ImdbApiMovieInfo {
@@ -181,13 +231,17 @@ pub struct ImdbApiMovieInfo {
     pub plot_local: Option<String>,
     pub director_list: Vec<IdentityObj>,
     pub star_list: Vec<IdentityObj>,
+    #[serde(default)]
+    pub actor_list: Vec<ActorObj>,
     pub genre_list: Vec<KeyValueObj>,
     pub country_list: Vec<KeyValueObj>,
     pub language_list: Vec<KeyValueObj>,
     pub content_rating: Option<String>,
     #[serde(rename = "imDbRating")]
     pub imdb_rating: Option<String>,
+    pub metacritic_rating: Option<String>,
     pub keyword_list: Vec<String>,
+    pub trailer: Option<TrailerObj>,
 }
 
 impl From<ImdbApiMovieInfo> for MovieInfo {
@@ -207,6 +261,14 @@ impl From<ImdbApiMovieInfo> for MovieInfo {
             .map(|d| d.name.to_string())
             .collect();
         let star_list = info.star_list.iter().map(|s| s.name.to_string()).collect();
+        let cast = info
+            .actor_list
+            .into_iter()
+            .map(|a| CastMember {
+                name: a.name,
+                character: a.as_character.unwrap_or_default(),
+            })
+            .collect();
         let genre_list = info
             .genre_list
             .iter()
@@ -224,24 +286,34 @@ impl From<ImdbApiMovieInfo> for MovieInfo {
             .collect();
         let content_rating = info.content_rating.unwrap_or_default();
         let imdb_rating = info.imdb_rating.and_then(|r| r.parse().ok());
+        let metacritic_rating = info.metacritic_rating.and_then(|r| r.parse().ok());
         let imdb_link = format!("https://www.imdb.com/title/{}", info.id);
+        let trailer = info
+            .trailer
+            .and_then(|t| t.link)
+            .filter(|link| !link.is_empty());
 
         Self {
             title,
             movie_type,
             year,
             image,
+            trailer,
             release_date,
             runtime,
             plot,
             director_list,
             star_list,
+            cast,
             genre_list,
             country_list,
             language_list,
             content_rating,
             imdb_rating,
+            rotten_tomatoes_rating: None,
+            metacritic_rating,
             imdb_link,
+            provider: Provider::ImdbApi,
         }
     }
 }
@@ -252,16 +324,18 @@ pub struct UsageData {
 }
 
 impl UsageData {
-    #[allow(dead_code)]
     pub fn new(count: u64, maximum: u64) -> Self {
         Self { count, maximum }
     }
+
+    pub fn percent(&self) -> Option<f64> {
+        (self.maximum != 0).then(|| self.count as f64 / self.maximum as f64 * 100.0)
+    }
 }
 
 impl Display for UsageData {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        if self.maximum != 0 {
-            let percent = self.count as f64 / self.maximum as f64 * 100.0;
+        if let Some(percent) = self.percent() {
             write!(
                 f,
                 "Usage: {:.2}% ({} / {})",
@@ -284,3 +358,71 @@ pub struct IdentityObj {
     pub id: String,
     pub name: String,
 }
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActorObj {
+    pub id: String,
+    pub name: String,
+    pub as_character: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TrailerObj {
+    pub link: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::serve;
+    use axum::extract::Path;
+    use axum::routing::get;
+    use axum::{Json, Router};
+    use serde_json::json;
+
+    /// Serves the usage endpoint on a local port, `k_good` and `k_busy` are the keys it knows.
+    fn mock_usage_endpoint() -> String {
+        let app = Router::new().route(
+            "/Usage/:api_key",
+            get(|Path(api_key): Path<String>| async move {
+                Json(match api_key.as_str() {
+                    "k_good" => json!({ "count": 3, "maximum": 100, "errorMessage": "" }),
+                    "k_busy" => json!({
+                        "count": 100,
+                        "maximum": 100,
+                        "errorMessage": "Maximum usage (100 of 100 per day)"
+                    }),
+                    _ => json!({ "errorMessage": "Invalid API Key" }),
+                })
+            }),
+        );
+
+        serve(app)
+    }
+
+    #[tokio::test]
+    async fn test_validate_api_key() {
+        let api = ImdbApi::new("k_default".to_string()).with_url(mock_usage_endpoint());
+        let client = Client::new();
+
+        assert!(api
+            .validate_api_key(&client, "k_good", Language::En)
+            .await
+            .is_ok());
+        assert!(api
+            .validate_api_key(&client, "k_busy", Language::En)
+            .await
+            .is_ok());
+
+        let error = api
+            .validate_api_key(&client, "k_typo", Language::En)
+            .await
+            .unwrap_err();
+        assert!(matches!(error, BotError::FeedBack(_)));
+        assert_eq!(
+            error.to_string(),
+            transcripts::imdb_token_rejected(Language::En, "Invalid API Key")
+        );
+    }
+}
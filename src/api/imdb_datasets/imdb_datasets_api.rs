@@ -0,0 +1,280 @@
+use crate::api::{CastMember, MovieInfo, Provider, RequestMovieInfo, SearchResult};
+use crate::error::{feedback_error, feedback_propagate_error, BotError};
+use crate::{transcripts, Language};
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use reqwest::Client;
+use sqlx::PgPool;
+
+const MIN_KEYWORD_LENGTH: usize = 3;
+
+/// Answers searches from the IMDb non-commercial datasets imported into our own database,
+/// see [`import_imdb_datasets`](super::import_imdb_datasets).
+#[derive(Debug, Clone)]
+pub struct ImdbDatasets {
+    pg_pool: PgPool,
+}
+
+impl ImdbDatasets {
+    pub fn new(pg_pool: PgPool) -> Self {
+        Self { pg_pool }
+    }
+
+    async fn select_titles(&self, keyword: &str, limits: usize) -> anyhow::Result<Vec<TitleRow>> {
+        // the trigram index can't serve shorter keywords, the query would scan every title
+        if keyword.trim().chars().count() < MIN_KEYWORD_LENGTH {
+            return Ok(Vec::new());
+        }
+
+        let titles = sqlx::query_as(
+            r#"
+SELECT b.tconst, b.title_type, b.primary_title, b.start_year, b.runtime_minutes, b.genres, r.average_rating
+FROM title_basics b
+LEFT JOIN title_ratings r ON r.tconst = b.tconst
+WHERE lower(b.primary_title) LIKE $1
+  AND NOT b.is_adult
+  AND b.title_type IN ('movie', 'tvMovie', 'tvSeries', 'tvMiniSeries', 'tvSpecial', 'short', 'video')
+ORDER BY r.num_votes DESC NULLS LAST
+LIMIT $2
+        "#,
+        )
+        // anywhere in the title, the trigram index makes it fast
+        .bind(format!("%{}%", escape_like_pattern(&keyword.to_lowercase())))
+        .bind(limits as i64)
+        .fetch_all(&self.pg_pool)
+        .await?;
+
+        Ok(titles)
+    }
+
+    async fn select_title(&self, tconst: &str) -> anyhow::Result<Option<TitleRow>> {
+        let title = sqlx::query_as(
+            r#"
+SELECT b.tconst, b.title_type, b.primary_title, b.start_year, b.runtime_minutes, b.genres, r.average_rating
+FROM title_basics b
+LEFT JOIN title_ratings r ON r.tconst = b.tconst
+WHERE b.tconst = $1
+        "#,
+        )
+        .bind(tconst)
+        .fetch_optional(&self.pg_pool)
+        .await?;
+
+        Ok(title)
+    }
+
+    async fn select_principals(&self, tconst: &str) -> anyhow::Result<Vec<Principal>> {
+        let principals = sqlx::query_as::<_, Principal>(
+            r#"
+SELECT p.category, n.primary_name, p.characters
+FROM title_principals p
+JOIN name_basics n ON n.nconst = p.nconst
+WHERE p.tconst = $1
+ORDER BY p.ordering
+        "#,
+        )
+        .bind(tconst)
+        .fetch_all(&self.pg_pool)
+        .await?;
+
+        Ok(principals)
+    }
+}
+
+#[async_trait]
+impl RequestMovieInfo for ImdbDatasets {
+    async fn search(
+        &self,
+        _client: &Client,
+        _api_key: &str,
+        keyword: &str,
+        limits: usize,
+        lang: Language,
+    ) -> Result<Vec<SearchResult>, BotError> {
+        let titles = self
+            .select_titles(keyword, limits)
+            .await
+            .map_err(|e| feedback_propagate_error(e.context(transcripts::database_error(lang))))?;
+
+        Ok(titles.into_iter().map(Into::into).collect())
+    }
+
+    async fn request_movie_information(
+        &self,
+        _client: &Client,
+        _api_key: &str,
+        id: &str,
+        lang: Language,
+    ) -> Result<MovieInfo, BotError> {
+        let title = self
+            .select_title(id)
+            .await
+            .map_err(|e| feedback_propagate_error(e.context(transcripts::database_error(lang))))?
+            .ok_or_else(|| feedback_error(anyhow!(transcripts::invalid_movie_id(lang))))?;
+
+        let principals = self
+            .select_principals(id)
+            .await
+            .map_err(|e| feedback_propagate_error(e.context(transcripts::database_error(lang))))?;
+
+        Ok(title.into_movie_info(principals))
+    }
+
+    fn provider(&self) -> Provider {
+        Provider::ImdbDatasets
+    }
+}
+
+fn escape_like_pattern(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+fn movie_type(title_type: &str) -> String {
+    match title_type {
+        "movie" => "Movie",
+        "tvMovie" => "TVMovie",
+        "tvSeries" => "TVSeries",
+        "tvMiniSeries" => "TVMiniSeries",
+        "tvSpecial" => "TVSpecial",
+        "tvEpisode" => "TVEpisode",
+        "short" => "Short",
+        "video" => "Video",
+        other => other,
+    }
+    .to_string()
+}
+
+/// Category, name and characters of someone working on a title.
+type Principal = (String, String, Vec<String>);
+
+#[derive(Debug, sqlx::FromRow)]
+struct TitleRow {
+    tconst: String,
+    title_type: String,
+    primary_title: String,
+    start_year: Option<i32>,
+    runtime_minutes: Option<i32>,
+    genres: Vec<String>,
+    average_rating: Option<f64>,
+}
+
+impl TitleRow {
+    fn into_movie_info(self, principals: Vec<Principal>) -> MovieInfo {
+        let mut director_list = Vec::new();
+        let mut star_list = Vec::new();
+        let mut cast = Vec::new();
+
+        for (category, name, characters) in principals {
+            match category.as_str() {
+                "director" => director_list.push(name),
+                "actor" | "actress" => {
+                    cast.push(CastMember {
+                        name: name.to_string(),
+                        character: characters.join(" / "),
+                    });
+                    star_list.push(name);
+                }
+                _ => {}
+            }
+        }
+
+        MovieInfo {
+            title: self.primary_title,
+            movie_type: movie_type(&self.title_type),
+            year: self.start_year.map(|y| y as u32),
+            // the datasets carry no posters, plots or release dates
+            image: String::new(),
+            trailer: None,
+            release_date: None,
+            runtime: self.runtime_minutes.map(|rt| rt as u32),
+            plot: String::new(),
+            director_list,
+            star_list,
+            cast,
+            genre_list: self.genres,
+            country_list: Vec::new(),
+            language_list: Vec::new(),
+            content_rating: String::new(),
+            imdb_rating: self.average_rating,
+            rotten_tomatoes_rating: None,
+            metacritic_rating: None,
+            imdb_link: format!("https://www.imdb.com/title/{}", self.tconst),
+            provider: Provider::ImdbDatasets,
+        }
+    }
+}
+
+impl From<TitleRow> for SearchResult {
+    fn from(row: TitleRow) -> Self {
+        let description = row
+            .start_year
+            .map(|year| format!("({})", year))
+            .unwrap_or_default();
+        let link = format!("https://www.imdb.com/title/{}", row.tconst);
+
+        Self {
+            id: row.tconst,
+            title: row.primary_title,
+            description,
+            link,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape_like_pattern() {
+        assert_eq!(escape_like_pattern("100% love"), "100\\% love");
+        assert_eq!(escape_like_pattern("a_b\\c"), "a\\_b\\\\c");
+    }
+
+    #[tokio::test]
+    async fn test_short_keywords_are_not_searched() {
+        // never connects, a query would fail
+        let pg_pool = PgPool::connect_lazy("postgres://localhost/unreachable").unwrap();
+        let api = ImdbDatasets::new(pg_pool);
+
+        assert!(api.select_titles("up", 5).await.unwrap().is_empty());
+        assert!(api.select_titles(" Up ", 5).await.unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_title_row_into_movie_info() {
+        let row = TitleRow {
+            tconst: "tt0133093".to_string(),
+            title_type: "movie".to_string(),
+            primary_title: "The Matrix".to_string(),
+            start_year: Some(1999),
+            runtime_minutes: Some(136),
+            genres: vec!["Action".to_string(), "Sci-Fi".to_string()],
+            average_rating: Some(8.7),
+        };
+        let principal = |category: &str, name: &str, characters: &[&str]| {
+            (
+                category.to_string(),
+                name.to_string(),
+                characters.iter().map(|c| c.to_string()).collect(),
+            )
+        };
+        let principals = vec![
+            principal("actor", "Keanu Reeves", &["Neo"]),
+            principal("actress", "Carrie-Anne Moss", &["Trinity"]),
+            principal("director", "Lana Wachowski", &[]),
+            principal("producer", "Joel Silver", &[]),
+        ];
+
+        let info = row.into_movie_info(principals);
+        assert_eq!(info.movie_type, "Movie");
+        assert_eq!(info.director_list, vec!["Lana Wachowski"]);
+        assert_eq!(info.star_list, vec!["Keanu Reeves", "Carrie-Anne Moss"]);
+        assert_eq!(info.cast[1].character, "Trinity");
+        assert_eq!(info.imdb_rating, Some(8.7));
+        assert_eq!(info.imdb_link, "https://www.imdb.com/title/tt0133093");
+    }
+}
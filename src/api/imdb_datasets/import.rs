@@ -0,0 +1,553 @@
+use anyhow::Context;
+use flate2::read::GzDecoder;
+use sqlx::postgres::PgPoolOptions;
+use sqlx::query_builder::Separated;
+use sqlx::{PgConnection, PgPool, Postgres, QueryBuilder};
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, Lines};
+use std::iter::Skip;
+use std::path::Path;
+
+const BATCH_SIZE: usize = 1000;
+
+type DatasetLines = Skip<Lines<Box<dyn BufRead + Send>>>;
+
+/// Loads the IMDb non-commercial datasets (https://datasets.imdbws.com) found in `dir` into the
+/// database. Files may be gzipped (`title.basics.tsv.gz`) or plain (`title.basics.tsv`).
+///
+/// Rows are upserted, so running it again with newer dumps refreshes the existing data, and rows that
+/// are gone from a dump are deleted. Dumps that haven't changed since the last import are skipped.
+pub async fn import_imdb_datasets(database_url: &str, dir: &Path) -> anyhow::Result<()> {
+    let pg_pool = PgPoolOptions::new()
+        .max_connections(1)
+        .connect(database_url)
+        .await?;
+
+    import_dataset::<NameBasicsRow>(&pg_pool, dir).await?;
+    import_dataset::<TitleBasicsRow>(&pg_pool, dir).await?;
+    import_dataset::<TitleRatingsRow>(&pg_pool, dir).await?;
+    import_dataset::<TitlePrincipalsRow>(&pg_pool, dir).await?;
+
+    Ok(())
+}
+
+async fn import_dataset<R: DatasetRow>(pg_pool: &PgPool, dir: &Path) -> anyhow::Result<()> {
+    // reading and unzipping the dumps blocks, so it runs off the async workers
+    let dir = dir.to_path_buf();
+    let (reader, file_info) =
+        tokio::task::spawn_blocking(move || open_dataset(&dir, R::DATASET)).await??;
+
+    // the keys of the dump are kept in a temporary table, which only lives in one connection
+    let mut conn = pg_pool.acquire().await?;
+
+    if select_dataset_file_info(&mut conn, R::DATASET).await? == Some(file_info) {
+        log::info!(
+            "{} hasn't changed since the last import, skipped",
+            R::DATASET
+        );
+        return Ok(());
+    }
+
+    log::info!("Importing {}...", R::DATASET);
+    create_imported_keys_table::<R>(&mut conn).await?;
+
+    // the first line is the header
+    let mut lines = reader.lines().skip(1);
+    let mut imported_rows = 0;
+
+    loop {
+        let (rest, batch) = tokio::task::spawn_blocking(move || {
+            let batch = read_batch::<R>(&mut lines);
+            (lines, batch)
+        })
+        .await?;
+        lines = rest;
+        let batch = batch?;
+
+        if batch.is_empty() {
+            break;
+        }
+
+        imported_rows += batch.len();
+        upsert_rows(&mut conn, batch).await?;
+
+        if imported_rows % (BATCH_SIZE * 100) == 0 {
+            log::info!("{}: {} rows imported", R::DATASET, imported_rows);
+        }
+    }
+
+    // an empty dump is rather a broken download than IMDb removing everything
+    let deleted_rows = if imported_rows > 0 {
+        delete_rows_not_imported::<R>(&mut conn).await?
+    } else {
+        0
+    };
+
+    update_dataset_file_info(&mut conn, R::DATASET, file_info).await?;
+    log::info!(
+        "{}: done, {} rows imported, {} rows deleted",
+        R::DATASET,
+        imported_rows,
+        deleted_rows
+    );
+
+    Ok(())
+}
+
+/// Parses the next `BATCH_SIZE` rows, fewer at the end of the dump.
+fn read_batch<R: DatasetRow>(lines: &mut DatasetLines) -> anyhow::Result<Vec<R>> {
+    let mut batch = Vec::with_capacity(BATCH_SIZE);
+
+    for line in lines {
+        let line = line.with_context(|| format!("failed to read {}", R::DATASET))?;
+        let fields = line.split('\t').collect::<Vec<_>>();
+
+        if let Some(row) = R::parse(&fields) {
+            batch.push(row);
+        }
+
+        if batch.len() == BATCH_SIZE {
+            break;
+        }
+    }
+
+    Ok(batch)
+}
+
+async fn upsert_rows<R: DatasetRow>(conn: &mut PgConnection, rows: Vec<R>) -> anyhow::Result<()> {
+    let mut query_builder = QueryBuilder::<Postgres>::new(format!(
+        "INSERT INTO imported_keys ( {} ) ",
+        R::KEY.join(", ")
+    ));
+    query_builder.push_values(&rows, |mut separated, row| {
+        row.push_key_binds(&mut separated)
+    });
+    query_builder.build().execute(&mut *conn).await?;
+
+    let mut query_builder = QueryBuilder::<Postgres>::new(R::INSERT);
+    query_builder.push_values(rows, |mut separated, row| row.push_binds(&mut separated));
+    query_builder.push(R::ON_CONFLICT);
+    query_builder.build().execute(&mut *conn).await?;
+
+    Ok(())
+}
+
+async fn create_imported_keys_table<R: DatasetRow>(conn: &mut PgConnection) -> anyhow::Result<()> {
+    sqlx::query("DROP TABLE IF EXISTS imported_keys")
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query(&format!(
+        "CREATE TEMPORARY TABLE imported_keys AS SELECT {} FROM {} WITH NO DATA",
+        R::KEY.join(", "),
+        R::TABLE
+    ))
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Deletes the rows whose keys weren't in the dump, they were removed from IMDb.
+async fn delete_rows_not_imported<R: DatasetRow>(conn: &mut PgConnection) -> anyhow::Result<u64> {
+    let deleted_rows = sqlx::query(&delete_rows_not_imported_query::<R>())
+        .execute(&mut *conn)
+        .await?
+        .rows_affected();
+
+    Ok(deleted_rows)
+}
+
+fn delete_rows_not_imported_query<R: DatasetRow>() -> String {
+    let same_key = R::KEY
+        .iter()
+        .map(|column| format!("k.{} = t.{}", column, column))
+        .collect::<Vec<_>>()
+        .join(" AND ");
+
+    format!(
+        "DELETE FROM {} t WHERE NOT EXISTS ( SELECT 1 FROM imported_keys k WHERE {} )",
+        R::TABLE,
+        same_key
+    )
+}
+
+/// Modification time and size of a dump, used to detect whether it changed since the last import.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct DatasetFileInfo {
+    modified: i64,
+    size: i64,
+}
+
+fn open_dataset(
+    dir: &Path,
+    dataset: &str,
+) -> anyhow::Result<(Box<dyn BufRead + Send>, DatasetFileInfo)> {
+    let gz_path = dir.join(format!("{}.tsv.gz", dataset));
+    let tsv_path = dir.join(format!("{}.tsv", dataset));
+
+    let (file, gzipped) = if gz_path.exists() {
+        (File::open(&gz_path)?, true)
+    } else {
+        let file = File::open(&tsv_path)
+            .with_context(|| format!("can't find {} in {}", dataset, dir.display()))?;
+        (file, false)
+    };
+
+    let metadata = file.metadata()?;
+    let modified = metadata
+        .modified()?
+        .duration_since(std::time::UNIX_EPOCH)?
+        .as_secs() as i64;
+    let file_info = DatasetFileInfo {
+        modified,
+        size: metadata.len() as i64,
+    };
+
+    let reader: Box<dyn BufRead + Send> = if gzipped {
+        Box::new(BufReader::new(GzDecoder::new(file)))
+    } else {
+        Box::new(BufReader::new(file))
+    };
+
+    Ok((reader, file_info))
+}
+
+async fn select_dataset_file_info(
+    conn: &mut PgConnection,
+    dataset: &str,
+) -> anyhow::Result<Option<DatasetFileInfo>> {
+    let file_info = sqlx::query_as::<_, (i64, i64)>(
+        r#"
+SELECT file_modified, file_size
+FROM imdb_dataset_imports
+WHERE dataset = $1
+        "#,
+    )
+    .bind(dataset)
+    .fetch_optional(conn)
+    .await?
+    .map(|(modified, size)| DatasetFileInfo { modified, size });
+
+    Ok(file_info)
+}
+
+async fn update_dataset_file_info(
+    conn: &mut PgConnection,
+    dataset: &str,
+    file_info: DatasetFileInfo,
+) -> anyhow::Result<()> {
+    sqlx::query(
+        r#"
+INSERT INTO imdb_dataset_imports ( dataset, file_modified, file_size )
+VALUES ( $1, $2, $3 )
+ON CONFLICT (dataset) DO UPDATE
+SET file_modified = EXCLUDED.file_modified, file_size = EXCLUDED.file_size
+        "#,
+    )
+    .bind(dataset)
+    .bind(file_info.modified)
+    .bind(file_info.size)
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+trait DatasetRow: Sized + Send + 'static {
+    const DATASET: &'static str;
+    const TABLE: &'static str;
+    /// The primary key of `TABLE`.
+    const KEY: &'static [&'static str];
+    const INSERT: &'static str;
+    const ON_CONFLICT: &'static str;
+
+    fn parse(fields: &[&str]) -> Option<Self>;
+
+    fn push_binds(self, separated: &mut Separated<'_, '_, Postgres, &'static str>);
+
+    fn push_key_binds(&self, separated: &mut Separated<'_, '_, Postgres, &'static str>);
+}
+
+/// The datasets use `\N` for missing values.
+fn nullable(field: &str) -> Option<&str> {
+    (field != "\\N").then_some(field)
+}
+
+fn nullable_i32(field: &str) -> Option<i32> {
+    nullable(field).and_then(|f| f.parse().ok())
+}
+
+#[derive(Debug, PartialEq)]
+struct TitleBasicsRow {
+    tconst: String,
+    title_type: String,
+    primary_title: String,
+    original_title: String,
+    is_adult: bool,
+    start_year: Option<i32>,
+    end_year: Option<i32>,
+    runtime_minutes: Option<i32>,
+    genres: Vec<String>,
+}
+
+impl DatasetRow for TitleBasicsRow {
+    const DATASET: &'static str = "title.basics";
+    const TABLE: &'static str = "title_basics";
+    const KEY: &'static [&'static str] = &["tconst"];
+    const INSERT: &'static str = "INSERT INTO title_basics ( tconst, title_type, primary_title, \
+        original_title, is_adult, start_year, end_year, runtime_minutes, genres ) ";
+    const ON_CONFLICT: &'static str = r#"
+ON CONFLICT (tconst) DO UPDATE
+SET title_type = EXCLUDED.title_type, primary_title = EXCLUDED.primary_title,
+    original_title = EXCLUDED.original_title, is_adult = EXCLUDED.is_adult,
+    start_year = EXCLUDED.start_year, end_year = EXCLUDED.end_year,
+    runtime_minutes = EXCLUDED.runtime_minutes, genres = EXCLUDED.genres
+WHERE (title_basics.title_type, title_basics.primary_title, title_basics.original_title,
+       title_basics.is_adult, title_basics.start_year, title_basics.end_year,
+       title_basics.runtime_minutes, title_basics.genres)
+    IS DISTINCT FROM
+      (EXCLUDED.title_type, EXCLUDED.primary_title, EXCLUDED.original_title, EXCLUDED.is_adult,
+       EXCLUDED.start_year, EXCLUDED.end_year, EXCLUDED.runtime_minutes, EXCLUDED.genres)
+    "#;
+
+    fn parse(fields: &[&str]) -> Option<Self> {
+        match *fields {
+            [tconst, title_type, primary_title, original_title, is_adult, start_year, end_year, runtime_minutes, genres] => {
+                Some(Self {
+                    tconst: tconst.to_string(),
+                    title_type: title_type.to_string(),
+                    primary_title: primary_title.to_string(),
+                    original_title: original_title.to_string(),
+                    is_adult: is_adult == "1",
+                    start_year: nullable_i32(start_year),
+                    end_year: nullable_i32(end_year),
+                    runtime_minutes: nullable_i32(runtime_minutes),
+                    genres: nullable(genres)
+                        .map(|g| g.split(',').map(ToOwned::to_owned).collect())
+                        .unwrap_or_default(),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    fn push_binds(self, separated: &mut Separated<'_, '_, Postgres, &'static str>) {
+        separated
+            .push_bind(self.tconst)
+            .push_bind(self.title_type)
+            .push_bind(self.primary_title)
+            .push_bind(self.original_title)
+            .push_bind(self.is_adult)
+            .push_bind(self.start_year)
+            .push_bind(self.end_year)
+            .push_bind(self.runtime_minutes)
+            .push_bind(self.genres);
+    }
+
+    fn push_key_binds(&self, separated: &mut Separated<'_, '_, Postgres, &'static str>) {
+        separated.push_bind(self.tconst.clone());
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct TitleRatingsRow {
+    tconst: String,
+    average_rating: f64,
+    num_votes: i32,
+}
+
+impl DatasetRow for TitleRatingsRow {
+    const DATASET: &'static str = "title.ratings";
+    const TABLE: &'static str = "title_ratings";
+    const KEY: &'static [&'static str] = &["tconst"];
+    const INSERT: &'static str = "INSERT INTO title_ratings ( tconst, average_rating, num_votes ) ";
+    const ON_CONFLICT: &'static str = r#"
+ON CONFLICT (tconst) DO UPDATE
+SET average_rating = EXCLUDED.average_rating, num_votes = EXCLUDED.num_votes
+WHERE (title_ratings.average_rating, title_ratings.num_votes)
+    IS DISTINCT FROM (EXCLUDED.average_rating, EXCLUDED.num_votes)
+    "#;
+
+    fn parse(fields: &[&str]) -> Option<Self> {
+        match *fields {
+            [tconst, average_rating, num_votes] => Some(Self {
+                tconst: tconst.to_string(),
+                average_rating: average_rating.parse().ok()?,
+                num_votes: num_votes.parse().ok()?,
+            }),
+            _ => None,
+        }
+    }
+
+    fn push_binds(self, separated: &mut Separated<'_, '_, Postgres, &'static str>) {
+        separated
+            .push_bind(self.tconst)
+            .push_bind(self.average_rating)
+            .push_bind(self.num_votes);
+    }
+
+    fn push_key_binds(&self, separated: &mut Separated<'_, '_, Postgres, &'static str>) {
+        separated.push_bind(self.tconst.clone());
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct TitlePrincipalsRow {
+    tconst: String,
+    ordering: i32,
+    nconst: String,
+    category: String,
+    characters: Vec<String>,
+}
+
+impl DatasetRow for TitlePrincipalsRow {
+    const DATASET: &'static str = "title.principals";
+    const TABLE: &'static str = "title_principals";
+    const KEY: &'static [&'static str] = &["tconst", "ordering"];
+    const INSERT: &'static str =
+        "INSERT INTO title_principals ( tconst, ordering, nconst, category, characters ) ";
+    const ON_CONFLICT: &'static str = r#"
+ON CONFLICT (tconst, ordering) DO UPDATE
+SET nconst = EXCLUDED.nconst, category = EXCLUDED.category, characters = EXCLUDED.characters
+WHERE (title_principals.nconst, title_principals.category, title_principals.characters)
+    IS DISTINCT FROM (EXCLUDED.nconst, EXCLUDED.category, EXCLUDED.characters)
+    "#;
+
+    fn parse(fields: &[&str]) -> Option<Self> {
+        match *fields {
+            [tconst, ordering, nconst, category, _job, characters] => {
+                // only directors and the cast end up in `MovieInfo`
+                if !matches!(category, "director" | "actor" | "actress") {
+                    return None;
+                }
+
+                Some(Self {
+                    tconst: tconst.to_string(),
+                    ordering: ordering.parse().ok()?,
+                    nconst: nconst.to_string(),
+                    category: category.to_string(),
+                    characters: nullable(characters)
+                        .and_then(|c| serde_json::from_str(c).ok())
+                        .unwrap_or_default(),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    fn push_binds(self, separated: &mut Separated<'_, '_, Postgres, &'static str>) {
+        separated
+            .push_bind(self.tconst)
+            .push_bind(self.ordering)
+            .push_bind(self.nconst)
+            .push_bind(self.category)
+            .push_bind(self.characters);
+    }
+
+    fn push_key_binds(&self, separated: &mut Separated<'_, '_, Postgres, &'static str>) {
+        separated
+            .push_bind(self.tconst.clone())
+            .push_bind(self.ordering);
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct NameBasicsRow {
+    nconst: String,
+    primary_name: String,
+}
+
+impl DatasetRow for NameBasicsRow {
+    const DATASET: &'static str = "name.basics";
+    const TABLE: &'static str = "name_basics";
+    const KEY: &'static [&'static str] = &["nconst"];
+    const INSERT: &'static str = "INSERT INTO name_basics ( nconst, primary_name ) ";
+    const ON_CONFLICT: &'static str = r#"
+ON CONFLICT (nconst) DO UPDATE
+SET primary_name = EXCLUDED.primary_name
+WHERE name_basics.primary_name IS DISTINCT FROM EXCLUDED.primary_name
+    "#;
+
+    fn parse(fields: &[&str]) -> Option<Self> {
+        match *fields {
+            [nconst, primary_name, ..] => Some(Self {
+                nconst: nconst.to_string(),
+                primary_name: primary_name.to_string(),
+            }),
+            _ => None,
+        }
+    }
+
+    fn push_binds(self, separated: &mut Separated<'_, '_, Postgres, &'static str>) {
+        separated
+            .push_bind(self.nconst)
+            .push_bind(self.primary_name);
+    }
+
+    fn push_key_binds(&self, separated: &mut Separated<'_, '_, Postgres, &'static str>) {
+        separated.push_bind(self.nconst.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_title_basics_row() {
+        let line = "tt0133093\tmovie\tThe Matrix\tThe Matrix\t0\t1999\t\\N\t136\tAction,Sci-Fi";
+        let fields = line.split('\t').collect::<Vec<_>>();
+
+        let row = TitleBasicsRow::parse(&fields).unwrap();
+        assert_eq!(row.tconst, "tt0133093");
+        assert!(!row.is_adult);
+        assert_eq!(row.start_year, Some(1999));
+        assert_eq!(row.end_year, None);
+        assert_eq!(row.runtime_minutes, Some(136));
+        assert_eq!(row.genres, vec!["Action", "Sci-Fi"]);
+
+        assert!(TitleBasicsRow::parse(&["tt0133093", "movie"]).is_none());
+    }
+
+    #[test]
+    fn test_parse_title_principals_row() {
+        let line = "tt0133093\t1\tnm0000206\tactor\t\\N\t[\"Neo\"]";
+        let fields = line.split('\t').collect::<Vec<_>>();
+
+        let row = TitlePrincipalsRow::parse(&fields).unwrap();
+        assert_eq!(row.ordering, 1);
+        assert_eq!(row.characters, vec!["Neo"]);
+
+        let line = "tt0133093\t9\tnm0005428\tproducer\tproducer\t\\N";
+        let fields = line.split('\t').collect::<Vec<_>>();
+        assert!(TitlePrincipalsRow::parse(&fields).is_none());
+    }
+
+    #[test]
+    fn test_parse_title_ratings_row() {
+        assert_eq!(
+            TitleRatingsRow::parse(&["tt0133093", "8.7", "1906073"]),
+            Some(TitleRatingsRow {
+                tconst: "tt0133093".to_string(),
+                average_rating: 8.7,
+                num_votes: 1906073,
+            })
+        );
+        assert!(TitleRatingsRow::parse(&["tt0133093", "\\N", "0"]).is_none());
+    }
+
+    #[test]
+    fn test_delete_rows_not_imported_query() {
+        assert_eq!(
+            delete_rows_not_imported_query::<TitleBasicsRow>(),
+            "DELETE FROM title_basics t WHERE NOT EXISTS \
+            ( SELECT 1 FROM imported_keys k WHERE k.tconst = t.tconst )"
+        );
+        assert_eq!(
+            delete_rows_not_imported_query::<TitlePrincipalsRow>(),
+            "DELETE FROM title_principals t WHERE NOT EXISTS \
+            ( SELECT 1 FROM imported_keys k WHERE k.tconst = t.tconst AND k.ordering = t.ordering )"
+        );
+    }
+}
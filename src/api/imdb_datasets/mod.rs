@@ -0,0 +1,5 @@
+mod imdb_datasets_api;
+mod import;
+
+pub use imdb_datasets_api::ImdbDatasets;
+pub use import::import_imdb_datasets;
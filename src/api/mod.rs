@@ -1,18 +1,41 @@
+mod cache;
 mod imdb;
+mod imdb_datasets;
 mod notion;
+mod omdb;
+mod provider_chain;
+mod tmdb;
 
+pub use cache::{
+    clean_up_movie_info_cache, CacheBackend, CachedApi, MemoryCacheStore, MovieInfoCache,
+    PgCacheStore,
+};
 pub use imdb::ImdbApi;
+pub use imdb_datasets::{import_imdb_datasets, ImdbDatasets};
 pub use notion::{
-    create_database, insert_movie_info_to_notion_database, parse_notion_page_id_from_user_input,
+    check_and_repair_database, create_database, find_movie_in_notion_database,
+    insert_movie_info_to_notion_database, parse_notion_page_id_from_user_input,
+    query_movie_pages_in_notion_database, refresh_movie_info_in_notion_page, resolve_property,
+    retrieve_database_schema, retrieve_notion_page, update_diary_in_notion_page,
+    update_movie_info_in_notion_page, validate_notion_token, DatabaseTemplate, DiaryEntry,
+    MovieField, NotionOAuth, NotionValidationError, PageObj, PropertyMapping, PropertySpec,
+    PropertyType, SchemaCheck, DATABASE_ICONS, MAX_RATING, NOTION_OAUTH_REDIRECT_PATH,
 };
+pub use omdb::OmdbApi;
+pub use provider_chain::ProviderChain;
+pub use tmdb::TmdbApi;
 
 use crate::error::BotError;
 use crate::Language;
+use anyhow::anyhow;
 use async_trait::async_trait;
 use chrono::NaiveDate;
-use reqwest::Client;
+use reqwest::{Client, Url};
 use serde::{Deserialize, Serialize};
 
+use std::str::FromStr;
+use thiserror::Error;
+
 #[async_trait]
 pub trait RequestMovieInfo {
     async fn search(
@@ -21,6 +44,7 @@ pub trait RequestMovieInfo {
         api_key: &str,
         keyword: &str,
         limits: usize,
+        lang: Language,
     ) -> Result<Vec<SearchResult>, BotError>;
 
     async fn request_movie_information(
@@ -30,6 +54,118 @@ pub trait RequestMovieInfo {
         id: &str,
         lang: Language,
     ) -> Result<MovieInfo, BotError>;
+
+    /// Fetches the title from the provider itself, bypassing any cache in between.
+    async fn refresh_movie_information(
+        &self,
+        client: &Client,
+        api_key: &str,
+        id: &str,
+        lang: Language,
+    ) -> Result<MovieInfo, BotError> {
+        self.request_movie_information(client, api_key, id, lang)
+            .await
+    }
+
+    fn provider(&self) -> Provider;
+
+    /// Whether `id` is a title id this provider understands, IMDb ids by default.
+    fn accepts_id(&self, id: &str) -> bool {
+        is_imdb_id(id)
+    }
+
+    /// Whether there is a key to call the provider with, either `api_key` or its default key.
+    fn has_api_key(&self, _api_key: &str) -> bool {
+        true
+    }
+}
+
+pub fn is_imdb_id(id: &str) -> bool {
+    id.strip_prefix("tt")
+        .map(|digits| !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit()))
+        .unwrap_or(false)
+}
+
+/// Recovers the id of a title from the link stored in the movie list.
+pub fn movie_id_from_link(link: &str) -> Option<String> {
+    let url = Url::parse(link).ok()?;
+    let segments = url
+        .path_segments()?
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>();
+
+    match (url.domain()?, segments.as_slice()) {
+        (domain, ["title", id]) if domain.ends_with("imdb.com") && is_imdb_id(id) => {
+            Some(id.to_string())
+        }
+        (domain, [media_type, id]) if domain.ends_with("themoviedb.org") => {
+            Some(format!("{}:{}/{}", Provider::Tmdb.code(), media_type, id))
+        }
+        _ => None,
+    }
+}
+
+/// The link a title is stored with in the movie list, from its IMDb id or a link to it.
+pub fn movie_link_from_user_input(input: &str) -> Option<String> {
+    let input = input.trim();
+    let id = if is_imdb_id(input) {
+        input.to_string()
+    } else {
+        movie_id_from_link(input)?
+    };
+
+    // titles without an IMDb id are stored with their TMDb link
+    match id.strip_prefix(&format!("{}:", Provider::Tmdb.code())) {
+        Some(path) => Some(format!("https://www.themoviedb.org/{}", path)),
+        None => Some(format!("https://www.imdb.com/title/{}", id)),
+    }
+}
+
+/// Returned by a provider when the API key has run out of its quota.
+#[derive(Debug, Error)]
+#[error("{0}")]
+pub struct QuotaExceeded(pub String);
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum Provider {
+    ImdbApi,
+    Tmdb,
+    Omdb,
+    ImdbDatasets,
+}
+
+impl Provider {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Provider::ImdbApi => "IMDb-API",
+            Provider::Tmdb => "TMDb",
+            Provider::Omdb => "OMDb",
+            Provider::ImdbDatasets => "IMDb datasets",
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            Provider::ImdbApi => "imdb_api",
+            Provider::Tmdb => "tmdb",
+            Provider::Omdb => "omdb",
+            Provider::ImdbDatasets => "imdb_datasets",
+        }
+    }
+}
+
+impl FromStr for Provider {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "imdb_api" | "imdb-api" | "imdbapi" => Ok(Provider::ImdbApi),
+            "tmdb" => Ok(Provider::Tmdb),
+            "omdb" => Ok(Provider::Omdb),
+            "imdb_datasets" | "imdb-datasets" => Ok(Provider::ImdbDatasets),
+            _ => Err(anyhow!("unknown movie information provider `{}`", s)),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -37,23 +173,123 @@ pub struct SearchResult {
     pub id: String,
     pub title: String,
     pub description: String,
+    pub link: String,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct MovieInfo {
     pub title: String,
     pub movie_type: String,
     pub year: Option<u32>,
     pub image: String,
+    /// A link to a trailer of the title.
+    #[serde(default)]
+    pub trailer: Option<String>,
     pub release_date: Option<NaiveDate>,
     pub runtime: Option<u32>,
     pub plot: String,
     pub director_list: Vec<String>,
     pub star_list: Vec<String>,
+    /// The cast with their characters, top billed first.
+    #[serde(default)]
+    pub cast: Vec<CastMember>,
     pub genre_list: Vec<String>,
     pub country_list: Vec<String>,
     pub language_list: Vec<String>,
     pub content_rating: String,
     pub imdb_rating: Option<f64>,
+    pub rotten_tomatoes_rating: Option<u32>,
+    pub metacritic_rating: Option<u32>,
     pub imdb_link: String,
+    pub provider: Provider,
+}
+
+#[cfg(test)]
+impl MovieInfo {
+    /// The Matrix with its basic details, tests fill in the fields they look at.
+    pub fn the_matrix() -> Self {
+        Self {
+            title: "The Matrix".to_string(),
+            movie_type: "Movie".to_string(),
+            year: Some(1999),
+            image: String::new(),
+            trailer: None,
+            release_date: NaiveDate::from_ymd_opt(1999, 3, 31),
+            runtime: Some(136),
+            plot: String::new(),
+            director_list: Vec::new(),
+            star_list: Vec::new(),
+            cast: Vec::new(),
+            genre_list: Vec::new(),
+            country_list: Vec::new(),
+            language_list: Vec::new(),
+            content_rating: String::new(),
+            imdb_rating: Some(8.7),
+            rotten_tomatoes_rating: None,
+            metacritic_rating: None,
+            imdb_link: "https://www.imdb.com/title/tt0133093".to_string(),
+            provider: Provider::ImdbApi,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct CastMember {
+    pub name: String,
+    /// Empty if the provider doesn't know who they play.
+    pub character: String,
+}
+
+/// Serves `router` on a free local port for mocking an API, returns its base url.
+#[cfg(test)]
+pub fn serve(router: axum::Router) -> String {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(
+        axum::Server::from_tcp(listener)
+            .unwrap()
+            .serve(router.into_make_service()),
+    );
+
+    url
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_movie_id_from_link() {
+        assert_eq!(
+            movie_id_from_link("https://www.imdb.com/title/tt0133093").unwrap(),
+            "tt0133093"
+        );
+        assert_eq!(
+            movie_id_from_link("https://www.imdb.com/title/tt0133093/").unwrap(),
+            "tt0133093"
+        );
+        assert_eq!(
+            movie_id_from_link("https://www.themoviedb.org/tv/1399").unwrap(),
+            "tmdb:tv/1399"
+        );
+        assert!(movie_id_from_link("https://www.imdb.com/name/nm0000206").is_none());
+        assert!(movie_id_from_link("https://example.com/title/tt0133093").is_none());
+        assert!(movie_id_from_link("tt0133093").is_none());
+    }
+
+    #[test]
+    fn test_movie_link_from_user_input() {
+        let link = "https://www.imdb.com/title/tt0133093";
+        assert_eq!(movie_link_from_user_input(" tt0133093 ").unwrap(), link);
+        assert_eq!(
+            movie_link_from_user_input("https://m.imdb.com/title/tt0133093/?ref_=nv_sr_srsg_0")
+                .unwrap(),
+            link
+        );
+        assert_eq!(
+            movie_link_from_user_input("https://www.themoviedb.org/tv/1399").unwrap(),
+            "https://www.themoviedb.org/tv/1399"
+        );
+        assert!(movie_link_from_user_input("The Matrix").is_none());
+    }
 }
@@ -0,0 +1,634 @@
+use crate::api::notion::objects::*;
+use crate::api::notion::schema::{DatabaseSchemaObj, PropertyType};
+use crate::api::MovieInfo;
+use crate::error::{feedback_error, BotError};
+use crate::{transcripts, Language};
+
+use anyhow::anyhow;
+use chrono::NaiveDate;
+use serde_json::Value;
+use std::str::FromStr;
+
+/// The information of a title that can be written to the movie list,
+/// including what the user keeps track of themselves.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum MovieField {
+    Title,
+    Type,
+    Year,
+    ReleaseDate,
+    Runtime,
+    Plot,
+    Director,
+    Star,
+    Genre,
+    Country,
+    Language,
+    ContentRating,
+    ImdbRating,
+    RottenTomatoes,
+    Metacritic,
+    ImdbLink,
+    Watched,
+    WatchedOn,
+    MyRating,
+}
+
+impl MovieField {
+    pub const ALL: [MovieField; 19] = [
+        MovieField::Title,
+        MovieField::Type,
+        MovieField::Year,
+        MovieField::ReleaseDate,
+        MovieField::Runtime,
+        MovieField::Plot,
+        MovieField::Director,
+        MovieField::Star,
+        MovieField::Genre,
+        MovieField::Country,
+        MovieField::Language,
+        MovieField::ContentRating,
+        MovieField::ImdbRating,
+        MovieField::RottenTomatoes,
+        MovieField::Metacritic,
+        MovieField::ImdbLink,
+        MovieField::Watched,
+        MovieField::WatchedOn,
+        MovieField::MyRating,
+    ];
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            MovieField::Title => "title",
+            MovieField::Type => "type",
+            MovieField::Year => "year",
+            MovieField::ReleaseDate => "release_date",
+            MovieField::Runtime => "runtime",
+            MovieField::Plot => "plot",
+            MovieField::Director => "director",
+            MovieField::Star => "star",
+            MovieField::Genre => "genre",
+            MovieField::Country => "country",
+            MovieField::Language => "language",
+            MovieField::ContentRating => "content_rating",
+            MovieField::ImdbRating => "imdb_rating",
+            MovieField::RottenTomatoes => "rotten_tomatoes",
+            MovieField::Metacritic => "metacritic",
+            MovieField::ImdbLink => "imdb_link",
+            MovieField::Watched => "watched",
+            MovieField::WatchedOn => "watched_on",
+            MovieField::MyRating => "my_rating",
+        }
+    }
+
+    /// The property name `create_database` uses.
+    pub fn default_name(&self) -> &'static str {
+        match self {
+            MovieField::Title => "Title",
+            MovieField::Type => "Type",
+            MovieField::Year => "Year",
+            MovieField::ReleaseDate => "Release Date",
+            MovieField::Runtime => "Runtime",
+            MovieField::Plot => "Plot",
+            MovieField::Director => "Director",
+            MovieField::Star => "Star",
+            MovieField::Genre => "Genre",
+            MovieField::Country => "Country",
+            MovieField::Language => "Language",
+            MovieField::ContentRating => "Content Rating",
+            MovieField::ImdbRating => "IMDb Rating",
+            MovieField::RottenTomatoes => "Rotten Tomatoes",
+            MovieField::Metacritic => "Metacritic",
+            MovieField::ImdbLink => "IMDb Link",
+            MovieField::Watched => "Watched",
+            MovieField::WatchedOn => "Watched On",
+            MovieField::MyRating => "My Rating",
+        }
+    }
+
+    /// The property types the field can be written to, the first one is the default.
+    pub fn property_types(&self) -> &'static [PropertyType] {
+        match self {
+            // the title is the only property a database always has
+            MovieField::Title => &[PropertyType::Title],
+            // the link is how pages are found again
+            MovieField::ImdbLink => &[PropertyType::Url],
+            MovieField::Type | MovieField::ContentRating => &[
+                PropertyType::Select,
+                PropertyType::RichText,
+                PropertyType::MultiSelect,
+            ],
+            MovieField::Year
+            | MovieField::Runtime
+            | MovieField::ImdbRating
+            | MovieField::RottenTomatoes
+            | MovieField::Metacritic => &[PropertyType::Number, PropertyType::RichText],
+            MovieField::ReleaseDate => &[PropertyType::Date, PropertyType::RichText],
+            MovieField::Plot => &[PropertyType::RichText],
+            MovieField::Watched => &[PropertyType::Checkbox],
+            MovieField::WatchedOn => &[PropertyType::Date],
+            MovieField::MyRating => &[PropertyType::Number, PropertyType::Select],
+            MovieField::Director
+            | MovieField::Star
+            | MovieField::Genre
+            | MovieField::Country
+            | MovieField::Language => &[
+                PropertyType::MultiSelect,
+                PropertyType::Select,
+                PropertyType::RichText,
+            ],
+        }
+    }
+
+    fn default_property_type(&self) -> PropertyType {
+        match self {
+            // written as text before it could be mapped
+            MovieField::ContentRating => PropertyType::RichText,
+            _ => self.property_types()[0],
+        }
+    }
+
+    /// The property `create_database` makes for the field.
+    pub fn default_property(&self) -> PropertySpec {
+        PropertySpec {
+            name: self.default_name().to_string(),
+            property_type: self.default_property_type(),
+        }
+    }
+
+    /// Whether the bot can't work without the field, so it can't be left out.
+    pub fn is_required(&self) -> bool {
+        matches!(self, MovieField::Title | MovieField::ImdbLink)
+    }
+
+    /// Whether the field is filled in by the user rather than the bot.
+    pub fn is_personal(&self) -> bool {
+        matches!(
+            self,
+            MovieField::Watched | MovieField::WatchedOn | MovieField::MyRating
+        )
+    }
+
+    /// Whether movie lists from before the field existed only get it when the user asks for it,
+    /// Notion rejects pages with properties the database doesn't have.
+    pub fn is_opt_in(&self) -> bool {
+        self.is_personal() || matches!(self, MovieField::RottenTomatoes | MovieField::Metacritic)
+    }
+
+    fn value(&self, movie_info: &MovieInfo) -> Option<FieldValue> {
+        let text = |text: &str| (!text.is_empty()).then(|| FieldValue::Text(text.to_string()));
+        let list = |list: &[String]| (!list.is_empty()).then(|| FieldValue::List(list.to_vec()));
+
+        match self {
+            MovieField::Title => text(&movie_info.title),
+            MovieField::Type => text(&movie_info.movie_type),
+            MovieField::Year => movie_info.year.map(FieldValue::Integer),
+            MovieField::ReleaseDate => movie_info.release_date.map(FieldValue::Date),
+            MovieField::Runtime => movie_info.runtime.map(FieldValue::Integer),
+            MovieField::Plot => text(&movie_info.plot),
+            MovieField::Director => list(&movie_info.director_list),
+            MovieField::Star => list(&movie_info.star_list),
+            MovieField::Genre => list(&movie_info.genre_list),
+            MovieField::Country => list(&movie_info.country_list),
+            MovieField::Language => list(&movie_info.language_list),
+            MovieField::ContentRating => text(&movie_info.content_rating),
+            MovieField::ImdbRating => movie_info.imdb_rating.map(FieldValue::Decimal),
+            MovieField::RottenTomatoes => {
+                movie_info.rotten_tomatoes_rating.map(FieldValue::Integer)
+            }
+            MovieField::Metacritic => movie_info.metacritic_rating.map(FieldValue::Integer),
+            MovieField::ImdbLink => Some(FieldValue::Url(movie_info.imdb_link.to_string())),
+            // filled in with the diary entries of the user
+            MovieField::Watched | MovieField::WatchedOn | MovieField::MyRating => None,
+        }
+    }
+}
+
+impl FromStr for MovieField {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_lowercase();
+        MovieField::ALL
+            .into_iter()
+            .find(|field| field.code() == s)
+            .ok_or(())
+    }
+}
+
+/// The highest rating `DiaryEntry::Rating` can give.
+pub const MAX_RATING: u32 = 10;
+
+/// What the user tells about a title in the movie list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiaryEntry {
+    Watched(NaiveDate),
+    Rating(u32),
+}
+
+impl DiaryEntry {
+    /// The fields the entry is written to.
+    pub fn fields(&self) -> &'static [MovieField] {
+        match self {
+            DiaryEntry::Watched(_) => &[MovieField::Watched, MovieField::WatchedOn],
+            DiaryEntry::Rating(_) => &[MovieField::MyRating],
+        }
+    }
+
+    fn value(&self, field: MovieField) -> Option<FieldValue> {
+        match (self, field) {
+            (DiaryEntry::Watched(_), MovieField::Watched) => Some(FieldValue::Checkbox(true)),
+            (DiaryEntry::Watched(date), MovieField::WatchedOn) => Some(FieldValue::Date(*date)),
+            (DiaryEntry::Rating(rating), MovieField::MyRating) => {
+                Some(FieldValue::Integer(*rating))
+            }
+            _ => None,
+        }
+    }
+}
+
+enum FieldValue {
+    Text(String),
+    List(Vec<String>),
+    Integer(u32),
+    Decimal(f64),
+    Date(NaiveDate),
+    Url(String),
+    Checkbox(bool),
+}
+
+impl FieldValue {
+    fn property_object(&self, property_type: PropertyType) -> Option<Value> {
+        let property = match (property_type, self) {
+            (PropertyType::Title, FieldValue::Text(text)) => title_database_property_object(text),
+            (PropertyType::RichText, FieldValue::Text(text) | FieldValue::Url(text)) => {
+                text_database_property_object(text)
+            }
+            (PropertyType::RichText, FieldValue::List(list)) => {
+                text_database_property_object(&list.join(", "))
+            }
+            (PropertyType::RichText, FieldValue::Integer(number)) => {
+                text_database_property_object(&number.to_string())
+            }
+            (PropertyType::RichText, FieldValue::Decimal(number)) => {
+                text_database_property_object(&number.to_string())
+            }
+            (PropertyType::RichText, FieldValue::Date(date)) => {
+                text_database_property_object(&date.to_string())
+            }
+            (PropertyType::Select, FieldValue::Text(text)) => select_database_property_object(text),
+            (PropertyType::Select, FieldValue::List(list)) => {
+                select_database_property_object(list.first()?)
+            }
+            (PropertyType::Select, FieldValue::Integer(number)) => {
+                select_database_property_object(&number.to_string())
+            }
+            (PropertyType::MultiSelect, FieldValue::Text(text)) => {
+                multi_select_database_property_object(&[text.to_string()])
+            }
+            (PropertyType::MultiSelect, FieldValue::List(list)) => {
+                multi_select_database_property_object(list)
+            }
+            (PropertyType::Number, FieldValue::Integer(number)) => {
+                u32_number_database_property_object(*number)
+            }
+            (PropertyType::Number, FieldValue::Decimal(number)) => {
+                f64_number_database_property_object(*number)
+            }
+            (PropertyType::Date, FieldValue::Date(date)) => date_database_property_object(date),
+            (PropertyType::Url, FieldValue::Url(url)) => url_database_property_object(url),
+            (PropertyType::Checkbox, FieldValue::Checkbox(checked)) => {
+                checkbox_database_property_object(*checked)
+            }
+            _ => return None,
+        };
+
+        Some(property)
+    }
+}
+
+/// A property of the movie list.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PropertySpec {
+    pub name: String,
+    pub property_type: PropertyType,
+}
+
+/// Which property of the movie list each piece of information goes to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PropertyMapping {
+    properties: Vec<(MovieField, Option<PropertySpec>)>,
+}
+
+impl Default for PropertyMapping {
+    fn default() -> Self {
+        let properties = MovieField::ALL
+            .into_iter()
+            .map(|field| {
+                let property = (!field.is_opt_in()).then(|| field.default_property());
+                (field, property)
+            })
+            .collect();
+
+        Self { properties }
+    }
+}
+
+impl PropertyMapping {
+    /// The property `field` goes to, `None` if the user left it out.
+    pub fn get(&self, field: MovieField) -> Option<&PropertySpec> {
+        self.properties
+            .iter()
+            .find(|(f, _)| *f == field)
+            .and_then(|(_, property)| property.as_ref())
+    }
+
+    pub fn set(&mut self, field: MovieField, property: Option<PropertySpec>) {
+        if let Some((_, p)) = self.properties.iter_mut().find(|(f, _)| *f == field) {
+            *p = property;
+        }
+    }
+
+    /// The properties that are written to, in the order of `MovieField::ALL`.
+    pub fn properties(&self) -> impl Iterator<Item = (MovieField, &PropertySpec)> {
+        self.properties
+            .iter()
+            .filter_map(|(field, property)| property.as_ref().map(|property| (*field, property)))
+    }
+
+    /// The properties of a page with the information of `fields`.
+    pub fn properties_object(&self, movie_info: &MovieInfo, fields: &[MovieField]) -> Value {
+        let properties = self
+            .properties()
+            .filter(|(field, _)| fields.contains(field))
+            .filter_map(|(field, property)| {
+                field
+                    .value(movie_info)
+                    .and_then(|value| value.property_object(property.property_type))
+                    .map(|value| (property.name.to_string(), value))
+            })
+            .collect::<serde_json::Map<_, _>>();
+
+        Value::Object(properties)
+    }
+
+    /// The properties of a page with `entry`, the fields left out of the list are skipped.
+    pub fn diary_properties_object(&self, entry: &DiaryEntry) -> Value {
+        let properties = entry
+            .fields()
+            .iter()
+            .filter_map(|field| {
+                let property = self.get(*field)?;
+                entry
+                    .value(*field)
+                    .and_then(|value| value.property_object(property.property_type))
+                    .map(|value| (property.name.to_string(), value))
+            })
+            .collect::<serde_json::Map<_, _>>();
+
+        Value::Object(properties)
+    }
+
+    pub fn summary(&self, lang: Language) -> String {
+        self.properties
+            .iter()
+            .map(|(field, property)| match property {
+                Some(property) => transcripts::property_mapping_entry(
+                    lang,
+                    field.code(),
+                    &property.name,
+                    property.property_type.as_str(),
+                ),
+                None => transcripts::property_mapping_omitted_entry(lang, field.code()),
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Finds the property `name` in the database and checks that `field` can be written to it.
+pub fn resolve_property(
+    field: MovieField,
+    name: &str,
+    property_type: Option<PropertyType>,
+    schema: &DatabaseSchemaObj,
+    fb_lang: Language,
+) -> Result<PropertySpec, BotError> {
+    let property = schema.properties.get(name).ok_or_else(|| {
+        feedback_error(anyhow!(transcripts::notion_property_not_found(
+            fb_lang, name
+        )))
+    })?;
+
+    let actual_type = property
+        .property_type
+        .parse::<PropertyType>()
+        .ok()
+        .filter(|actual_type| field.property_types().contains(actual_type))
+        .ok_or_else(|| {
+            feedback_error(anyhow!(transcripts::unsupported_property_type(
+                fb_lang,
+                field.code(),
+                &property.property_type,
+                &property_types_to_string(field.property_types()),
+            )))
+        })?;
+
+    if let Some(property_type) = property_type {
+        if property_type != actual_type {
+            return Err(feedback_error(anyhow!(
+                transcripts::notion_property_type_differs(
+                    fb_lang,
+                    name,
+                    property_type.as_str(),
+                    actual_type.as_str()
+                )
+            )));
+        }
+    }
+
+    Ok(PropertySpec {
+        name: name.to_string(),
+        property_type: actual_type,
+    })
+}
+
+fn property_types_to_string(property_types: &[PropertyType]) -> String {
+    property_types
+        .iter()
+        .map(PropertyType::as_str)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    fn movie_info() -> MovieInfo {
+        MovieInfo {
+            director_list: vec!["Lana Wachowski".to_string(), "Lilly Wachowski".to_string()],
+            genre_list: vec!["Action".to_string(), "Sci-Fi".to_string()],
+            content_rating: "R".to_string(),
+            ..MovieInfo::the_matrix()
+        }
+    }
+
+    #[test]
+    fn test_default_properties_object() {
+        let properties =
+            PropertyMapping::default().properties_object(&movie_info(), &MovieField::ALL);
+
+        assert_eq!(properties["Year"], json!({ "number": 1999 }));
+        assert_eq!(properties["IMDb Rating"], json!({ "number": 8.7 }));
+        assert_eq!(
+            properties["Content Rating"]["rich_text"][0]["text"]["content"],
+            "R"
+        );
+        assert_eq!(properties["Genre"]["multi_select"][1]["name"], "Sci-Fi");
+        assert_eq!(
+            properties["IMDb Link"]["url"],
+            "https://www.imdb.com/title/tt0133093"
+        );
+        // empty information is left out
+        assert!(properties.get("Plot").is_none());
+        assert!(properties.get("Metacritic").is_none());
+        assert!(properties.get("Watched").is_none());
+    }
+
+    #[test]
+    fn test_mapped_properties_object() {
+        let mut mapping = PropertyMapping::default();
+        mapping.set(
+            MovieField::Director,
+            Some(PropertySpec {
+                name: "Regisseur".to_string(),
+                property_type: PropertyType::RichText,
+            }),
+        );
+        mapping.set(
+            MovieField::Genre,
+            Some(PropertySpec {
+                name: "Genre".to_string(),
+                property_type: PropertyType::Select,
+            }),
+        );
+        mapping.set(MovieField::Year, None);
+
+        let properties = mapping.properties_object(&movie_info(), &MovieField::ALL);
+        assert_eq!(
+            properties["Regisseur"]["rich_text"][0]["text"]["content"],
+            "Lana Wachowski, Lilly Wachowski"
+        );
+        assert_eq!(properties["Genre"]["select"]["name"], "Action");
+        assert!(properties.get("Director").is_none());
+        assert!(properties.get("Year").is_none());
+
+        let properties = mapping.properties_object(&movie_info(), &[MovieField::ImdbRating]);
+        assert_eq!(properties, json!({ "IMDb Rating": { "number": 8.7 } }));
+    }
+
+    #[test]
+    fn test_diary_properties_object() {
+        let mut mapping = PropertyMapping::default();
+        assert_eq!(
+            mapping.diary_properties_object(&DiaryEntry::Rating(8)),
+            json!({})
+        );
+
+        for field in [
+            MovieField::Watched,
+            MovieField::WatchedOn,
+            MovieField::MyRating,
+        ] {
+            mapping.set(field, Some(field.default_property()));
+        }
+        let watched_on = NaiveDate::from_ymd_opt(2022, 11, 12).unwrap();
+
+        assert_eq!(
+            mapping.diary_properties_object(&DiaryEntry::Watched(watched_on)),
+            json!({
+                "Watched": { "checkbox": true },
+                "Watched On": { "date": { "start": "2022-11-12" } }
+            })
+        );
+        assert_eq!(
+            mapping.diary_properties_object(&DiaryEntry::Rating(8)),
+            json!({ "My Rating": { "number": 8 } })
+        );
+
+        mapping.set(
+            MovieField::MyRating,
+            Some(PropertySpec {
+                name: "Score".to_string(),
+                property_type: PropertyType::Select,
+            }),
+        );
+        mapping.set(MovieField::WatchedOn, None);
+        assert_eq!(
+            mapping.diary_properties_object(&DiaryEntry::Rating(8)),
+            json!({ "Score": { "type": "select", "select": { "name": "8" } } })
+        );
+        assert_eq!(
+            mapping.diary_properties_object(&DiaryEntry::Watched(watched_on)),
+            json!({ "Watched": { "checkbox": true } })
+        );
+    }
+
+    #[test]
+    fn test_resolve_property() {
+        let schema: DatabaseSchemaObj = serde_json::from_value(json!({
+            "id": "bc1211ca-e3f1-4939-ae34-5260b16f627c",
+            "properties": {
+                "Name": { "id": "title", "type": "title", "title": {} },
+                "Regisseur": { "id": "abc", "type": "multi_select", "multi_select": {} },
+                "Watched": { "id": "def", "type": "checkbox", "checkbox": {} }
+            }
+        }))
+        .unwrap();
+        let lang = Language::default();
+
+        assert_eq!(
+            resolve_property(MovieField::Director, "Regisseur", None, &schema, lang).unwrap(),
+            PropertySpec {
+                name: "Regisseur".to_string(),
+                property_type: PropertyType::MultiSelect
+            }
+        );
+        assert!(resolve_property(
+            MovieField::Director,
+            "Regisseur",
+            Some(PropertyType::MultiSelect),
+            &schema,
+            lang
+        )
+        .is_ok());
+        assert!(resolve_property(
+            MovieField::Director,
+            "Regisseur",
+            Some(PropertyType::Select),
+            &schema,
+            lang
+        )
+        .is_err());
+        assert!(resolve_property(MovieField::Year, "Regisseur", None, &schema, lang).is_err());
+        assert!(resolve_property(MovieField::Plot, "Watched", None, &schema, lang).is_err());
+        assert_eq!(
+            resolve_property(MovieField::Watched, "Watched", None, &schema, lang)
+                .unwrap()
+                .property_type,
+            PropertyType::Checkbox
+        );
+        assert!(resolve_property(MovieField::Director, "Director", None, &schema, lang).is_err());
+        assert!(resolve_property(MovieField::Title, "Name", None, &schema, lang).is_ok());
+    }
+
+    #[test]
+    fn test_parse_movie_field() {
+        assert_eq!("director".parse(), Ok(MovieField::Director));
+        assert_eq!(" Release_Date ".parse(), Ok(MovieField::ReleaseDate));
+        assert!("rating".parse::<MovieField>().is_err());
+    }
+}
@@ -1,4 +1,15 @@
+mod mapping;
+mod oauth;
 mod objects;
+mod schema;
+mod template;
+
+pub use mapping::{
+    resolve_property, DiaryEntry, MovieField, PropertyMapping, PropertySpec, MAX_RATING,
+};
+pub use oauth::{NotionOAuth, NOTION_OAUTH_REDIRECT_PATH};
+pub use schema::{check_and_repair_database, retrieve_database_schema, PropertyType, SchemaCheck};
+pub use template::{DatabaseTemplate, DATABASE_ICONS};
 
 use crate::api::notion::objects::*;
 use crate::api::MovieInfo;
@@ -6,15 +17,90 @@ use crate::error::{feedback_error, feedback_propagate_error, propagate_error, Bo
 use crate::{transcripts, Language};
 
 use anyhow::anyhow;
-use reqwest::{Client, Response, Url};
+use chrono::NaiveDate;
+use reqwest::{Client, Method, RequestBuilder, Response, StatusCode, Url};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
+use thiserror::Error;
+
+use std::time::Duration;
+
+const MAX_RETRIES: u32 = 4;
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+/// How many of the cast are listed on a page.
+const CAST_TABLE_ROWS: usize = 10;
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct DatabaseObj {
     pub id: String,
 }
 
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PageObj {
+    pub id: String,
+    pub url: String,
+    #[serde(default)]
+    pub properties: Value,
+}
+
+impl PageObj {
+    pub fn imdb_link(&self, mapping: &PropertyMapping) -> Option<&str> {
+        let property = mapping.get(MovieField::ImdbLink)?;
+
+        self.properties[property.name.as_str()]["url"]
+            .as_str()
+            .filter(|link| !link.is_empty())
+    }
+
+    /// Whether `movie_info` has ratings or a release date that the page doesn't show yet.
+    pub fn is_outdated(&self, movie_info: &MovieInfo, mapping: &PropertyMapping) -> bool {
+        // properties written as text are not compared
+        let current_value = |field: MovieField, property_type: PropertyType| {
+            mapping
+                .get(field)
+                .filter(|property| property.property_type == property_type)
+                .map(|property| &self.properties[property.name.as_str()])
+        };
+        let changed = |field: MovieField, new: Option<f64>| {
+            current_value(field, PropertyType::Number)
+                .map(|current| new.is_some() && new != current["number"].as_f64())
+                .unwrap_or(false)
+        };
+        let release_date_changed = current_value(MovieField::ReleaseDate, PropertyType::Date)
+            .map(|current| {
+                let release_date = current["date"]["start"]
+                    .as_str()
+                    .and_then(|date| date.parse::<NaiveDate>().ok());
+                movie_info.release_date.is_some() && movie_info.release_date != release_date
+            })
+            .unwrap_or(false);
+
+        changed(MovieField::ImdbRating, movie_info.imdb_rating)
+            || changed(
+                MovieField::RottenTomatoes,
+                movie_info.rotten_tomatoes_rating.map(f64::from),
+            )
+            || changed(
+                MovieField::Metacritic,
+                movie_info.metacritic_rating.map(f64::from),
+            )
+            || release_date_changed
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct QueryResultObj {
+    pub results: Vec<PageObj>,
+    #[serde(default)]
+    pub next_cursor: Option<String>,
+}
+
+/// Returned when Notion rejects a request because it doesn't fit the database, e.g. a missing property.
+#[derive(Debug, Error)]
+#[error("{0}")]
+pub struct NotionValidationError(pub String);
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct NotionErrorObj {
     pub object: String,
@@ -23,96 +109,20 @@ pub struct NotionErrorObj {
     pub message: String,
 }
 
+/// Creates the movie list of `template`, with the properties of `mapping` which the template is applied to.
 pub async fn create_database(
     client: &Client,
     token: &str,
-    page_id: &str,
+    template: &DatabaseTemplate,
+    mapping: &PropertyMapping,
     fb_lang: Language,
 ) -> Result<String, BotError> {
     let url = "https://api.notion.com/v1/databases";
 
-    let body = json!({
-        "parent": {
-            "type": "page_id",
-            "page_id": page_id
-        },
-        "icon": {
-            "type": "emoji",
-            "emoji": "🎬"
-        },
-        "title": [
-            {
-                "type": "text",
-                "text": {
-                    "content": "Movie List",
-                    "link": null
-                }
-            }
-        ],
-        "properties": {
-            "Title": {
-                "title": {}
-            },
-            "Type": {
-                "select": {
-                    "options": []
-                }
-            },
-            "Year": {
-                "number": {}
-            },
-            "Release Date": {
-                "date": {}
-            },
-            "Runtime": {
-                "number": {}
-            },
-            "Plot": {
-                "rich_text": {}
-            },
-            "Director": {
-                "type": "multi_select",
-                "multi_select": {
-                    "options": []
-                }
-            },
-            "Star": {
-                "type": "multi_select",
-                "multi_select": {
-                    "options": []
-                }
-            },
-            "Genre": {
-                "type": "multi_select",
-                "multi_select": {
-                    "options": []
-                }
-            },
-            "Country": {
-                "type": "multi_select",
-                "multi_select": {
-                    "options": []
-                }
-            },
-            "Language": {
-                "type": "multi_select",
-                "multi_select": {
-                    "options": []
-                }
-            },
-            "Content Rating": {
-                "rich_text": {}
-            },
-            "IMDb Rating": {
-                "number": {}
-            },
-            "IMDb Link": {
-                "url": {}
-            }
-        }
-    });
+    let body = template.database_object(mapping);
 
-    let response = request_data_from_notion(client, url, token, &body, fb_lang).await?;
+    let response =
+        request_data_from_notion(client, Method::POST, url, token, Some(&body), fb_lang).await?;
 
     if !response.status().is_success() {
         let error_message: NotionErrorObj = response.json().await.map_err(|e| {
@@ -129,6 +139,30 @@ pub async fn create_database(
     Ok(database_obj.id)
 }
 
+/// Checks that the Notion API at `api_url` accepts `token` before it's stored.
+pub async fn validate_notion_token(
+    client: &Client,
+    api_url: &str,
+    token: &str,
+    fb_lang: Language,
+) -> Result<(), BotError> {
+    let url = format!("{}/users/me", api_url.trim_end_matches('/'));
+    let response =
+        request_data_from_notion(client, Method::GET, &url, token, None, fb_lang).await?;
+
+    if response.status() == StatusCode::UNAUTHORIZED {
+        return Err(feedback_error(anyhow!(transcripts::notion_token_rejected(
+            fb_lang
+        ))));
+    }
+
+    if !response.status().is_success() {
+        return Err(handle_notion_error_response(response, fb_lang).await);
+    }
+
+    Ok(())
+}
+
 pub fn parse_notion_page_id_from_user_input(
     input: &str,
     fb_lang: Language,
@@ -145,9 +179,9 @@ fn get_notion_page_id_from_user_input(input: &str) -> Option<String> {
                 return None;
             }
 
-            url.path_segments().and_then(|sgm| {
-                sgm.last()
-                    .and_then(|s| s.split('-').last())
+            url.path_segments().and_then(|mut sgm| {
+                sgm.next_back()
+                    .and_then(|s| s.split('-').next_back())
                     .map(ToOwned::to_owned)
             })?
         }
@@ -162,115 +196,405 @@ pub async fn insert_movie_info_to_notion_database(
     token: &str,
     db_id: &str,
     movie_info: &MovieInfo,
+    mapping: &PropertyMapping,
     fb_lang: Language,
-) -> Result<(), BotError> {
+) -> Result<PageObj, BotError> {
     let url = "https://api.notion.com/v1/pages";
-    let body = notion_create_page_body(db_id, movie_info);
-    let response = request_data_from_notion(client, url, token, &body, fb_lang).await?;
+    let body = notion_create_page_body(db_id, movie_info, mapping);
+    let response =
+        request_data_from_notion(client, Method::POST, url, token, Some(&body), fb_lang).await?;
+
+    if !response.status().is_success() {
+        return Err(handle_notion_error_response(response, fb_lang).await);
+    }
+
+    let page: PageObj = response.json().await.map_err(propagate_error)?;
+
+    // the page is there already, it just looks emptier without its content
+    let children = notion_page_children(movie_info, fb_lang);
+    if let Err(e) = append_block_children(client, token, &page.id, &children, fb_lang).await {
+        log::warn!("failed to add the content of {}: {:?}", page.url, e.inner());
+    }
+
+    Ok(page)
+}
+
+/// Adds `children` to the end of the block, a page is a block too.
+async fn append_block_children(
+    client: &Client,
+    token: &str,
+    block_id: &str,
+    children: &[Value],
+    fb_lang: Language,
+) -> Result<(), BotError> {
+    if children.is_empty() {
+        return Ok(());
+    }
+
+    let url = format!("https://api.notion.com/v1/blocks/{}/children", block_id);
+    let body = json!({ "children": children });
+    let response =
+        request_data_from_notion(client, Method::PATCH, &url, token, Some(&body), fb_lang).await?;
 
     if !response.status().is_success() {
-        handle_notion_error_response(response, fb_lang).await?;
+        return Err(handle_notion_error_response(response, fb_lang).await);
     }
 
     Ok(())
 }
 
-async fn request_data_from_notion(
+/// Finds the page of the title with `imdb_link` in the movie list, if it's already there.
+pub async fn find_movie_in_notion_database(
     client: &Client,
-    url: &str,
     token: &str,
-    body: &Value,
+    db_id: &str,
+    imdb_link: &str,
+    mapping: &PropertyMapping,
     fb_lang: Language,
-) -> Result<Response, BotError> {
-    client
-        .post(url)
-        .header("Notion-Version", "2022-06-28")
-        .bearer_auth(token)
-        .json(body)
-        .send()
-        .await
-        .map_err(|e| {
-            feedback_propagate_error(
-                anyhow!(e).context(transcripts::cannot_reach_server(fb_lang, "Notion")),
-            )
-        })
+) -> Result<Option<PageObj>, BotError> {
+    // without the link there is nothing to tell the titles apart
+    let link_property = match mapping.get(MovieField::ImdbLink) {
+        Some(property) => &property.name,
+        None => return Ok(None),
+    };
+
+    let url = format!("https://api.notion.com/v1/databases/{}/query", db_id);
+    let body = json!({
+        "filter": {
+            "property": link_property,
+            "url": {
+                "equals": imdb_link
+            }
+        },
+        "page_size": 1
+    });
+    let response =
+        request_data_from_notion(client, Method::POST, &url, token, Some(&body), fb_lang).await?;
+
+    if !response.status().is_success() {
+        return Err(handle_notion_error_response(response, fb_lang).await);
+    }
+
+    let query_result: QueryResultObj = response.json().await.map_err(propagate_error)?;
+
+    Ok(query_result.results.into_iter().next())
 }
 
-async fn handle_notion_error_response(
-    response: Response,
+/// Overwrites the properties and cover of an existing page with `movie_info`.
+pub async fn update_movie_info_in_notion_page(
+    client: &Client,
+    token: &str,
+    page_id: &str,
+    movie_info: &MovieInfo,
+    mapping: &PropertyMapping,
     fb_lang: Language,
 ) -> Result<(), BotError> {
-    let error_message: NotionErrorObj = response.json().await.map_err(|e| {
-        feedback_propagate_error(
-            anyhow!(e).context(transcripts::parse_notion_error_message_failed(fb_lang)),
-        )
-    })?;
+    let url = format!("https://api.notion.com/v1/pages/{}", page_id);
+    let body = notion_page_properties_body(movie_info, mapping);
+    let response =
+        request_data_from_notion(client, Method::PATCH, &url, token, Some(&body), fb_lang).await?;
+
+    if !response.status().is_success() {
+        return Err(handle_notion_error_response(response, fb_lang).await);
+    }
 
-    Err(feedback_error(anyhow!(error_message.message)))
+    Ok(())
 }
 
-fn notion_create_page_body(db_id: &str, movie_info: &MovieInfo) -> Value {
-    let mut body = new_database_object();
+/// Writes what the user tells about the title to its page.
+pub async fn update_diary_in_notion_page(
+    client: &Client,
+    token: &str,
+    page_id: &str,
+    entry: &DiaryEntry,
+    mapping: &PropertyMapping,
+    fb_lang: Language,
+) -> Result<(), BotError> {
+    let url = format!("https://api.notion.com/v1/pages/{}", page_id);
+    let body = json!({ "properties": mapping.diary_properties_object(entry) });
+    let response =
+        request_data_from_notion(client, Method::PATCH, &url, token, Some(&body), fb_lang).await?;
 
-    if !movie_info.image.is_empty() {
-        body["cover"] = file_object(&movie_info.image);
+    if !response.status().is_success() {
+        return Err(handle_notion_error_response(response, fb_lang).await);
     }
 
-    let properties = &mut body["properties"];
+    Ok(())
+}
+
+/// Lists the pages in the movie list that link to a title, 100 at a time starting at `start_cursor`.
+pub async fn query_movie_pages_in_notion_database(
+    client: &Client,
+    token: &str,
+    db_id: &str,
+    start_cursor: Option<&str>,
+    mapping: &PropertyMapping,
+    fb_lang: Language,
+) -> Result<QueryResultObj, BotError> {
+    let link_property = match mapping.get(MovieField::ImdbLink) {
+        Some(property) => &property.name,
+        None => {
+            return Ok(QueryResultObj {
+                results: vec![],
+                next_cursor: None,
+            })
+        }
+    };
 
-    properties["Title"] = title_database_property_object(&movie_info.title);
+    let url = format!("https://api.notion.com/v1/databases/{}/query", db_id);
+    let mut body = json!({
+        "filter": {
+            "property": link_property,
+            "url": {
+                "is_not_empty": true
+            }
+        },
+        "page_size": 100
+    });
 
-    if !movie_info.movie_type.is_empty() {
-        properties["Type"] = select_database_property_object(&movie_info.movie_type);
+    if let Some(start_cursor) = start_cursor {
+        body["start_cursor"] = json!(start_cursor);
     }
 
-    if let Some(year) = movie_info.year {
-        properties["Year"] = u32_number_database_property_object(year);
+    let response =
+        request_data_from_notion(client, Method::POST, &url, token, Some(&body), fb_lang).await?;
+
+    if !response.status().is_success() {
+        return Err(handle_notion_error_response(response, fb_lang).await);
     }
 
-    if let Some(date) = &movie_info.release_date {
-        properties["Release Date"] = date_database_property_object(date);
+    response.json().await.map_err(propagate_error)
+}
+
+pub async fn retrieve_notion_page(
+    client: &Client,
+    token: &str,
+    page_id: &str,
+    fb_lang: Language,
+) -> Result<PageObj, BotError> {
+    let url = format!("https://api.notion.com/v1/pages/{}", page_id);
+    let response =
+        request_data_from_notion(client, Method::GET, &url, token, None, fb_lang).await?;
+
+    if !response.status().is_success() {
+        return Err(handle_notion_error_response(response, fb_lang).await);
     }
 
-    if let Some(runtime) = movie_info.runtime {
-        properties["Runtime"] = u32_number_database_property_object(runtime);
+    response.json().await.map_err(propagate_error)
+}
+
+/// Updates only the information of a page that changes over time, leaving the user's edits alone.
+pub async fn refresh_movie_info_in_notion_page(
+    client: &Client,
+    token: &str,
+    page_id: &str,
+    movie_info: &MovieInfo,
+    mapping: &PropertyMapping,
+    fb_lang: Language,
+) -> Result<(), BotError> {
+    let url = format!("https://api.notion.com/v1/pages/{}", page_id);
+    let body = notion_refresh_page_body(movie_info, mapping);
+    let response =
+        request_data_from_notion(client, Method::PATCH, &url, token, Some(&body), fb_lang).await?;
+
+    if !response.status().is_success() {
+        return Err(handle_notion_error_response(response, fb_lang).await);
     }
 
-    if !movie_info.plot.is_empty() {
-        properties["Plot"] = text_database_property_object(&movie_info.plot);
+    Ok(())
+}
+
+async fn request_data_from_notion(
+    client: &Client,
+    method: Method,
+    url: &str,
+    token: &str,
+    body: Option<&Value>,
+    fb_lang: Language,
+) -> Result<Response, BotError> {
+    let mut request = client
+        .request(method, url)
+        .header("Notion-Version", "2022-06-28")
+        .bearer_auth(token);
+
+    if let Some(body) = body {
+        request = request.json(body);
     }
 
-    if !movie_info.director_list.is_empty() {
-        properties["Director"] = multi_select_database_property_object(&movie_info.director_list);
+    send_notion_request(request, fb_lang).await
+}
+
+/// Sends the request, retrying it when Notion is rate limiting us or has a transient problem.
+async fn send_notion_request(
+    request: RequestBuilder,
+    fb_lang: Language,
+) -> Result<Response, BotError> {
+    let mut attempt = 0;
+
+    loop {
+        let response = request
+            .try_clone()
+            .ok_or_else(|| propagate_error(anyhow!("Notion request can't be retried")))?
+            .send()
+            .await
+            .map_err(|e| {
+                feedback_propagate_error(
+                    anyhow!(e).context(transcripts::cannot_reach_server(fb_lang, "Notion")),
+                )
+            })?;
+
+        let retry_after = response
+            .headers()
+            .get("Retry-After")
+            .and_then(|value| value.to_str().ok());
+
+        match retry_delay(response.status(), retry_after, attempt) {
+            Some(delay) if attempt < MAX_RETRIES => {
+                log::warn!(
+                    "Notion responded with {}, retrying in {:?}",
+                    response.status(),
+                    delay
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            _ => return Ok(response),
+        }
     }
+}
 
-    if !movie_info.star_list.is_empty() {
-        properties["Star"] = multi_select_database_property_object(&movie_info.star_list);
+/// How long to wait before retrying a request that got a `status` response, if it's worth it.
+fn retry_delay(status: StatusCode, retry_after: Option<&str>, attempt: u32) -> Option<Duration> {
+    let backoff = INITIAL_BACKOFF * 2u32.pow(attempt);
+
+    match status {
+        StatusCode::TOO_MANY_REQUESTS => Some(
+            retry_after
+                .and_then(|secs| secs.trim().parse().ok())
+                .map(Duration::from_secs)
+                .unwrap_or(backoff)
+                .min(MAX_RETRY_AFTER),
+        ),
+        StatusCode::CONFLICT => Some(backoff),
+        status if status.is_server_error() => Some(backoff),
+        _ => None,
     }
+}
+
+/// The error Notion answered with, it takes the response because nothing else can be read from it.
+async fn handle_notion_error_response(response: Response, fb_lang: Language) -> BotError {
+    let error_message: NotionErrorObj = match response.json().await {
+        Ok(error_message) => error_message,
+        Err(e) => {
+            return feedback_propagate_error(
+                anyhow!(e).context(transcripts::parse_notion_error_message_failed(fb_lang)),
+            )
+        }
+    };
 
-    if !movie_info.genre_list.is_empty() {
-        properties["Genre"] = multi_select_database_property_object(&movie_info.genre_list);
+    if error_message.code == "validation_error" {
+        return feedback_error(anyhow!(NotionValidationError(error_message.message)));
     }
 
-    if !movie_info.country_list.is_empty() {
-        properties["Country"] = multi_select_database_property_object(&movie_info.country_list);
+    feedback_error(anyhow!(error_message.message))
+}
+
+fn notion_create_page_body(
+    db_id: &str,
+    movie_info: &MovieInfo,
+    mapping: &PropertyMapping,
+) -> Value {
+    let mut body = notion_page_properties_body(movie_info, mapping);
+    body["parent"] = parent_object(db_id);
+
+    body
+}
+
+/// The content of a page: the poster, the plot, the cast and the trailer.
+fn notion_page_children(movie_info: &MovieInfo, lang: Language) -> Vec<Value> {
+    let mut children = Vec::new();
+
+    if !movie_info.image.is_empty() {
+        children.push(image_block_object(&movie_info.image));
     }
 
-    if !movie_info.language_list.is_empty() {
-        properties["Language"] = multi_select_database_property_object(&movie_info.language_list);
+    children.extend(
+        movie_info
+            .plot
+            .lines()
+            .map(str::trim)
+            .filter(|paragraph| !paragraph.is_empty())
+            .map(paragraph_block_object),
+    );
+
+    if !movie_info.cast.is_empty() {
+        let header = vec![
+            transcripts::actor(lang).to_string(),
+            transcripts::character(lang).to_string(),
+        ];
+        let rows = std::iter::once(header)
+            .chain(
+                movie_info
+                    .cast
+                    .iter()
+                    .take(CAST_TABLE_ROWS)
+                    .map(|member| vec![member.name.to_string(), member.character.to_string()]),
+            )
+            .collect::<Vec<_>>();
+
+        children.push(heading_block_object(transcripts::cast(lang)));
+        children.push(table_block_object(&rows));
     }
 
-    if !movie_info.content_rating.is_empty() {
-        properties["Content Rating"] = text_database_property_object(&movie_info.content_rating);
+    if let Some(trailer) = &movie_info.trailer {
+        children.push(heading_block_object(transcripts::trailer(lang)));
+        // Notion only plays videos it knows, anything else is better off embedded
+        if is_youtube_link(trailer) {
+            children.push(video_block_object(trailer));
+        } else {
+            children.push(embed_block_object(trailer));
+        }
     }
 
-    if let Some(rating) = movie_info.imdb_rating {
-        properties["IMDb Rating"] = f64_number_database_property_object(rating);
+    children
+}
+
+fn is_youtube_link(link: &str) -> bool {
+    Url::parse(link)
+        .ok()
+        .and_then(|url| url.domain().map(|domain| domain.ends_with("youtube.com")))
+        .unwrap_or(false)
+}
+
+fn notion_page_properties_body(movie_info: &MovieInfo, mapping: &PropertyMapping) -> Value {
+    let mut body = new_database_object();
+
+    if !movie_info.image.is_empty() {
+        body["cover"] = file_object(&movie_info.image);
     }
 
-    properties["IMDb Link"] = url_database_property_object(&movie_info.imdb_link);
+    body["properties"] = mapping.properties_object(movie_info, &MovieField::ALL);
 
-    body["parent"] = parent_object(db_id);
+    body
+}
+
+fn notion_refresh_page_body(movie_info: &MovieInfo, mapping: &PropertyMapping) -> Value {
+    let mut body = new_database_object();
+
+    if !movie_info.image.is_empty() {
+        body["cover"] = file_object(&movie_info.image);
+    }
+
+    body["properties"] = mapping.properties_object(
+        movie_info,
+        &[
+            MovieField::ReleaseDate,
+            MovieField::Runtime,
+            MovieField::ImdbRating,
+            MovieField::RottenTomatoes,
+            MovieField::Metacritic,
+        ],
+    );
 
     body
 }
@@ -278,6 +602,178 @@ fn notion_create_page_body(db_id: &str, movie_info: &MovieInfo) -> Value {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::api::{serve, CastMember};
+    use axum::http::HeaderMap;
+    use axum::routing::get;
+    use axum::{Json, Router};
+
+    #[test]
+    fn test_retry_delay() {
+        assert_eq!(
+            retry_delay(StatusCode::TOO_MANY_REQUESTS, Some("3"), 0),
+            Some(Duration::from_secs(3))
+        );
+        assert_eq!(
+            retry_delay(StatusCode::TOO_MANY_REQUESTS, Some("3600"), 0),
+            Some(MAX_RETRY_AFTER)
+        );
+        assert_eq!(
+            retry_delay(StatusCode::TOO_MANY_REQUESTS, None, 1),
+            Some(Duration::from_secs(1))
+        );
+        assert_eq!(
+            retry_delay(StatusCode::CONFLICT, None, 0),
+            Some(Duration::from_millis(500))
+        );
+        assert_eq!(
+            retry_delay(StatusCode::BAD_GATEWAY, None, 2),
+            Some(Duration::from_secs(2))
+        );
+        assert!(retry_delay(StatusCode::BAD_REQUEST, None, 0).is_none());
+        assert!(retry_delay(StatusCode::OK, None, 0).is_none());
+    }
+
+    #[test]
+    fn test_page_imdb_link() {
+        let page: PageObj = serde_json::from_value(json!({
+            "object": "page",
+            "id": "59833787-2cf9-4fdf-8782-e53db20768a5",
+            "url": "https://www.notion.so/The-Matrix-598337872cf94fdf8782e53db20768a5",
+            "properties": {
+                "IMDb Link": {
+                    "id": "%3DR%5Cw",
+                    "type": "url",
+                    "url": "https://www.imdb.com/title/tt0133093"
+                }
+            }
+        }))
+        .unwrap();
+        assert_eq!(
+            page.imdb_link(&PropertyMapping::default()),
+            Some("https://www.imdb.com/title/tt0133093")
+        );
+
+        let page: PageObj = serde_json::from_value(json!({
+            "id": "59833787-2cf9-4fdf-8782-e53db20768a5",
+            "url": "https://www.notion.so/598337872cf94fdf8782e53db20768a5",
+            "properties": {
+                "IMDb Link": {
+                    "type": "url",
+                    "url": null
+                }
+            }
+        }))
+        .unwrap();
+        assert!(page.imdb_link(&PropertyMapping::default()).is_none());
+    }
+
+    #[test]
+    fn test_page_is_outdated() {
+        let page: PageObj = serde_json::from_value(json!({
+            "id": "59833787-2cf9-4fdf-8782-e53db20768a5",
+            "url": "https://www.notion.so/598337872cf94fdf8782e53db20768a5",
+            "properties": {
+                "IMDb Rating": {
+                    "type": "number",
+                    "number": 8.7
+                },
+                "Metacritic": {
+                    "type": "number",
+                    "number": null
+                },
+                "Release Date": {
+                    "type": "date",
+                    "date": {
+                        "start": "1999-03-31",
+                        "end": null,
+                        "time_zone": null
+                    }
+                }
+            }
+        }))
+        .unwrap();
+
+        let mut mapping = PropertyMapping::default();
+        mapping.set(
+            MovieField::Metacritic,
+            Some(MovieField::Metacritic.default_property()),
+        );
+        let mut movie_info = MovieInfo::the_matrix();
+        assert!(!page.is_outdated(&movie_info, &mapping));
+
+        movie_info.imdb_rating = None;
+        assert!(!page.is_outdated(&movie_info, &mapping));
+
+        movie_info.imdb_rating = Some(8.8);
+        assert!(page.is_outdated(&movie_info, &mapping));
+
+        movie_info.imdb_rating = Some(8.7);
+        movie_info.metacritic_rating = Some(73);
+        assert!(page.is_outdated(&movie_info, &mapping));
+
+        movie_info.metacritic_rating = None;
+        movie_info.release_date = NaiveDate::from_ymd_opt(1999, 6, 11);
+        assert!(page.is_outdated(&movie_info, &mapping));
+
+        // ratings written as text are left alone
+        mapping.set(
+            MovieField::ReleaseDate,
+            Some(PropertySpec {
+                name: "Release Date".to_string(),
+                property_type: PropertyType::RichText,
+            }),
+        );
+        assert!(!page.is_outdated(&movie_info, &mapping));
+    }
+
+    #[test]
+    fn test_notion_page_children() {
+        let lang = Language::default();
+        let mut movie_info = MovieInfo::the_matrix();
+        assert!(notion_page_children(&movie_info, lang).is_empty());
+
+        movie_info.image = "https://m.media-amazon.com/images/M/MV5B.jpg".to_string();
+        movie_info.plot =
+            "Thomas A. Anderson is a man living two lives.\n\nBy night he is a hacker.".to_string();
+        movie_info.cast = vec![CastMember {
+            name: "Keanu Reeves".to_string(),
+            character: "Neo".to_string(),
+        }];
+        movie_info.trailer = Some("https://www.youtube.com/watch?v=vKQi3bBA1y8".to_string());
+
+        let children = notion_page_children(&movie_info, lang);
+        let types = children
+            .iter()
+            .map(|child| child["type"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            types,
+            vec![
+                "image",
+                "paragraph",
+                "paragraph",
+                "heading_2",
+                "table",
+                "heading_2",
+                "video"
+            ]
+        );
+        assert_eq!(
+            children[2]["paragraph"]["rich_text"][0]["text"]["content"],
+            "By night he is a hacker."
+        );
+
+        let table = &children[4]["table"];
+        assert_eq!(table["table_width"], 2);
+        assert_eq!(
+            table["children"][1]["table_row"]["cells"][1][0]["text"]["content"],
+            "Neo"
+        );
+
+        movie_info.trailer = Some("https://www.imdb.com/video/vi1032782617".to_string());
+        let children = notion_page_children(&movie_info, lang);
+        assert_eq!(children.last().unwrap()["type"], "embed");
+    }
 
     #[test]
     fn test_get_notion_token() {
@@ -299,4 +795,57 @@ mod tests {
         let input = "https://www.example.com/?v=d408c958e7c74846a298243fd4334f27";
         assert!(get_notion_page_id_from_user_input(input).is_none());
     }
+
+    /// Serves `users/me` on a local port, only `secret_good` is a valid token.
+    fn mock_users_endpoint() -> String {
+        let app = Router::new().route(
+            "/users/me",
+            get(|headers: HeaderMap| async move {
+                let authorized = headers
+                    .get("authorization")
+                    .and_then(|value| value.to_str().ok())
+                    == Some("Bearer secret_good");
+
+                if authorized {
+                    (
+                        StatusCode::OK,
+                        Json(json!({ "object": "user", "id": "u", "type": "bot" })),
+                    )
+                } else {
+                    (
+                        StatusCode::UNAUTHORIZED,
+                        Json(json!({
+                            "object": "error",
+                            "status": 401,
+                            "code": "unauthorized",
+                            "message": "API token is invalid."
+                        })),
+                    )
+                }
+            }),
+        );
+
+        format!("{}/", serve(app))
+    }
+
+    #[tokio::test]
+    async fn test_validate_notion_token() {
+        let api_url = mock_users_endpoint();
+        let client = Client::new();
+
+        assert!(
+            validate_notion_token(&client, &api_url, "secret_good", Language::En)
+                .await
+                .is_ok()
+        );
+
+        let error = validate_notion_token(&client, &api_url, "secret_bad", Language::En)
+            .await
+            .unwrap_err();
+        assert!(matches!(error, BotError::FeedBack(_)));
+        assert_eq!(
+            error.to_string(),
+            transcripts::notion_token_rejected(Language::En)
+        );
+    }
 }
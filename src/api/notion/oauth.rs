@@ -0,0 +1,213 @@
+use crate::error::{feedback_propagate_error, propagate_error, BotError};
+use crate::{transcripts, Language};
+
+use anyhow::anyhow;
+use reqwest::{Client, Url};
+use serde::Deserialize;
+use serde_json::json;
+
+use std::fmt;
+
+/// Where Notion sends the user back to after they decided on the authorization page.
+pub const NOTION_OAUTH_REDIRECT_PATH: &str = "/notion/oauth";
+
+/// The public integration users connect their workspace to, instead of pasting a token into the chat.
+#[derive(Clone)]
+pub struct NotionOAuth {
+    url: String,
+    client_id: String,
+    client_secret: String,
+    redirect_uri: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NotionAccessToken {
+    pub access_token: String,
+    #[serde(default)]
+    pub workspace_name: Option<String>,
+}
+
+impl NotionOAuth {
+    pub fn new(client_id: String, client_secret: String, redirect_uri: String) -> Self {
+        Self {
+            url: "https://api.notion.com/v1/oauth".to_string(),
+            client_id,
+            client_secret,
+            redirect_uri,
+        }
+    }
+
+    pub fn with_url(mut self, url: impl Into<String>) -> Self {
+        self.url = url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Without a public integration users set the token of an internal one themselves.
+    pub fn is_enabled(&self) -> bool {
+        !(self.client_id.is_empty() || self.client_secret.is_empty())
+    }
+
+    /// The page where the user picks the pages the bot gets access to, `state` comes back with the redirect.
+    pub fn authorization_url(&self, state: &str) -> Result<Url, BotError> {
+        Url::parse_with_params(
+            &format!("{}/authorize", self.url),
+            &[
+                ("client_id", self.client_id.as_str()),
+                ("response_type", "code"),
+                ("owner", "user"),
+                ("redirect_uri", self.redirect_uri.as_str()),
+                ("state", state),
+            ],
+        )
+        .map_err(propagate_error)
+    }
+
+    /// Trades the code of the redirect for the access token of the user's workspace.
+    pub async fn exchange_code(
+        &self,
+        client: &Client,
+        code: &str,
+        fb_lang: Language,
+    ) -> Result<NotionAccessToken, BotError> {
+        let body = json!({
+            "grant_type": "authorization_code",
+            "code": code,
+            "redirect_uri": self.redirect_uri
+        });
+
+        let response = client
+            .post(format!("{}/token", self.url))
+            .basic_auth(&self.client_id, Some(&self.client_secret))
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| {
+                feedback_propagate_error(
+                    anyhow!(e).context(transcripts::cannot_reach_server(fb_lang, "Notion")),
+                )
+            })?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let message = response.text().await.unwrap_or_default();
+
+            return Err(feedback_propagate_error(
+                anyhow!(
+                    "Notion refused the authorization code with {}: {}",
+                    status,
+                    message
+                )
+                .context(transcripts::notion_authorization_failed(fb_lang)),
+            ));
+        }
+
+        response.json().await.map_err(propagate_error)
+    }
+}
+
+/// Everything but the client secret, it stays out of the logs.
+impl fmt::Debug for NotionOAuth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NotionOAuth")
+            .field("url", &self.url)
+            .field("client_id", &self.client_id)
+            .field("redirect_uri", &self.redirect_uri)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::serve;
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::routing::post;
+    use axum::{Json, Router};
+    use serde_json::Value;
+
+    /// Serves a token endpoint on a local port that only knows the code `good_code`.
+    fn mock_token_endpoint() -> String {
+        let app = Router::new().route(
+            "/token",
+            post(|headers: HeaderMap, Json(body): Json<Value>| async move {
+                // base64 of `client:secret`
+                let authorized = headers
+                    .get("authorization")
+                    .and_then(|value| value.to_str().ok())
+                    == Some("Basic Y2xpZW50OnNlY3JldA==");
+
+                if authorized
+                    && body["grant_type"] == "authorization_code"
+                    && body["code"] == "good_code"
+                    && body["redirect_uri"] == "https://example.com/notion/oauth"
+                {
+                    (
+                        StatusCode::OK,
+                        Json(json!({
+                            "access_token": "secret_abc",
+                            "workspace_name": "Movies",
+                            "bot_id": "b"
+                        })),
+                    )
+                } else {
+                    (
+                        StatusCode::BAD_REQUEST,
+                        Json(json!({ "error": "invalid_grant" })),
+                    )
+                }
+            }),
+        );
+
+        serve(app)
+    }
+
+    fn notion_oauth() -> NotionOAuth {
+        NotionOAuth::new(
+            "client".to_string(),
+            "secret".to_string(),
+            "https://example.com/notion/oauth".to_string(),
+        )
+    }
+
+    #[test]
+    fn test_authorization_url() {
+        let url = notion_oauth().authorization_url("a b").unwrap();
+        assert_eq!(
+            url.as_str(),
+            "https://api.notion.com/v1/oauth/authorize?client_id=client&response_type=code&owner=user\
+            &redirect_uri=https%3A%2F%2Fexample.com%2Fnotion%2Foauth&state=a+b"
+        );
+        assert!(notion_oauth().is_enabled());
+        assert!(!NotionOAuth::new(String::new(), String::new(), String::new()).is_enabled());
+    }
+
+    #[test]
+    fn test_debug_omits_client_secret() {
+        let debug = format!("{:?}", notion_oauth());
+        assert!(debug.contains("client"));
+        assert!(!debug.contains("secret"));
+    }
+
+    #[tokio::test]
+    async fn test_exchange_code() {
+        let oauth = notion_oauth().with_url(mock_token_endpoint());
+        let client = Client::new();
+
+        let token = oauth
+            .exchange_code(&client, "good_code", Language::En)
+            .await
+            .unwrap();
+        assert_eq!(token.access_token, "secret_abc");
+        assert_eq!(token.workspace_name.as_deref(), Some("Movies"));
+
+        let error = oauth
+            .exchange_code(&client, "used_code", Language::En)
+            .await
+            .unwrap_err();
+        assert!(matches!(error, BotError::FeedBackPropagate(_)));
+        assert_eq!(
+            error.to_string(),
+            transcripts::notion_authorization_failed(Language::En)
+        );
+    }
+}
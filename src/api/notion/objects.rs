@@ -1,30 +1,23 @@
 use chrono::NaiveDate;
 use serde_json::{json, Value};
 
+/// The longest content of a text object Notion accepts, in UTF-16 code units like Notion counts them.
+pub const MAX_TEXT_LENGTH: usize = 2000;
+/// The most text objects a rich text array may have.
+pub const MAX_RICH_TEXT_ELEMENTS: usize = 100;
+/// The longest name of a select option Notion accepts.
+pub const MAX_OPTION_NAME_LENGTH: usize = 100;
+
 pub fn title_database_property_object(content: &str) -> Value {
     json!({
         "type": "title",
-        "title": [
-            {
-                "type": "text",
-                "text": {
-                    "content": content
-                }
-            }
-        ]
+        "title": rich_text_objects(content)
     })
 }
 
 pub fn text_database_property_object(content: &str) -> Value {
     json!({
-        "rich_text": [
-            {
-                "type": "text",
-                "text": {
-                    "content": content
-                }
-            }
-        ]
+        "rich_text": rich_text_objects(content)
     })
 }
 
@@ -41,18 +34,29 @@ pub fn u32_number_database_property_object(number: u32) -> Value {
 }
 
 pub fn select_database_property_object(content: &str) -> Value {
+    let option = select_option_name(content).map(|name| json!({ "name": name }));
+
     json!({
         "type": "select",
-        "select": {
-            "name": content
-        }
+        "select": option
     })
 }
 
 pub fn multi_select_database_property_object(contents: &[String]) -> Value {
-    let contents = contents
+    let mut names: Vec<String> = Vec::new();
+
+    for name in contents
         .iter()
-        .map(|content| json!({ "name": content }))
+        .filter_map(|content| select_option_name(content))
+    {
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+
+    let contents = names
+        .into_iter()
+        .map(|name| json!({ "name": name }))
         .collect::<Vec<_>>();
 
     json!({
@@ -69,6 +73,10 @@ pub fn date_database_property_object(date: &NaiveDate) -> Value {
     })
 }
 
+pub fn checkbox_database_property_object(checked: bool) -> Value {
+    json!({ "checkbox": checked })
+}
+
 pub fn new_database_object() -> Value {
     json!({
         "properties": {}
@@ -90,3 +98,297 @@ pub fn parent_object(db_id: &str) -> Value {
         "type": "database_id"
     })
 }
+
+pub fn text_object(content: &str) -> Value {
+    json!({
+        "type": "text",
+        "text": {
+            "content": content
+        }
+    })
+}
+
+/// `content` as text objects short enough for Notion, what doesn't fit in a rich text array is cut off.
+pub fn rich_text_objects(content: &str) -> Vec<Value> {
+    let mut chunks = split_text(content, MAX_TEXT_LENGTH);
+
+    if chunks.len() <= MAX_RICH_TEXT_ELEMENTS {
+        return chunks.into_iter().map(text_object).collect();
+    }
+
+    chunks.truncate(MAX_RICH_TEXT_ELEMENTS);
+    let mut last_chunk = chunks.pop().unwrap_or_default().to_string();
+    // the ellipsis takes the place of the last character, so the chunk stays short enough
+    last_chunk.pop();
+    last_chunk.push('…');
+
+    chunks
+        .into_iter()
+        .chain(std::iter::once(last_chunk.as_str()))
+        .map(text_object)
+        .collect()
+}
+
+/// `content` as the name of a select option, `None` if nothing is left of it.
+/// Notion doesn't allow commas in option names and limits their length.
+pub fn select_option_name(content: &str) -> Option<String> {
+    let name = content
+        .replace(',', " ")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    match split_text(&name, MAX_OPTION_NAME_LENGTH).as_slice() {
+        [] => None,
+        [name] => Some(name.to_string()),
+        [name, ..] => {
+            let mut name = name.trim_end().to_string();
+            name.pop();
+            name.push('…');
+            Some(name)
+        }
+    }
+}
+
+/// Splits `content` at character boundaries into chunks of at most `max_length` UTF-16 code units.
+fn split_text(content: &str, max_length: usize) -> Vec<&str> {
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    let mut length = 0;
+
+    for (index, c) in content.char_indices() {
+        if length + c.len_utf16() > max_length {
+            chunks.push(&content[start..index]);
+            start = index;
+            length = 0;
+        }
+
+        length += c.len_utf16();
+    }
+
+    if start < content.len() {
+        chunks.push(&content[start..]);
+    }
+
+    chunks
+}
+
+pub fn heading_block_object(content: &str) -> Value {
+    json!({
+        "object": "block",
+        "type": "heading_2",
+        "heading_2": {
+            "rich_text": rich_text_objects(content)
+        }
+    })
+}
+
+pub fn paragraph_block_object(content: &str) -> Value {
+    json!({
+        "object": "block",
+        "type": "paragraph",
+        "paragraph": {
+            "rich_text": rich_text_objects(content)
+        }
+    })
+}
+
+pub fn image_block_object(url: &str) -> Value {
+    json!({
+        "object": "block",
+        "type": "image",
+        "image": file_object(url)
+    })
+}
+
+pub fn video_block_object(url: &str) -> Value {
+    json!({
+        "object": "block",
+        "type": "video",
+        "video": file_object(url)
+    })
+}
+
+pub fn embed_block_object(url: &str) -> Value {
+    json!({
+        "object": "block",
+        "type": "embed",
+        "embed": {
+            "url": url
+        }
+    })
+}
+
+/// A table with `rows` of text, the first one is the header.
+pub fn table_block_object(rows: &[Vec<String>]) -> Value {
+    let table_width = rows.first().map(Vec::len).unwrap_or_default();
+    let rows = rows
+        .iter()
+        .map(|row| table_row_block_object(row))
+        .collect::<Vec<_>>();
+
+    json!({
+        "object": "block",
+        "type": "table",
+        "table": {
+            "table_width": table_width,
+            "has_column_header": true,
+            "has_row_header": false,
+            "children": rows
+        }
+    })
+}
+
+pub fn table_row_block_object(cells: &[String]) -> Value {
+    let cells = cells
+        .iter()
+        .map(|cell| json!(rich_text_objects(cell)))
+        .collect::<Vec<_>>();
+
+    json!({
+        "type": "table_row",
+        "table_row": {
+            "cells": cells
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contents(objects: &[Value]) -> Vec<&str> {
+        objects
+            .iter()
+            .map(|object| object["text"]["content"].as_str().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_split_ascii_text() {
+        assert!(split_text("", MAX_TEXT_LENGTH).is_empty());
+        assert_eq!(split_text("abcde", 2), vec!["ab", "cd", "e"]);
+
+        let content = "a".repeat(MAX_TEXT_LENGTH);
+        assert_eq!(split_text(&content, MAX_TEXT_LENGTH).len(), 1);
+
+        let content = "a".repeat(MAX_TEXT_LENGTH + 1);
+        let chunks = split_text(&content, MAX_TEXT_LENGTH);
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].len(), MAX_TEXT_LENGTH);
+        assert_eq!(chunks[1], "a");
+    }
+
+    #[test]
+    fn test_split_multi_byte_text() {
+        // three bytes in UTF-8, one code unit in UTF-16
+        let content = "電".repeat(MAX_TEXT_LENGTH + 1);
+        let chunks = split_text(&content, MAX_TEXT_LENGTH);
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].chars().count(), MAX_TEXT_LENGTH);
+        assert_eq!(chunks[1], "電");
+
+        // four bytes in UTF-8, a surrogate pair in UTF-16
+        let content = format!("a{}", "🎬".repeat(MAX_TEXT_LENGTH / 2));
+        let chunks = split_text(&content, MAX_TEXT_LENGTH);
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].encode_utf16().count(), MAX_TEXT_LENGTH - 1);
+        assert_eq!(chunks[1], "🎬");
+        assert_eq!(chunks.concat(), content);
+
+        let content = "Amélie 🎬 Ünïcödé ".repeat(500);
+        let chunks = split_text(&content, MAX_TEXT_LENGTH);
+        assert!(chunks
+            .iter()
+            .all(|chunk| chunk.encode_utf16().count() <= MAX_TEXT_LENGTH));
+        assert_eq!(chunks.concat(), content);
+    }
+
+    #[test]
+    fn test_rich_text_objects() {
+        let plot = "Neo ".repeat(MAX_TEXT_LENGTH);
+        let property = text_database_property_object(&plot);
+        let objects = property["rich_text"].as_array().unwrap();
+        assert_eq!(objects.len(), 4);
+        assert_eq!(contents(objects).concat(), plot);
+
+        let title = title_database_property_object("The Matrix");
+        assert_eq!(
+            contents(title["title"].as_array().unwrap()),
+            vec!["The Matrix"]
+        );
+    }
+
+    #[test]
+    fn test_select_option_name() {
+        assert_eq!(
+            select_option_name("Jr., Robert Downey").unwrap(),
+            "Jr. Robert Downey"
+        );
+        assert_eq!(
+            select_option_name("Korea, Republic of").unwrap(),
+            "Korea Republic of"
+        );
+        assert_eq!(
+            select_option_name(" Keanu  Reeves\t").unwrap(),
+            "Keanu Reeves"
+        );
+        assert_eq!(select_option_name("Zoë Kravitz").unwrap(), "Zoë Kravitz");
+        assert_eq!(select_option_name("趙薇").unwrap(), "趙薇");
+        assert!(select_option_name("").is_none());
+        assert!(select_option_name(" , ").is_none());
+
+        let name = select_option_name(&"Wolfeschlegelsteinhausenbergerdorff ".repeat(4)).unwrap();
+        assert_eq!(name.chars().count(), MAX_OPTION_NAME_LENGTH);
+        assert!(name.ends_with('…'));
+
+        let name = select_option_name(&"🎬".repeat(MAX_OPTION_NAME_LENGTH)).unwrap();
+        assert!(name.encode_utf16().count() <= MAX_OPTION_NAME_LENGTH);
+    }
+
+    #[test]
+    fn test_select_property_objects() {
+        assert_eq!(
+            select_database_property_object("Sci-Fi, Action"),
+            json!({ "type": "select", "select": { "name": "Sci-Fi Action" } })
+        );
+        assert_eq!(
+            select_database_property_object(" "),
+            json!({ "type": "select", "select": null })
+        );
+
+        let names = [
+            "Jr., Robert Downey",
+            "Gwyneth Paltrow",
+            "Jr.  Robert Downey",
+            "",
+            "Gwyneth Paltrow ",
+        ]
+        .map(String::from);
+        assert_eq!(
+            multi_select_database_property_object(&names),
+            json!({
+                "type": "multi_select",
+                "multi_select": [
+                    { "name": "Jr. Robert Downey" },
+                    { "name": "Gwyneth Paltrow" }
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn test_rich_text_objects_are_truncated() {
+        let content = "🎬".repeat(MAX_TEXT_LENGTH / 2 * MAX_RICH_TEXT_ELEMENTS + 1);
+        let objects = rich_text_objects(&content);
+        assert_eq!(objects.len(), MAX_RICH_TEXT_ELEMENTS);
+
+        let contents = contents(&objects);
+        let last = contents.last().unwrap();
+        assert!(last.ends_with("🎬…"));
+        assert!(last.encode_utf16().count() <= MAX_TEXT_LENGTH);
+        assert!(contents
+            .iter()
+            .all(|content| content.encode_utf16().count() <= MAX_TEXT_LENGTH));
+    }
+}
@@ -0,0 +1,433 @@
+use crate::api::notion::mapping::{MovieField, PropertyMapping, PropertySpec};
+use crate::api::notion::{handle_notion_error_response, request_data_from_notion};
+use crate::error::{propagate_error, BotError};
+use crate::{transcripts, Language};
+
+use reqwest::{Client, Method};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// The types of database properties the bot writes to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PropertyType {
+    Title,
+    RichText,
+    Number,
+    Select,
+    MultiSelect,
+    Date,
+    Url,
+    Checkbox,
+}
+
+impl PropertyType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PropertyType::Title => "title",
+            PropertyType::RichText => "rich_text",
+            PropertyType::Number => "number",
+            PropertyType::Select => "select",
+            PropertyType::MultiSelect => "multi_select",
+            PropertyType::Date => "date",
+            PropertyType::Url => "url",
+            PropertyType::Checkbox => "checkbox",
+        }
+    }
+
+    /// The property schema used when creating or updating a database.
+    pub fn schema_object(&self) -> Value {
+        match self {
+            PropertyType::Select | PropertyType::MultiSelect => json!({
+                "type": self.as_str(),
+                self.as_str(): {
+                    "options": []
+                }
+            }),
+            _ => json!({
+                self.as_str(): {}
+            }),
+        }
+    }
+}
+
+impl Display for PropertyType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for PropertyType {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "title" => Ok(PropertyType::Title),
+            "rich_text" | "text" => Ok(PropertyType::RichText),
+            "number" => Ok(PropertyType::Number),
+            "select" => Ok(PropertyType::Select),
+            "multi_select" => Ok(PropertyType::MultiSelect),
+            "date" => Ok(PropertyType::Date),
+            "url" => Ok(PropertyType::Url),
+            "checkbox" => Ok(PropertyType::Checkbox),
+            _ => Err(()),
+        }
+    }
+}
+
+/// The properties of a movie list with `mapping`, as `create_database` makes them.
+pub fn movie_list_properties_object(mapping: &PropertyMapping) -> Value {
+    let properties = mapping
+        .properties()
+        .map(|(_, property)| {
+            (
+                property.name.to_string(),
+                property.property_type.schema_object(),
+            )
+        })
+        .collect::<serde_json::Map<_, _>>();
+
+    Value::Object(properties)
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct DatabaseSchemaObj {
+    pub id: String,
+    pub properties: HashMap<String, PropertySchemaObj>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PropertySchemaObj {
+    #[serde(rename = "type")]
+    pub property_type: String,
+}
+
+impl DatabaseSchemaObj {
+    /// The name of the title property, a database has exactly one.
+    pub fn title_property_name(&self) -> Option<&str> {
+        self.properties
+            .iter()
+            .find(|(_, property)| property.property_type == PropertyType::Title.as_str())
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Compares the database with the properties the bot writes to.
+    pub fn check(&self, mapping: &PropertyMapping) -> SchemaCheck {
+        let mut check = SchemaCheck::default();
+
+        for (field, expected) in mapping.properties() {
+            // whatever the user named it, the title goes to the title property
+            if field == MovieField::Title {
+                check.title = self
+                    .title_property_name()
+                    .filter(|name| *name != expected.name)
+                    .map(str::to_string);
+                continue;
+            }
+
+            match self.properties.get(&expected.name) {
+                Some(property) if property.property_type == expected.property_type.as_str() => {}
+                Some(property) => check.mismatched.push(PropertyMismatch {
+                    name: expected.name.to_string(),
+                    expected: expected.property_type,
+                    actual: property.property_type.clone(),
+                }),
+                None => check
+                    .missing
+                    .push((expected.name.to_string(), expected.property_type)),
+            }
+        }
+
+        check
+    }
+
+    /// The body of the database update that adds the missing properties.
+    pub fn repair_object(&self, check: &SchemaCheck) -> Value {
+        let properties = check
+            .missing
+            .iter()
+            .map(|(name, property_type)| (name.clone(), property_type.schema_object()))
+            .collect::<serde_json::Map<_, _>>();
+
+        json!({ "properties": properties })
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SchemaCheck {
+    pub missing: Vec<(String, PropertyType)>,
+    pub mismatched: Vec<PropertyMismatch>,
+    /// The name of the title property if the mapping knows it by another one.
+    pub title: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct PropertyMismatch {
+    pub name: String,
+    pub expected: PropertyType,
+    pub actual: String,
+}
+
+impl SchemaCheck {
+    /// The title property isn't part of it, `apply_title` takes care of that.
+    pub fn is_compatible(&self) -> bool {
+        self.missing.is_empty() && self.mismatched.is_empty()
+    }
+
+    /// Points the title of `mapping` to the title property of the database.
+    /// Returns whether the mapping changed.
+    pub fn apply_title(&self, mapping: &mut PropertyMapping) -> bool {
+        match &self.title {
+            Some(name) => {
+                mapping.set(
+                    MovieField::Title,
+                    Some(PropertySpec {
+                        name: name.to_string(),
+                        property_type: PropertyType::Title,
+                    }),
+                );
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// One line for each missing property.
+    pub fn missing_properties(&self, lang: Language) -> String {
+        self.missing
+            .iter()
+            .map(|(name, property_type)| {
+                transcripts::notion_property_missing(lang, name, property_type.as_str())
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// One line for each property with the wrong type.
+    pub fn mismatched_properties(&self, lang: Language) -> String {
+        self.mismatched
+            .iter()
+            .map(|mismatch| {
+                transcripts::notion_property_type_mismatch(
+                    lang,
+                    &mismatch.name,
+                    mismatch.expected.as_str(),
+                    &mismatch.actual,
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+pub async fn retrieve_database_schema(
+    client: &Client,
+    token: &str,
+    db_id: &str,
+    fb_lang: Language,
+) -> Result<DatabaseSchemaObj, BotError> {
+    let url = format!("https://api.notion.com/v1/databases/{}", db_id);
+    let response =
+        request_data_from_notion(client, Method::GET, &url, token, None, fb_lang).await?;
+
+    if !response.status().is_success() {
+        return Err(handle_notion_error_response(response, fb_lang).await);
+    }
+
+    response.json().await.map_err(propagate_error)
+}
+
+/// Adds the properties `check` found missing to the database.
+pub async fn repair_database_schema(
+    client: &Client,
+    token: &str,
+    schema: &DatabaseSchemaObj,
+    check: &SchemaCheck,
+    fb_lang: Language,
+) -> Result<(), BotError> {
+    if check.missing.is_empty() {
+        return Ok(());
+    }
+
+    let url = format!("https://api.notion.com/v1/databases/{}", schema.id);
+    let body = schema.repair_object(check);
+    let response =
+        request_data_from_notion(client, Method::PATCH, &url, token, Some(&body), fb_lang).await?;
+
+    if !response.status().is_success() {
+        return Err(handle_notion_error_response(response, fb_lang).await);
+    }
+
+    Ok(())
+}
+
+/// Checks the database against the properties the bot writes to and adds the missing ones.
+/// The returned check tells what was added and which properties the user has to fix.
+pub async fn check_and_repair_database(
+    client: &Client,
+    token: &str,
+    db_id: &str,
+    mapping: &PropertyMapping,
+    fb_lang: Language,
+) -> Result<SchemaCheck, BotError> {
+    let schema = retrieve_database_schema(client, token, db_id, fb_lang).await?;
+    let check = schema.check(mapping);
+    repair_database_schema(client, token, &schema, &check, fb_lang).await?;
+
+    Ok(check)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_movie_list_properties_object() {
+        let mapping = PropertyMapping::default();
+        let properties = movie_list_properties_object(&mapping);
+        assert_eq!(
+            properties.as_object().unwrap().len(),
+            mapping.properties().count()
+        );
+        assert_eq!(properties["Title"], json!({ "title": {} }));
+        assert_eq!(
+            properties["Director"],
+            json!({ "type": "multi_select", "multi_select": { "options": [] } })
+        );
+        assert_eq!(properties["Content Rating"], json!({ "rich_text": {} }));
+        assert_eq!(properties["IMDb Link"], json!({ "url": {} }));
+        assert!(properties.get("Watched").is_none());
+
+        let mut mapping = mapping;
+        mapping.set(MovieField::Star, None);
+        mapping.set(
+            MovieField::Watched,
+            Some(MovieField::Watched.default_property()),
+        );
+        let properties = movie_list_properties_object(&mapping);
+        assert!(properties.get("Star").is_none());
+        assert_eq!(properties["Watched"], json!({ "checkbox": {} }));
+    }
+
+    #[test]
+    fn test_check_database_schema() {
+        let mapping = PropertyMapping::default();
+        let schema: DatabaseSchemaObj = serde_json::from_value(json!({
+            "object": "database",
+            "id": "bc1211ca-e3f1-4939-ae34-5260b16f627c",
+            "properties": {
+                "Name": {
+                    "id": "title",
+                    "name": "Name",
+                    "type": "title",
+                    "title": {}
+                }
+            }
+        }))
+        .unwrap();
+        let check = schema.check(&mapping);
+        assert_eq!(check.missing.len(), mapping.properties().count() - 1);
+        assert_eq!(check.title.as_deref(), Some("Name"));
+
+        let mut mapping = mapping;
+        assert!(check.apply_title(&mut mapping));
+        assert_eq!(mapping.get(MovieField::Title).unwrap().name, "Name");
+        assert!(schema.check(&mapping).title.is_none());
+        let mapping = PropertyMapping::default();
+
+        let mut schema = DatabaseSchemaObj {
+            id: "bc1211ca-e3f1-4939-ae34-5260b16f627c".to_string(),
+            properties: mapping
+                .properties()
+                .map(|(_, property)| {
+                    let schema = PropertySchemaObj {
+                        property_type: property.property_type.as_str().to_string(),
+                    };
+                    (property.name.to_string(), schema)
+                })
+                .collect(),
+        };
+        assert!(schema.check(&mapping).is_compatible());
+
+        schema.properties.remove("Plot");
+        schema.properties.insert(
+            "Year".to_string(),
+            PropertySchemaObj {
+                property_type: "rich_text".to_string(),
+            },
+        );
+        let check = schema.check(&mapping);
+        assert!(!check.is_compatible());
+        assert_eq!(
+            check.missing,
+            vec![("Plot".to_string(), PropertyType::RichText)]
+        );
+        assert_eq!(check.mismatched.len(), 1);
+        assert_eq!(check.mismatched[0].name, "Year");
+        assert_eq!(check.mismatched[0].expected, PropertyType::Number);
+        assert_eq!(check.mismatched[0].actual, "rich_text");
+
+        // the user told us where the year goes
+        let mut mapping = mapping;
+        mapping.set(
+            MovieField::Year,
+            Some(PropertySpec {
+                name: "Year".to_string(),
+                property_type: PropertyType::RichText,
+            }),
+        );
+        mapping.set(MovieField::Plot, None);
+        assert!(schema.check(&mapping).is_compatible());
+    }
+
+    #[test]
+    fn test_repair_object() {
+        let schema: DatabaseSchemaObj = serde_json::from_value(json!({
+            "id": "bc1211ca-e3f1-4939-ae34-5260b16f627c",
+            "properties": {
+                "Name": {
+                    "id": "title",
+                    "name": "Name",
+                    "type": "title",
+                    "title": {}
+                },
+                "Year": {
+                    "id": "abc",
+                    "name": "Year",
+                    "type": "rich_text",
+                    "rich_text": {}
+                }
+            }
+        }))
+        .unwrap();
+        let check = schema.check(&PropertyMapping::default());
+        let body = schema.repair_object(&check);
+        let properties = body["properties"].as_object().unwrap();
+
+        // the user's title property keeps its name
+        assert!(!properties.contains_key("Name"));
+        assert_eq!(check.title.as_deref(), Some("Name"));
+        assert_eq!(
+            properties["Genre"],
+            json!({ "type": "multi_select", "multi_select": { "options": [] } })
+        );
+        assert!(!properties.contains_key("Title"));
+        assert!(!properties.contains_key("Year"));
+        // the diary is opt-in for movie lists that already exist
+        assert!(!properties.contains_key("Watched"));
+        assert_eq!(
+            properties.len(),
+            PropertyMapping::default().properties().count() - 2
+        );
+    }
+
+    #[test]
+    fn test_parse_property_type() {
+        assert_eq!("multi_select".parse(), Ok(PropertyType::MultiSelect));
+        assert_eq!(" Text ".parse(), Ok(PropertyType::RichText));
+        assert_eq!("checkbox".parse(), Ok(PropertyType::Checkbox));
+        assert!("formula".parse::<PropertyType>().is_err());
+    }
+}
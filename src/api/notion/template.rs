@@ -0,0 +1,161 @@
+use crate::api::notion::mapping::{MovieField, PropertyMapping};
+use crate::api::notion::schema::movie_list_properties_object;
+
+use serde_json::{json, Value};
+
+pub const DEFAULT_DATABASE_TITLE: &str = "Movie List";
+pub const DATABASE_ICONS: [&str; 6] = ["🎬", "🍿", "🎥", "📽️", "🎞️", "📺"];
+
+/// What the movie list created by `create_database` looks like.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DatabaseTemplate {
+    pub page_id: String,
+    pub title: String,
+    pub icon: String,
+    /// The fields that get a property, in the order of `MovieField::ALL`.
+    pub fields: Vec<MovieField>,
+}
+
+impl DatabaseTemplate {
+    /// A template with the properties of `mapping`, which is what the user had before, and the
+    /// properties older movie lists only get on request.
+    pub fn new(page_id: &str, title: Option<&str>, mapping: &PropertyMapping) -> Self {
+        let title = title
+            .map(str::trim)
+            .filter(|title| !title.is_empty())
+            .unwrap_or(DEFAULT_DATABASE_TITLE);
+
+        Self {
+            page_id: page_id.to_string(),
+            title: title.to_string(),
+            icon: DATABASE_ICONS[0].to_string(),
+            fields: MovieField::ALL
+                .into_iter()
+                .filter(|field| field.is_opt_in() || mapping.get(*field).is_some())
+                .collect(),
+        }
+    }
+
+    pub fn includes(&self, field: MovieField) -> bool {
+        field.is_required() || self.fields.contains(&field)
+    }
+
+    /// Includes `field` if it's left out and the other way round, required fields always stay.
+    pub fn toggle(&mut self, field: MovieField) {
+        if field.is_required() {
+            return;
+        }
+
+        if self.fields.contains(&field) {
+            self.fields.retain(|f| *f != field);
+        } else {
+            self.fields = MovieField::ALL
+                .into_iter()
+                .filter(|f| *f == field || self.fields.contains(f))
+                .collect();
+        }
+    }
+
+    /// `mapping` with the fields of the template, the ones it adds get their default property.
+    pub fn apply(&self, mapping: &PropertyMapping) -> PropertyMapping {
+        let mut applied = mapping.clone();
+
+        for field in MovieField::ALL {
+            let property = match (self.includes(field), mapping.get(field)) {
+                (true, Some(property)) => Some(property.clone()),
+                (true, None) => Some(field.default_property()),
+                (false, _) => None,
+            };
+            applied.set(field, property);
+        }
+
+        applied
+    }
+
+    /// The body of the request creating the database, `mapping` should be applied already.
+    pub fn database_object(&self, mapping: &PropertyMapping) -> Value {
+        json!({
+            "parent": {
+                "type": "page_id",
+                "page_id": self.page_id
+            },
+            "icon": {
+                "type": "emoji",
+                "emoji": self.icon
+            },
+            "title": [
+                {
+                    "type": "text",
+                    "text": {
+                        "content": self.title,
+                        "link": null
+                    }
+                }
+            ],
+            "properties": movie_list_properties_object(mapping)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::notion::mapping::PropertySpec;
+    use crate::api::notion::schema::PropertyType;
+
+    #[test]
+    fn test_toggle_fields() {
+        let mut template = DatabaseTemplate::new("abc", None, &PropertyMapping::default());
+        assert_eq!(template.title, DEFAULT_DATABASE_TITLE);
+        assert!(template.includes(MovieField::Star));
+        assert!(template.includes(MovieField::Watched));
+
+        template.toggle(MovieField::Star);
+        template.toggle(MovieField::Watched);
+        template.toggle(MovieField::Title);
+        assert!(!template.includes(MovieField::Star));
+        assert!(!template.includes(MovieField::Watched));
+        assert!(template.includes(MovieField::Title));
+
+        // the order doesn't depend on the order of the clicks
+        template.toggle(MovieField::Star);
+        let fields = MovieField::ALL
+            .into_iter()
+            .filter(|field| *field != MovieField::Watched)
+            .collect::<Vec<_>>();
+        assert_eq!(template.fields, fields);
+    }
+
+    #[test]
+    fn test_apply_template() {
+        let mut mapping = PropertyMapping::default();
+        let director = PropertySpec {
+            name: "Regisseur".to_string(),
+            property_type: PropertyType::RichText,
+        };
+        mapping.set(MovieField::Director, Some(director.clone()));
+
+        let mut template = DatabaseTemplate::new("abc", Some(" Films "), &mapping);
+        assert_eq!(template.title, "Films");
+        template.toggle(MovieField::Country);
+        template.toggle(MovieField::WatchedOn);
+
+        let applied = template.apply(&mapping);
+        assert_eq!(applied.get(MovieField::Director), Some(&director));
+        assert!(applied.get(MovieField::Country).is_none());
+        // new movie lists are diaries too
+        assert_eq!(
+            applied.get(MovieField::MyRating),
+            Some(&MovieField::MyRating.default_property())
+        );
+        assert!(applied.get(MovieField::WatchedOn).is_none());
+
+        let body = template.database_object(&applied);
+        assert_eq!(body["parent"]["page_id"], "abc");
+        assert_eq!(body["icon"]["emoji"], DATABASE_ICONS[0]);
+        assert_eq!(body["title"][0]["text"]["content"], "Films");
+        assert_eq!(body["properties"]["My Rating"], json!({ "number": {} }));
+        assert!(body["properties"].get("Country").is_none());
+        assert!(body["properties"].get("Regisseur").is_some());
+    }
+}
@@ -0,0 +1,3 @@
+mod omdb_api;
+
+pub use omdb_api::OmdbApi;
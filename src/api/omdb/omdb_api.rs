@@ -0,0 +1,385 @@
+use crate::api::{CastMember, MovieInfo, Provider, QuotaExceeded, RequestMovieInfo, SearchResult};
+use crate::error::{feedback_error, feedback_propagate_error, BotError};
+use crate::{transcripts, Language};
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Debug, Clone)]
+pub struct OmdbApi {
+    url: String,
+    default_key: String,
+}
+
+impl OmdbApi {
+    pub fn new(default_key: String) -> Self {
+        Self {
+            default_key,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_url(mut self, url: impl Into<String>) -> Self {
+        self.url = url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    fn api_key_or_default<'a>(&'a self, api_key: &'a str) -> &'a str {
+        if api_key.is_empty() {
+            &self.default_key
+        } else {
+            api_key
+        }
+    }
+}
+
+impl Default for OmdbApi {
+    fn default() -> Self {
+        Self {
+            url: "https://www.omdbapi.com".to_string(),
+            default_key: "".to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl RequestMovieInfo for OmdbApi {
+    async fn search(
+        &self,
+        client: &Client,
+        api_key: &str,
+        keyword: &str,
+        limits: usize,
+        lang: Language,
+    ) -> Result<Vec<SearchResult>, BotError> {
+        let query = [("apikey", self.api_key_or_default(api_key)), ("s", keyword)];
+
+        let data = request_data_from_omdb(client, &self.url, &query, lang).await?;
+
+        // OMDb reports an empty search as an error
+        match omdb_error_message(&data) {
+            Some(OMDB_NOT_FOUND) => Ok(Vec::new()),
+            Some(err) => Err(omdb_error(err)),
+            None => Ok(parse_search_results(&data, limits)),
+        }
+    }
+
+    async fn request_movie_information(
+        &self,
+        client: &Client,
+        api_key: &str,
+        id: &str,
+        lang: Language,
+    ) -> Result<MovieInfo, BotError> {
+        let query = [
+            ("apikey", self.api_key_or_default(api_key)),
+            ("i", id),
+            ("plot", "full"),
+        ];
+
+        let data = request_data_from_omdb(client, &self.url, &query, lang).await?;
+
+        if let Some(err) = omdb_error_message(&data) {
+            return Err(omdb_error(err));
+        }
+
+        let movie_info: OmdbMovieInfo = serde_json::from_value(data).map_err(|e| {
+            feedback_propagate_error(
+                anyhow!(e).context(transcripts::parse_omdb_api_response_failed(lang)),
+            )
+        })?;
+
+        Ok(movie_info.into())
+    }
+
+    fn provider(&self) -> Provider {
+        Provider::Omdb
+    }
+
+    fn has_api_key(&self, api_key: &str) -> bool {
+        !self.api_key_or_default(api_key).is_empty()
+    }
+}
+
+const OMDB_NOT_FOUND: &str = "Movie not found!";
+const OMDB_LIMIT_REACHED: &str = "Request limit reached!";
+
+async fn request_data_from_omdb(
+    client: &Client,
+    url: &str,
+    query: &[(&str, &str)],
+    fb_lang: Language,
+) -> Result<Value, BotError> {
+    let response = client.get(url).query(query).send().await.map_err(|e| {
+        feedback_propagate_error(
+            anyhow!(e).context(transcripts::cannot_reach_server(fb_lang, "OMDb")),
+        )
+    })?;
+
+    response.json::<Value>().await.map_err(|e| {
+        feedback_propagate_error(
+            anyhow!(e).context(transcripts::parse_omdb_api_error_message_failed(fb_lang)),
+        )
+    })
+}
+
+fn omdb_error_message(data: &Value) -> Option<&str> {
+    if data["Response"].as_str() == Some("False") {
+        Some(data["Error"].as_str().unwrap_or_default())
+    } else {
+        None
+    }
+}
+
+fn omdb_error(err: &str) -> BotError {
+    if err == OMDB_LIMIT_REACHED {
+        feedback_error(anyhow!(QuotaExceeded(err.to_string())))
+    } else {
+        feedback_error(anyhow!(err.to_string()))
+    }
+}
+
+fn parse_search_results(data: &Value, limits: usize) -> Vec<SearchResult> {
+    match data["Search"].as_array() {
+        Some(results) => results
+            .iter()
+            .take(limits)
+            .cloned()
+            .filter_map(|result| {
+                let search_result: OmdbSearchResult = serde_json::from_value(result).ok()?;
+                Some(search_result.into())
+            })
+            .collect(),
+        None => Vec::new(),
+    }
+}
+
+/// OMDb fills every unknown field with `N/A`.
+fn not_available(value: Option<String>) -> Option<String> {
+    value.filter(|v| !v.is_empty() && v != "N/A")
+}
+
+fn split_comma_joined(value: Option<String>) -> Vec<String> {
+    not_available(value)
+        .map(|v| {
+            v.split(',')
+                .map(str::trim)
+                .filter(|s| !s.is_empty() && *s != "N/A")
+                .map(ToOwned::to_owned)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Parses `87%` (Rotten Tomatoes) and `73/100` (Metacritic) into a score out of 100.
+fn parse_rating_score(value: &str) -> Option<u32> {
+    let score = value.trim().trim_end_matches('%');
+    let score = score.strip_suffix("/100").unwrap_or(score);
+    score.parse().ok()
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct OmdbSearchResult {
+    pub title: String,
+    pub year: String,
+    #[serde(rename = "imdbID")]
+    pub imdb_id: String,
+}
+
+impl From<OmdbSearchResult> for SearchResult {
+    fn from(result: OmdbSearchResult) -> Self {
+        let link = format!("https://www.imdb.com/title/{}", result.imdb_id);
+
+        Self {
+            id: result.imdb_id,
+            title: result.title,
+            description: format!("({})", result.year),
+            link,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct OmdbMovieInfo {
+    pub title: String,
+    #[serde(rename = "Type")]
+    pub movie_type: Option<String>,
+    pub year: Option<String>,
+    pub rated: Option<String>,
+    pub released: Option<String>,
+    pub runtime: Option<String>,
+    pub genre: Option<String>,
+    pub director: Option<String>,
+    pub actors: Option<String>,
+    pub plot: Option<String>,
+    pub language: Option<String>,
+    pub country: Option<String>,
+    pub poster: Option<String>,
+    #[serde(default)]
+    pub ratings: Vec<OmdbRatingObj>,
+    #[serde(rename = "imdbRating")]
+    pub imdb_rating: Option<String>,
+    #[serde(rename = "imdbID")]
+    pub imdb_id: String,
+}
+
+impl From<OmdbMovieInfo> for MovieInfo {
+    fn from(info: OmdbMovieInfo) -> Self {
+        let title = info.title;
+        let movie_type = match not_available(info.movie_type).as_deref() {
+            Some("movie") => "Movie".to_string(),
+            Some("series") => "TVSeries".to_string(),
+            Some("episode") => "TVEpisode".to_string(),
+            Some(other) => other.to_string(),
+            None => String::new(),
+        };
+        // series have years like `2011–2019`
+        let year = not_available(info.year).and_then(|y| y.get(..4).and_then(|y| y.parse().ok()));
+        let image = not_available(info.poster).unwrap_or_default();
+        let release_date = not_available(info.released)
+            .and_then(|date| NaiveDate::parse_from_str(&date, "%d %b %Y").ok());
+        let runtime = not_available(info.runtime)
+            .and_then(|rt| rt.split_whitespace().next().and_then(|rt| rt.parse().ok()));
+        let plot = not_available(info.plot).unwrap_or_default();
+        let director_list = split_comma_joined(info.director);
+        let star_list = split_comma_joined(info.actors);
+        // OMDb doesn't tell who plays whom
+        let cast = star_list
+            .iter()
+            .map(|name| CastMember {
+                name: name.to_string(),
+                character: String::new(),
+            })
+            .collect();
+        let genre_list = split_comma_joined(info.genre);
+        let country_list = split_comma_joined(info.country);
+        let language_list = split_comma_joined(info.language);
+        let content_rating = not_available(info.rated).unwrap_or_default();
+        let imdb_rating = not_available(info.imdb_rating).and_then(|r| r.parse().ok());
+        let rotten_tomatoes_rating = info
+            .ratings
+            .iter()
+            .find(|r| r.source == "Rotten Tomatoes")
+            .and_then(|r| parse_rating_score(&r.value));
+        let metacritic_rating = info
+            .ratings
+            .iter()
+            .find(|r| r.source == "Metacritic")
+            .and_then(|r| parse_rating_score(&r.value));
+        let imdb_link = format!("https://www.imdb.com/title/{}", info.imdb_id);
+
+        Self {
+            title,
+            movie_type,
+            year,
+            image,
+            trailer: None,
+            release_date,
+            runtime,
+            plot,
+            director_list,
+            star_list,
+            cast,
+            genre_list,
+            country_list,
+            language_list,
+            content_rating,
+            imdb_rating,
+            rotten_tomatoes_rating,
+            metacritic_rating,
+            imdb_link,
+            provider: Provider::Omdb,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct OmdbRatingObj {
+    pub source: String,
+    pub value: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_movie_info_from_omdb_response() {
+        let data = json!({
+            "Title": "The Matrix",
+            "Year": "1999",
+            "Rated": "R",
+            "Released": "31 Mar 1999",
+            "Runtime": "136 min",
+            "Genre": "Action, Sci-Fi",
+            "Director": "Lana Wachowski, Lilly Wachowski",
+            "Writer": "Lilly Wachowski, Lana Wachowski",
+            "Actors": "Keanu Reeves, Laurence Fishburne, Carrie-Anne Moss",
+            "Plot": "When a beautiful stranger leads computer hacker Neo to a forbidding underworld...",
+            "Language": "English",
+            "Country": "United States, Australia",
+            "Awards": "Won 4 Oscars. 42 wins & 51 nominations total",
+            "Poster": "N/A",
+            "Ratings": [
+                { "Source": "Internet Movie Database", "Value": "8.7/10" },
+                { "Source": "Rotten Tomatoes", "Value": "83%" },
+                { "Source": "Metacritic", "Value": "73/100" }
+            ],
+            "Metascore": "73",
+            "imdbRating": "8.7",
+            "imdbVotes": "1,906,073",
+            "imdbID": "tt0133093",
+            "Type": "movie",
+            "Response": "True"
+        });
+
+        let info: OmdbMovieInfo = serde_json::from_value(data).unwrap();
+        let info: MovieInfo = info.into();
+
+        assert_eq!(info.movie_type, "Movie");
+        assert_eq!(info.year, Some(1999));
+        assert_eq!(info.release_date, NaiveDate::from_ymd_opt(1999, 3, 31));
+        assert_eq!(info.runtime, Some(136));
+        assert_eq!(info.image, "");
+        assert_eq!(
+            info.director_list,
+            vec!["Lana Wachowski", "Lilly Wachowski"]
+        );
+        assert_eq!(
+            info.star_list,
+            vec!["Keanu Reeves", "Laurence Fishburne", "Carrie-Anne Moss"]
+        );
+        assert_eq!(info.country_list, vec!["United States", "Australia"]);
+        assert_eq!(info.imdb_rating, Some(8.7));
+        assert_eq!(info.rotten_tomatoes_rating, Some(83));
+        assert_eq!(info.metacritic_rating, Some(73));
+        assert_eq!(info.imdb_link, "https://www.imdb.com/title/tt0133093");
+    }
+
+    #[test]
+    fn test_omdb_error_message() {
+        let data = json!({ "Response": "False", "Error": "Invalid API key!" });
+        assert_eq!(omdb_error_message(&data), Some("Invalid API key!"));
+
+        let data = json!({ "Response": "True", "Search": [] });
+        assert!(omdb_error_message(&data).is_none());
+    }
+
+    #[test]
+    fn test_split_comma_joined() {
+        assert!(split_comma_joined(Some("N/A".to_string())).is_empty());
+        assert!(split_comma_joined(None).is_empty());
+        assert_eq!(
+            split_comma_joined(Some("Drama,  Romance ,".to_string())),
+            vec!["Drama", "Romance"]
+        );
+    }
+}
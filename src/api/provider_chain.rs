@@ -0,0 +1,379 @@
+use crate::api::{MovieInfo, Provider, QuotaExceeded, RequestMovieInfo, SearchResult};
+use crate::error::{feedback_error, BotError};
+use crate::{transcripts, Language};
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use reqwest::Client;
+
+use std::sync::Arc;
+
+type Api = Arc<dyn RequestMovieInfo + Send + Sync>;
+
+/// Asks an ordered list of providers and falls back to the next one when a provider runs out of
+/// quota, can't be reached or finds nothing.
+///
+/// The user's API key only belongs to the first provider, the fallbacks use their default keys and
+/// are skipped when they don't have one.
+/// Search result ids are prefixed with the provider code (`tmdb:movie/603`), so the details are
+/// requested from the provider that answered the search.
+#[derive(Clone)]
+pub struct ProviderChain {
+    providers: Vec<Api>,
+}
+
+impl ProviderChain {
+    pub fn new(providers: Vec<Api>) -> Self {
+        assert!(
+            !providers.is_empty(),
+            "a provider chain needs at least one provider"
+        );
+
+        Self { providers }
+    }
+
+    /// A chain asking `provider` first, e.g. the one the user picked, and the others as fallbacks.
+    pub fn prefer(&self, provider: Option<Provider>) -> Self {
+        let mut providers = self.providers.clone();
+
+        if let Some(index) = provider.and_then(|p| self.position(p)) {
+            let preferred = providers.remove(index);
+            providers.insert(0, preferred);
+        }
+
+        Self { providers }
+    }
+
+    /// Providers that users can pick from.
+    pub fn providers(&self) -> Vec<Provider> {
+        self.providers.iter().map(|api| api.provider()).collect()
+    }
+
+    fn position(&self, provider: Provider) -> Option<usize> {
+        self.providers
+            .iter()
+            .position(|api| api.provider() == provider)
+    }
+
+    fn api_key_for<'a>(&self, index: usize, api_key: &'a str) -> &'a str {
+        if index == 0 {
+            api_key
+        } else {
+            ""
+        }
+    }
+
+    fn split_id<'a>(&self, id: &'a str) -> (Option<usize>, &'a str) {
+        id.split_once(':')
+            .and_then(|(code, inner_id)| {
+                self.providers
+                    .iter()
+                    .position(|api| api.provider().code() == code)
+                    .map(|index| (Some(index), inner_id))
+            })
+            // ids without a known prefix come from buttons sent before the chain existed
+            .unwrap_or((None, id))
+    }
+
+    /// Asks the provider that found the title first, then the others which understand its id.
+    /// With `refresh` the providers skip their caches.
+    async fn movie_information(
+        &self,
+        client: &Client,
+        api_key: &str,
+        id: &str,
+        refresh: bool,
+        lang: Language,
+    ) -> Result<MovieInfo, BotError> {
+        let (owner, id) = self.split_id(id);
+
+        let candidates = owner
+            .into_iter()
+            .chain((0..self.providers.len()).filter(|index| Some(*index) != owner))
+            .filter(|index| self.providers[*index].accepts_id(id))
+            .collect::<Vec<_>>();
+
+        let mut last_error = None;
+
+        for index in candidates {
+            let api = &self.providers[index];
+            let api_key = self.api_key_for(index, api_key);
+
+            if !api.has_api_key(api_key) {
+                continue;
+            }
+
+            let result = if refresh {
+                api.refresh_movie_information(client, api_key, id, lang)
+                    .await
+            } else {
+                api.request_movie_information(client, api_key, id, lang)
+                    .await
+            };
+
+            match result {
+                Ok(movie_info) => return Ok(movie_info),
+                Err(e) if should_fall_back(&e) => {
+                    log::warn!(
+                        "{} failed, falling back: {:?}",
+                        api.provider().name(),
+                        e.inner()
+                    );
+                    last_error = Some(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        Err(last_error
+            .unwrap_or_else(|| feedback_error(anyhow!(transcripts::invalid_movie_id(lang)))))
+    }
+}
+
+#[async_trait]
+impl RequestMovieInfo for ProviderChain {
+    async fn search(
+        &self,
+        client: &Client,
+        api_key: &str,
+        keyword: &str,
+        limits: usize,
+        lang: Language,
+    ) -> Result<Vec<SearchResult>, BotError> {
+        let mut last_error = None;
+
+        for (index, api) in self.providers.iter().enumerate() {
+            let provider = api.provider();
+            let api_key = self.api_key_for(index, api_key);
+
+            if !api.has_api_key(api_key) {
+                continue;
+            }
+
+            match api.search(client, api_key, keyword, limits, lang).await {
+                Ok(search_results) if search_results.is_empty() => {
+                    log::info!("{} found nothing for `{}`", provider.name(), keyword);
+                }
+                Ok(search_results) => {
+                    return Ok(search_results
+                        .into_iter()
+                        .map(|mut search_result| {
+                            search_result.id = format!("{}:{}", provider.code(), search_result.id);
+                            search_result
+                        })
+                        .collect());
+                }
+                Err(e) if should_fall_back(&e) => {
+                    log::warn!("{} failed, falling back: {:?}", provider.name(), e.inner());
+                    last_error = Some(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        match last_error {
+            Some(e) => Err(e),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    async fn request_movie_information(
+        &self,
+        client: &Client,
+        api_key: &str,
+        id: &str,
+        lang: Language,
+    ) -> Result<MovieInfo, BotError> {
+        self.movie_information(client, api_key, id, false, lang)
+            .await
+    }
+
+    async fn refresh_movie_information(
+        &self,
+        client: &Client,
+        api_key: &str,
+        id: &str,
+        lang: Language,
+    ) -> Result<MovieInfo, BotError> {
+        self.movie_information(client, api_key, id, true, lang)
+            .await
+    }
+
+    fn provider(&self) -> Provider {
+        self.providers[0].provider()
+    }
+
+    fn accepts_id(&self, id: &str) -> bool {
+        let (_, id) = self.split_id(id);
+        self.providers.iter().any(|api| api.accepts_id(id))
+    }
+
+    fn has_api_key(&self, api_key: &str) -> bool {
+        self.providers
+            .iter()
+            .enumerate()
+            .any(|(index, api)| api.has_api_key(self.api_key_for(index, api_key)))
+    }
+}
+
+/// Quota and network errors are worth trying another provider for, anything else would most likely
+/// fail there too.
+fn should_fall_back(error: &BotError) -> bool {
+    error
+        .inner()
+        .chain()
+        .any(|e| e.is::<QuotaExceeded>() || e.is::<reqwest::Error>())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::feedback_propagate_error;
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    enum Outcome {
+        Found,
+        Empty,
+        QuotaExceeded,
+        InvalidKey,
+    }
+
+    struct FakeApi {
+        provider: Provider,
+        outcome: Outcome,
+        calls: AtomicUsize,
+    }
+
+    impl FakeApi {
+        fn new(provider: Provider, outcome: Outcome) -> Arc<Self> {
+            Arc::new(Self {
+                provider,
+                outcome,
+                calls: AtomicUsize::new(0),
+            })
+        }
+
+        fn result<T>(&self, found: T) -> Result<T, BotError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+
+            match self.outcome {
+                Outcome::Found | Outcome::Empty => Ok(found),
+                Outcome::QuotaExceeded => Err(feedback_error(anyhow!(QuotaExceeded(
+                    "Maximum usage".to_string()
+                )))),
+                Outcome::InvalidKey => Err(feedback_propagate_error(anyhow!("Invalid API Key"))),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl RequestMovieInfo for FakeApi {
+        async fn search(
+            &self,
+            _client: &Client,
+            _api_key: &str,
+            _keyword: &str,
+            _limits: usize,
+            _lang: Language,
+        ) -> Result<Vec<SearchResult>, BotError> {
+            let search_results = match self.outcome {
+                Outcome::Empty => Vec::new(),
+                _ => vec![SearchResult {
+                    id: "tt0133093".to_string(),
+                    title: "The Matrix".to_string(),
+                    description: "(1999)".to_string(),
+                    link: "https://www.imdb.com/title/tt0133093".to_string(),
+                }],
+            };
+
+            self.result(search_results)
+        }
+
+        async fn request_movie_information(
+            &self,
+            _client: &Client,
+            _api_key: &str,
+            _id: &str,
+            _lang: Language,
+        ) -> Result<MovieInfo, BotError> {
+            self.result(MovieInfo {
+                provider: self.provider,
+                ..MovieInfo::the_matrix()
+            })
+        }
+
+        fn provider(&self) -> Provider {
+            self.provider
+        }
+    }
+
+    #[tokio::test]
+    async fn test_search_falls_back_on_quota_and_empty_results() {
+        let imdb_api = FakeApi::new(Provider::ImdbApi, Outcome::QuotaExceeded);
+        let imdb_datasets = FakeApi::new(Provider::ImdbDatasets, Outcome::Empty);
+        let omdb = FakeApi::new(Provider::Omdb, Outcome::Found);
+        let chain = ProviderChain::new(vec![imdb_api.clone(), imdb_datasets.clone(), omdb.clone()]);
+
+        let results = chain
+            .search(&Client::new(), "", "matrix", 5, Language::En)
+            .await
+            .unwrap();
+        assert_eq!(results[0].id, "omdb:tt0133093");
+        assert_eq!(imdb_api.calls.load(Ordering::SeqCst), 1);
+        assert_eq!(imdb_datasets.calls.load(Ordering::SeqCst), 1);
+
+        let movie_info = chain
+            .request_movie_information(&Client::new(), "", &results[0].id, Language::En)
+            .await
+            .unwrap();
+        assert_eq!(movie_info.provider, Provider::Omdb);
+        assert_eq!(imdb_api.calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_search_stops_on_other_errors() {
+        let imdb_api = FakeApi::new(Provider::ImdbApi, Outcome::InvalidKey);
+        let omdb = FakeApi::new(Provider::Omdb, Outcome::Found);
+        let chain = ProviderChain::new(vec![imdb_api, omdb.clone()]);
+
+        assert!(chain
+            .search(&Client::new(), "", "matrix", 5, Language::En)
+            .await
+            .is_err());
+        assert_eq!(omdb.calls.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_prefer_provider() {
+        let chain = ProviderChain::new(vec![
+            FakeApi::new(Provider::ImdbApi, Outcome::Found),
+            FakeApi::new(Provider::Tmdb, Outcome::Found),
+            FakeApi::new(Provider::Omdb, Outcome::Found),
+        ]);
+
+        assert_eq!(
+            chain.prefer(Some(Provider::Omdb)).providers(),
+            vec![Provider::Omdb, Provider::ImdbApi, Provider::Tmdb]
+        );
+        assert_eq!(
+            chain.prefer(Some(Provider::ImdbDatasets)).providers(),
+            chain.providers()
+        );
+        assert_eq!(chain.prefer(None).providers(), chain.providers());
+    }
+
+    #[tokio::test]
+    async fn test_details_fall_back_to_providers_understanding_the_id() {
+        let imdb_api = FakeApi::new(Provider::ImdbApi, Outcome::QuotaExceeded);
+        let omdb = FakeApi::new(Provider::Omdb, Outcome::Found);
+        let chain = ProviderChain::new(vec![imdb_api.clone(), omdb]);
+
+        let movie_info = chain
+            .request_movie_information(&Client::new(), "", "imdb_api:tt0133093", Language::En)
+            .await
+            .unwrap();
+        assert_eq!(movie_info.provider, Provider::Omdb);
+        assert_eq!(imdb_api.calls.load(Ordering::SeqCst), 1);
+    }
+}
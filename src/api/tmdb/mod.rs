@@ -0,0 +1,3 @@
+mod tmdb_api;
+
+pub use tmdb_api::TmdbApi;
@@ -0,0 +1,680 @@
+use crate::api::{CastMember, MovieInfo, Provider, QuotaExceeded, RequestMovieInfo, SearchResult};
+use crate::error::{feedback_error, feedback_propagate_error, BotError};
+use crate::{transcripts, Language};
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use chrono::{Datelike, NaiveDate};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Debug, Clone)]
+pub struct TmdbApi {
+    url: String,
+    default_key: String,
+}
+
+impl TmdbApi {
+    pub fn new(default_key: String) -> Self {
+        Self {
+            default_key,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_url(mut self, url: impl Into<String>) -> Self {
+        self.url = url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    fn search_api(&self) -> String {
+        format!("{}/3/search/multi", self.url)
+    }
+
+    fn title_info_api(&self, media_type: TmdbMediaType, tmdb_id: u64) -> String {
+        format!("{}/3/{}/{}", self.url, media_type.as_str(), tmdb_id)
+    }
+
+    fn api_key_or_default<'a>(&'a self, api_key: &'a str) -> &'a str {
+        if api_key.is_empty() {
+            &self.default_key
+        } else {
+            api_key
+        }
+    }
+}
+
+impl Default for TmdbApi {
+    fn default() -> Self {
+        Self {
+            url: "https://api.themoviedb.org".to_string(),
+            default_key: "".to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl RequestMovieInfo for TmdbApi {
+    async fn search(
+        &self,
+        client: &Client,
+        api_key: &str,
+        keyword: &str,
+        limits: usize,
+        lang: Language,
+    ) -> Result<Vec<SearchResult>, BotError> {
+        let query = [
+            ("api_key", self.api_key_or_default(api_key)),
+            ("query", keyword),
+            ("language", tmdb_language(lang)),
+            ("include_adult", "false"),
+        ];
+
+        let data = request_data_from_tmdb(client, &self.search_api(), &query, lang).await?;
+
+        Ok(parse_search_results(&data, limits))
+    }
+
+    async fn request_movie_information(
+        &self,
+        client: &Client,
+        api_key: &str,
+        id: &str,
+        lang: Language,
+    ) -> Result<MovieInfo, BotError> {
+        let (media_type, tmdb_id) = parse_tmdb_id(id)
+            .ok_or_else(|| feedback_error(anyhow!(transcripts::invalid_movie_id(lang))))?;
+
+        let query = [
+            ("api_key", self.api_key_or_default(api_key)),
+            ("language", tmdb_language(lang)),
+            ("append_to_response", media_type.append_to_response()),
+        ];
+
+        let url = self.title_info_api(media_type, tmdb_id);
+        let data = request_data_from_tmdb(client, &url, &query, lang).await?;
+        let movie_info: TmdbMovieInfo = serde_json::from_value(data).map_err(|e| {
+            feedback_propagate_error(
+                anyhow!(e).context(transcripts::parse_tmdb_api_response_failed(lang)),
+            )
+        })?;
+
+        Ok(movie_info.into_movie_info(media_type))
+    }
+
+    fn provider(&self) -> Provider {
+        Provider::Tmdb
+    }
+
+    fn accepts_id(&self, id: &str) -> bool {
+        parse_tmdb_id(id).is_some()
+    }
+
+    fn has_api_key(&self, api_key: &str) -> bool {
+        !self.api_key_or_default(api_key).is_empty()
+    }
+}
+
+async fn request_data_from_tmdb(
+    client: &Client,
+    url: &str,
+    query: &[(&str, &str)],
+    fb_lang: Language,
+) -> Result<Value, BotError> {
+    let response = client.get(url).query(query).send().await.map_err(|e| {
+        feedback_propagate_error(
+            anyhow!(e).context(transcripts::cannot_reach_server(fb_lang, "TMDb")),
+        )
+    })?;
+
+    let data = response.json::<Value>().await.map_err(|e| {
+        feedback_propagate_error(
+            anyhow!(e).context(transcripts::parse_tmdb_api_error_message_failed(fb_lang)),
+        )
+    })?;
+
+    // TMDb only includes `success` in its error responses
+    if data["success"].as_bool() == Some(false) {
+        let err = data["status_message"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+
+        // 25: "Your request count (#) is over the allowed limit of (#)."
+        if data["status_code"].as_u64() == Some(25) {
+            return Err(feedback_error(anyhow!(QuotaExceeded(err))));
+        }
+
+        return Err(feedback_error(anyhow!(err)));
+    }
+
+    Ok(data)
+}
+
+fn tmdb_language(lang: Language) -> &'static str {
+    match lang {
+        Language::En => "en-US",
+    }
+}
+
+fn parse_search_results(data: &Value, limits: usize) -> Vec<SearchResult> {
+    match data["results"].as_array() {
+        Some(results) => results
+            .iter()
+            .cloned()
+            .filter_map(|result| {
+                let search_result: TmdbSearchResult = serde_json::from_value(result).ok()?;
+                search_result.into_search_result()
+            })
+            .take(limits)
+            .collect(),
+        None => Vec::new(),
+    }
+}
+
+/// TMDb ids are only unique per media type, so they are passed around as `movie/603` or `tv/1399`.
+fn parse_tmdb_id(id: &str) -> Option<(TmdbMediaType, u64)> {
+    let (media_type, tmdb_id) = id.split_once('/')?;
+    let media_type = TmdbMediaType::parse(media_type)?;
+    let tmdb_id = tmdb_id.parse().ok()?;
+
+    Some((media_type, tmdb_id))
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum TmdbMediaType {
+    Movie,
+    Tv,
+}
+
+impl TmdbMediaType {
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "movie" => Some(Self::Movie),
+            "tv" => Some(Self::Tv),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Movie => "movie",
+            Self::Tv => "tv",
+        }
+    }
+
+    fn append_to_response(&self) -> &'static str {
+        match self {
+            Self::Movie => "credits,release_dates,videos",
+            Self::Tv => "credits,content_ratings,external_ids,videos",
+        }
+    }
+
+    fn movie_type(&self) -> &'static str {
+        match self {
+            Self::Movie => "Movie",
+            Self::Tv => "TVSeries",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TmdbSearchResult {
+    pub id: u64,
+    pub media_type: String,
+    pub title: Option<String>,
+    pub name: Option<String>,
+    pub release_date: Option<String>,
+    pub first_air_date: Option<String>,
+}
+
+impl TmdbSearchResult {
+    fn into_search_result(self) -> Option<SearchResult> {
+        // `search/multi` also returns people
+        let media_type = TmdbMediaType::parse(&self.media_type)?;
+        let title = self.title.or(self.name)?;
+        let description = self
+            .release_date
+            .or(self.first_air_date)
+            .and_then(|date| date.get(..4).map(|year| format!("({})", year)))
+            .unwrap_or_default();
+
+        Some(SearchResult {
+            id: format!("{}/{}", media_type.as_str(), self.id),
+            title,
+            description,
+            link: format!(
+                "https://www.themoviedb.org/{}/{}",
+                media_type.as_str(),
+                self.id
+            ),
+        })
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TmdbMovieInfo {
+    pub id: u64,
+    pub title: Option<String>,
+    pub name: Option<String>,
+    pub poster_path: Option<String>,
+    pub release_date: Option<String>,
+    pub first_air_date: Option<String>,
+    pub runtime: Option<u32>,
+    #[serde(default)]
+    pub episode_run_time: Vec<u32>,
+    pub overview: Option<String>,
+    #[serde(default)]
+    pub genres: Vec<NamedObj>,
+    #[serde(default)]
+    pub production_countries: Vec<NamedObj>,
+    #[serde(default)]
+    pub spoken_languages: Vec<SpokenLanguageObj>,
+    #[serde(default)]
+    pub created_by: Vec<NamedObj>,
+    pub imdb_id: Option<String>,
+    pub external_ids: Option<ExternalIdsObj>,
+    #[serde(default)]
+    pub credits: CreditsObj,
+    pub release_dates: Option<CountryResultsObj<ReleaseDatesObj>>,
+    pub content_ratings: Option<CountryResultsObj<ContentRatingObj>>,
+    pub videos: Option<VideosObj>,
+}
+
+impl TmdbMovieInfo {
+    fn into_movie_info(self, media_type: TmdbMediaType) -> MovieInfo {
+        let title = self.title.or(self.name).unwrap_or_default();
+        let movie_type = media_type.movie_type().to_string();
+        let release_date = self
+            .release_date
+            .or(self.first_air_date)
+            .and_then(|date| NaiveDate::parse_from_str(&date, "%F").ok());
+        let year = release_date.map(|date| date.year() as u32);
+        let image = self
+            .poster_path
+            .map(|path| format!("https://image.tmdb.org/t/p/original{}", path))
+            .unwrap_or_default();
+        let runtime = self
+            .runtime
+            .or_else(|| self.episode_run_time.first().copied())
+            .filter(|rt| *rt > 0);
+        let plot = self.overview.unwrap_or_default();
+        let director_list = match media_type {
+            TmdbMediaType::Movie => self
+                .credits
+                .crew
+                .iter()
+                .filter(|c| c.job.as_deref() == Some("Director"))
+                .map(|c| c.name.to_string())
+                .collect(),
+            TmdbMediaType::Tv => self.created_by.iter().map(|c| c.name.to_string()).collect(),
+        };
+        let star_list = self
+            .credits
+            .cast
+            .iter()
+            .take(5)
+            .map(|c| c.name.to_string())
+            .collect();
+        let cast = self
+            .credits
+            .cast
+            .into_iter()
+            .map(|c| CastMember {
+                name: c.name,
+                character: c.character.unwrap_or_default(),
+            })
+            .collect();
+        let trailer = self.videos.and_then(|v| {
+            v.results
+                .into_iter()
+                .find(|v| v.site == "YouTube" && v.video_type == "Trailer")
+                .map(|v| format!("https://www.youtube.com/watch?v={}", v.key))
+        });
+        let genre_list = self.genres.iter().map(|g| g.name.to_string()).collect();
+        let country_list = self
+            .production_countries
+            .iter()
+            .map(|c| c.name.to_string())
+            .collect();
+        let language_list = self
+            .spoken_languages
+            .iter()
+            .map(|l| l.english_name.to_string())
+            .collect();
+        let content_rating = match media_type {
+            TmdbMediaType::Movie => self.release_dates.and_then(|r| r.us()).and_then(|r| {
+                r.release_dates
+                    .into_iter()
+                    .map(|d| d.certification)
+                    .find(|c| !c.is_empty())
+            }),
+            TmdbMediaType::Tv => self.content_ratings.and_then(|r| r.us()).map(|r| r.rating),
+        }
+        .unwrap_or_default();
+        let imdb_id = self
+            .imdb_id
+            .or_else(|| self.external_ids.and_then(|ids| ids.imdb_id))
+            .filter(|id| !id.is_empty());
+        let imdb_link = match imdb_id {
+            Some(imdb_id) => format!("https://www.imdb.com/title/{}", imdb_id),
+            None => format!(
+                "https://www.themoviedb.org/{}/{}",
+                media_type.as_str(),
+                self.id
+            ),
+        };
+
+        MovieInfo {
+            title,
+            movie_type,
+            year,
+            image,
+            trailer,
+            release_date,
+            runtime,
+            plot,
+            director_list,
+            star_list,
+            cast,
+            genre_list,
+            country_list,
+            language_list,
+            content_rating,
+            // TMDb has its own user score, which is not comparable with IMDb ratings
+            imdb_rating: None,
+            rotten_tomatoes_rating: None,
+            metacritic_rating: None,
+            imdb_link,
+            provider: Provider::Tmdb,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct NamedObj {
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SpokenLanguageObj {
+    pub english_name: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ExternalIdsObj {
+    pub imdb_id: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct CreditsObj {
+    #[serde(default)]
+    pub cast: Vec<CastObj>,
+    #[serde(default)]
+    pub crew: Vec<CrewObj>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CastObj {
+    pub name: String,
+    pub character: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct VideosObj {
+    pub results: Vec<VideoObj>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct VideoObj {
+    pub key: String,
+    pub site: String,
+    #[serde(rename = "type")]
+    pub video_type: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CrewObj {
+    pub name: String,
+    pub job: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CountryResultsObj<T> {
+    pub results: Vec<T>,
+}
+
+impl<T: CountryResult> CountryResultsObj<T> {
+    fn us(self) -> Option<T> {
+        self.results.into_iter().find(|r| r.country() == "US")
+    }
+}
+
+pub trait CountryResult {
+    fn country(&self) -> &str;
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ReleaseDatesObj {
+    pub iso_3166_1: String,
+    pub release_dates: Vec<CertificationObj>,
+}
+
+impl CountryResult for ReleaseDatesObj {
+    fn country(&self) -> &str {
+        &self.iso_3166_1
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CertificationObj {
+    pub certification: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ContentRatingObj {
+    pub iso_3166_1: String,
+    pub rating: String,
+}
+
+impl CountryResult for ContentRatingObj {
+    fn country(&self) -> &str {
+        &self.iso_3166_1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::serve;
+    use axum::extract::{Path, Query};
+    use axum::routing::get;
+    use axum::{Json, Router};
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    /// Serves the search and the movie details on a local port, only for the key `good_key`.
+    fn mock_tmdb_api() -> String {
+        fn authorized(query: &HashMap<String, String>) -> Option<Value> {
+            if query.get("api_key").map(String::as_str) == Some("good_key")
+                && query.get("language").map(String::as_str) == Some("en-US")
+            {
+                None
+            } else {
+                Some(json!({
+                    "success": false,
+                    "status_code": 7,
+                    "status_message": "Invalid API key: You must be granted a valid key."
+                }))
+            }
+        }
+
+        let app = Router::new()
+            .route(
+                "/3/search/multi",
+                get(|Query(query): Query<HashMap<String, String>>| async move {
+                    Json(authorized(&query).unwrap_or_else(|| {
+                        json!({
+                            "page": 1,
+                            "results": [
+                                { "id": 603, "media_type": "movie", "title": "The Matrix", "release_date": "1999-03-30" },
+                                { "id": 6384, "media_type": "person", "name": "Keanu Reeves" }
+                            ]
+                        })
+                    }))
+                }),
+            )
+            .route(
+                "/3/movie/:id",
+                get(
+                    |Path(id): Path<u64>, Query(query): Query<HashMap<String, String>>| async move {
+                        Json(authorized(&query).unwrap_or_else(|| {
+                            json!({
+                                "id": id,
+                                "title": "The Matrix",
+                                "release_date": "1999-03-30",
+                                "runtime": 136,
+                                "imdb_id": "tt0133093"
+                            })
+                        }))
+                    },
+                ),
+            );
+
+        serve(app)
+    }
+
+    #[tokio::test]
+    async fn test_request_tmdb_api() {
+        let api = TmdbApi::new("good_key".to_string()).with_url(mock_tmdb_api());
+        let client = Client::new();
+        let lang = Language::En;
+
+        let results = api.search(&client, "", "matrix", 5, lang).await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].id, "movie/603");
+
+        let info = api
+            .request_movie_information(&client, "", &results[0].id, lang)
+            .await
+            .unwrap();
+        assert_eq!(info.title, "The Matrix");
+        assert_eq!(info.runtime, Some(136));
+        assert_eq!(info.imdb_link, "https://www.imdb.com/title/tt0133093");
+
+        // the user's own key goes first
+        let error = api
+            .search(&client, "bad_key", "matrix", 5, lang)
+            .await
+            .unwrap_err();
+        assert!(matches!(error, BotError::FeedBack(_)));
+        assert!(api
+            .request_movie_information(&client, "bad_key", "movie/603", lang)
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn test_parse_search_results() {
+        let data = json!({
+            "page": 1,
+            "results": [
+                { "id": 603, "media_type": "movie", "title": "The Matrix", "release_date": "1999-03-30" },
+                { "id": 6384, "media_type": "person", "name": "Keanu Reeves" },
+                { "id": 1399, "media_type": "tv", "name": "Game of Thrones", "first_air_date": "2011-04-17" },
+                { "id": 604, "media_type": "movie", "title": "The Matrix Reloaded", "release_date": "" }
+            ]
+        });
+
+        let results = parse_search_results(&data, 5);
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].id, "movie/603");
+        assert_eq!(results[0].description, "(1999)");
+        assert_eq!(results[0].link, "https://www.themoviedb.org/movie/603");
+        assert_eq!(results[1].id, "tv/1399");
+        assert_eq!(results[1].title, "Game of Thrones");
+        assert_eq!(results[2].description, "");
+
+        assert_eq!(parse_search_results(&data, 1).len(), 1);
+        assert!(parse_search_results(&json!({}), 5).is_empty());
+    }
+
+    #[test]
+    fn test_parse_tmdb_id() {
+        assert_eq!(
+            parse_tmdb_id("movie/603"),
+            Some((TmdbMediaType::Movie, 603))
+        );
+        assert_eq!(parse_tmdb_id("tv/1399"), Some((TmdbMediaType::Tv, 1399)));
+        assert!(parse_tmdb_id("person/6384").is_none());
+        assert!(parse_tmdb_id("tt0133093").is_none());
+    }
+
+    #[test]
+    fn test_movie_info_from_tmdb_details() {
+        let data = json!({
+            "id": 603,
+            "title": "The Matrix",
+            "poster_path": "/f89U3ADr1oiB1s9GkdPOEpXUk5H.jpg",
+            "release_date": "1999-03-30",
+            "runtime": 136,
+            "overview": "Set in the 22nd century...",
+            "genres": [{ "id": 28, "name": "Action" }, { "id": 878, "name": "Science Fiction" }],
+            "production_countries": [{ "iso_3166_1": "US", "name": "United States of America" }],
+            "spoken_languages": [{ "english_name": "English", "iso_639_1": "en", "name": "English" }],
+            "imdb_id": "tt0133093",
+            "credits": {
+                "cast": [
+                    { "name": "Keanu Reeves", "character": "Neo" },
+                    { "name": "Laurence Fishburne", "character": "Morpheus" }
+                ],
+                "crew": [
+                    { "name": "Lana Wachowski", "job": "Director" },
+                    { "name": "Joel Silver", "job": "Producer" },
+                    { "name": "Lilly Wachowski", "job": "Director" }
+                ]
+            },
+            "release_dates": {
+                "results": [
+                    { "iso_3166_1": "DE", "release_dates": [{ "certification": "16" }] },
+                    { "iso_3166_1": "US", "release_dates": [{ "certification": "" }, { "certification": "R" }] }
+                ]
+            },
+            "videos": {
+                "results": [
+                    { "key": "L0fw0WzFaBM", "site": "YouTube", "type": "Featurette" },
+                    { "key": "vKQi3bBA1y8", "site": "YouTube", "type": "Trailer" }
+                ]
+            }
+        });
+
+        let info: TmdbMovieInfo = serde_json::from_value(data).unwrap();
+        let info = info.into_movie_info(TmdbMediaType::Movie);
+
+        assert_eq!(info.title, "The Matrix");
+        assert_eq!(info.movie_type, "Movie");
+        assert_eq!(info.year, Some(1999));
+        assert_eq!(info.runtime, Some(136));
+        assert_eq!(
+            info.director_list,
+            vec!["Lana Wachowski", "Lilly Wachowski"]
+        );
+        assert_eq!(info.star_list, vec!["Keanu Reeves", "Laurence Fishburne"]);
+        assert_eq!(
+            info.cast[1],
+            CastMember {
+                name: "Laurence Fishburne".to_string(),
+                character: "Morpheus".to_string()
+            }
+        );
+        assert_eq!(
+            info.trailer.as_deref(),
+            Some("https://www.youtube.com/watch?v=vKQi3bBA1y8")
+        );
+        assert_eq!(info.genre_list, vec!["Action", "Science Fiction"]);
+        assert_eq!(info.content_rating, "R");
+        assert_eq!(info.imdb_link, "https://www.imdb.com/title/tt0133093");
+        assert!(info.imdb_rating.is_none());
+    }
+}
@@ -0,0 +1,253 @@
+use crate::api::MovieField;
+
+/// What a button below a bot message asks for. Telegram limits callback data to 64 bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CallbackData {
+    /// Add the title with the id to the movie list, unless it's already there.
+    Add(String),
+    /// Add the title even though it's already on the list.
+    AddAnyway(String),
+    /// Overwrite the existing page with fresh information of the title.
+    UpdateExisting { page_id: String, id: String },
+    /// Refresh the ratings and metadata of a page in the movie list.
+    Refresh(String),
+}
+
+impl CallbackData {
+    pub fn parse(data: &str) -> Self {
+        if let Some(id) = data.strip_prefix("force:") {
+            return Self::AddAnyway(id.to_string());
+        }
+
+        if let Some(page_id) = data.strip_prefix("refresh:") {
+            return Self::Refresh(page_id.to_string());
+        }
+
+        if let Some((page_id, id)) = data
+            .strip_prefix("update:")
+            .and_then(|data| data.split_once(':'))
+        {
+            return Self::UpdateExisting {
+                page_id: page_id.to_string(),
+                id: id.to_string(),
+            };
+        }
+
+        // plain ids are what the search result buttons have always sent
+        Self::Add(data.to_string())
+    }
+
+    pub fn encode(&self) -> String {
+        match self {
+            Self::Add(id) => id.to_string(),
+            Self::AddAnyway(id) => format!("force:{}", id),
+            // Notion accepts page ids without dashes, which saves some bytes
+            Self::UpdateExisting { page_id, id } => {
+                format!("update:{}:{}", page_id.replace('-', ""), id)
+            }
+            Self::Refresh(page_id) => format!("refresh:{}", page_id.replace('-', "")),
+        }
+    }
+}
+
+/// What a button below a title added to the movie list tells about it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiaryAction {
+    /// Mark the page as watched today.
+    Watched(String),
+    /// Show the ratings to choose from.
+    Rate(String),
+    /// Give the page the rating.
+    Rating { page_id: String, rating: u32 },
+}
+
+impl DiaryAction {
+    /// `None` if the data belongs to another button.
+    pub fn parse(data: &str) -> Option<Self> {
+        if let Some(page_id) = data.strip_prefix("watched:") {
+            return Some(Self::Watched(page_id.to_string()));
+        }
+
+        if let Some(page_id) = data.strip_prefix("rate:") {
+            return Some(Self::Rate(page_id.to_string()));
+        }
+
+        let (page_id, rating) = data.strip_prefix("rating:")?.split_once(':')?;
+
+        Some(Self::Rating {
+            page_id: page_id.to_string(),
+            rating: rating.parse().ok()?,
+        })
+    }
+
+    pub fn encode(&self) -> String {
+        match self {
+            Self::Watched(page_id) => format!("watched:{}", page_id.replace('-', "")),
+            Self::Rate(page_id) => format!("rate:{}", page_id.replace('-', "")),
+            Self::Rating { page_id, rating } => {
+                format!("rating:{}:{}", page_id.replace('-', ""), rating)
+            }
+        }
+    }
+}
+
+/// What a button of the database template keyboard after `/create_notion_db` asks for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TemplateAction {
+    /// Include the field in the database or leave it out.
+    Toggle(MovieField),
+    /// Use the icon with the index in `DATABASE_ICONS`.
+    Icon(usize),
+    Create,
+    Cancel,
+}
+
+impl TemplateAction {
+    /// `None` if the data belongs to another button.
+    pub fn parse(data: &str) -> Option<Self> {
+        let action = data.strip_prefix("template:")?;
+
+        if let Some(field) = action.strip_prefix("toggle:") {
+            return field.parse().ok().map(Self::Toggle);
+        }
+
+        if let Some(index) = action.strip_prefix("icon:") {
+            return index.parse().ok().map(Self::Icon);
+        }
+
+        match action {
+            "create" => Some(Self::Create),
+            "cancel" => Some(Self::Cancel),
+            _ => None,
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        match self {
+            Self::Toggle(field) => format!("template:toggle:{}", field.code()),
+            Self::Icon(index) => format!("template:icon:{}", index),
+            Self::Create => "template:create".to_string(),
+            Self::Cancel => "template:cancel".to_string(),
+        }
+    }
+}
+
+/// What a button below a step of the setup after `/start` asks for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetupAction {
+    /// Leave the optional step out.
+    Skip,
+    Cancel,
+}
+
+impl SetupAction {
+    /// `None` if the data belongs to another button.
+    pub fn parse(data: &str) -> Option<Self> {
+        match data.strip_prefix("setup:")? {
+            "skip" => Some(Self::Skip),
+            "cancel" => Some(Self::Cancel),
+            _ => None,
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        match self {
+            Self::Skip => "setup:skip".to_string(),
+            Self::Cancel => "setup:cancel".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_callback_data_round_trip() {
+        let data = [
+            CallbackData::Add("imdb_api:tt0133093".to_string()),
+            CallbackData::AddAnyway("tmdb:movie/603".to_string()),
+            CallbackData::UpdateExisting {
+                page_id: "25195fba545b4a5636d20ae776bf3189".to_string(),
+                id: "tmdb:movie/603".to_string(),
+            },
+            CallbackData::Refresh("25195fba545b4a5636d20ae776bf3189".to_string()),
+        ];
+
+        for data in data {
+            assert!(data.encode().len() <= 64);
+            assert_eq!(CallbackData::parse(&data.encode()), data);
+        }
+
+        assert_eq!(
+            CallbackData::parse("tt0133093"),
+            CallbackData::Add("tt0133093".to_string())
+        );
+        assert_eq!(
+            CallbackData::UpdateExisting {
+                page_id: "25195fba-545b-4a56-36d2-0ae776bf3189".to_string(),
+                id: "tt0133093".to_string(),
+            }
+            .encode(),
+            "update:25195fba545b4a5636d20ae776bf3189:tt0133093"
+        );
+    }
+
+    #[test]
+    fn test_diary_action_round_trip() {
+        let page_id = "25195fba545b4a5636d20ae776bf3189".to_string();
+        let actions = [
+            DiaryAction::Watched(page_id.clone()),
+            DiaryAction::Rate(page_id.clone()),
+            DiaryAction::Rating {
+                page_id,
+                rating: 10,
+            },
+        ];
+
+        for action in actions {
+            assert!(action.encode().len() <= 64);
+            assert_eq!(DiaryAction::parse(&action.encode()), Some(action));
+        }
+
+        assert_eq!(DiaryAction::parse("tt0133093"), None);
+        assert_eq!(
+            DiaryAction::parse("refresh:25195fba545b4a5636d20ae776bf3189"),
+            None
+        );
+        assert_eq!(
+            DiaryAction::parse("rating:25195fba545b4a5636d20ae776bf3189:x"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_template_action_round_trip() {
+        let actions = MovieField::ALL
+            .into_iter()
+            .map(TemplateAction::Toggle)
+            .chain([
+                TemplateAction::Icon(3),
+                TemplateAction::Create,
+                TemplateAction::Cancel,
+            ]);
+
+        for action in actions {
+            assert!(action.encode().len() <= 64);
+            assert_eq!(TemplateAction::parse(&action.encode()), Some(action));
+        }
+
+        assert_eq!(TemplateAction::parse("tt0133093"), None);
+        assert_eq!(TemplateAction::parse("template:toggle:rating"), None);
+    }
+
+    #[test]
+    fn test_setup_action_round_trip() {
+        for action in [SetupAction::Skip, SetupAction::Cancel] {
+            assert_eq!(SetupAction::parse(&action.encode()), Some(action));
+        }
+
+        assert_eq!(SetupAction::parse("template:cancel"), None);
+        assert_eq!(SetupAction::parse("setup:restart"), None);
+    }
+}
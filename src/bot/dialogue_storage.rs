@@ -0,0 +1,126 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use sqlx::PgPool;
+use teloxide::dispatching::dialogue::Storage;
+use teloxide::types::ChatId;
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+type StorageFuture<T> = Pin<Box<dyn Future<Output = anyhow::Result<T>> + Send>>;
+
+/// Keeps the dialogues in Postgres, so a conversation carries on after a restart.
+pub struct PgDialogueStorage {
+    pg_pool: PgPool,
+}
+
+impl PgDialogueStorage {
+    pub fn new(pg_pool: PgPool) -> Self {
+        Self { pg_pool }
+    }
+}
+
+impl<D> Storage<D> for PgDialogueStorage
+where
+    D: Serialize + DeserializeOwned + Send + 'static,
+{
+    type Error = anyhow::Error;
+
+    fn remove_dialogue(self: Arc<Self>, chat_id: ChatId) -> StorageFuture<()>
+    where
+        D: Send + 'static,
+    {
+        Box::pin(async move {
+            sqlx::query(
+                r#"
+DELETE FROM dialogues
+WHERE chat_id = $1
+        "#,
+            )
+            .bind(chat_id.0)
+            .execute(&self.pg_pool)
+            .await?;
+
+            Ok(())
+        })
+    }
+
+    fn update_dialogue(self: Arc<Self>, chat_id: ChatId, dialogue: D) -> StorageFuture<()>
+    where
+        D: Send + 'static,
+    {
+        Box::pin(async move {
+            let dialogue = serialize_dialogue(&dialogue)?;
+
+            sqlx::query(
+                r#"
+INSERT INTO dialogues ( chat_id, dialogue )
+VALUES ( $1, $2 )
+ON CONFLICT ( chat_id ) DO UPDATE
+SET dialogue = EXCLUDED.dialogue
+        "#,
+            )
+            .bind(chat_id.0)
+            .bind(dialogue)
+            .execute(&self.pg_pool)
+            .await?;
+
+            Ok(())
+        })
+    }
+
+    fn get_dialogue(self: Arc<Self>, chat_id: ChatId) -> StorageFuture<Option<D>> {
+        Box::pin(async move {
+            let dialogue = sqlx::query_as::<_, (String,)>(
+                r#"
+SELECT dialogue
+FROM dialogues
+WHERE chat_id = $1
+        "#,
+            )
+            .bind(chat_id.0)
+            .fetch_optional(&self.pg_pool)
+            .await?
+            .and_then(|(dialogue,)| deserialize_dialogue(&dialogue));
+
+            Ok(dialogue)
+        })
+    }
+}
+
+fn serialize_dialogue<D: Serialize>(dialogue: &D) -> anyhow::Result<String> {
+    Ok(serde_json::to_string(dialogue)?)
+}
+
+/// `None` for a dialogue from an older version of the bot, it starts over.
+fn deserialize_dialogue<D: DeserializeOwned>(dialogue: &str) -> Option<D> {
+    serde_json::from_str(dialogue).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::setup_wizard::SetupState;
+
+    #[test]
+    fn test_serialized_dialogue() {
+        assert_eq!(
+            serialize_dialogue(&SetupState::NotionPage).unwrap(),
+            r#""NotionPage""#
+        );
+
+        for state in [
+            SetupState::Idle,
+            SetupState::NotionToken,
+            SetupState::NotionPage,
+            SetupState::ImdbKey,
+        ] {
+            let dialogue = serialize_dialogue(&state).unwrap();
+            assert_eq!(deserialize_dialogue::<SetupState>(&dialogue), Some(state));
+        }
+
+        assert_eq!(deserialize_dialogue::<SetupState>(r#""TmdbKey""#), None);
+        assert_eq!(deserialize_dialogue::<SetupState>("not json"), None);
+    }
+}
@@ -1,40 +1,25 @@
 use crate::api::*;
-use crate::bot::{get_user_from_msg, BotWork, Database, HandlerResult, MovieInfoApi};
-use crate::error::{feedback_error, propagate_error};
+use crate::bot::callback_data::{CallbackData, DiaryAction, TemplateAction};
+use crate::bot::mapping_command::MappingCommand;
+use crate::bot::notion_oauth;
+use crate::bot::{
+    get_user_from_msg, ApiRateLimiter, BotWork, Database, HandlerResult, ImdbUsageApi,
+    ImdbUsageWarnings, MovieInfoApi, NotionOAuthApi,
+};
+use crate::db::{NotionOAuthRequest, NotionToken};
+use crate::error::{feedback_error, propagate_error, BotError};
+use crate::secret::mask_secret;
 use crate::{transcripts, Language};
 
 use anyhow::anyhow;
-use reqwest::Client;
+use chrono::{NaiveDate, Utc};
+use reqwest::{Client, Url};
 use teloxide::prelude::*;
 use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup, ParseMode};
 use teloxide::Bot;
 
 use crate::config::CONFIG;
 
-// start command handler
-pub async fn start(bot: AutoSend<Bot>, msg: Message, database: Database) -> HandlerResult {
-    BotWork::new(&bot, msg.chat.id)
-        .do_it(async {
-            let user = get_user_from_msg(&msg)?;
-            let user_id = user.id.0;
-
-            let lang = Language::default();
-            // check if user's config is already in database
-            let user_tokens = database.get_user_tokens(user_id, lang).await?;
-
-            if user_tokens.is_none() {
-                database.init_user_tokens(user_id).await?;
-            }
-
-            bot.send_message(msg.chat.id, transcripts::transcript_welcome(lang))
-                .await
-                .map_err(propagate_error)?;
-
-            Ok(())
-        })
-        .await
-}
-
 pub async fn help(bot: AutoSend<Bot>, msg: Message) -> HandlerResult {
     let help_text = transcripts::help_message(Language::default(), &CONFIG.help_page);
     bot.send_message(msg.chat.id, help_text)
@@ -66,6 +51,8 @@ pub async fn set_imdb_token(
     msg: Message,
     token: String,
     database: Database,
+    client: Client,
+    imdb_usage_api: ImdbUsageApi,
 ) -> HandlerResult {
     BotWork::new(&bot, msg.chat.id)
         .do_it(async {
@@ -80,10 +67,20 @@ pub async fn set_imdb_token(
                 )));
             }
 
+            let secret_deleted = delete_secret_message(&bot, &msg).await;
+            imdb_usage_api
+                .validate_api_key(&client, token, lang)
+                .await?;
             let success = database.store_imdb_token(user_id, token, lang).await?;
 
             let reply_text = success
-                .then(|| transcripts::imdb_token_set_as(lang, token))
+                .then(|| {
+                    secret_confirmation(
+                        transcripts::imdb_token_set_as(lang, &mask_secret(token)),
+                        secret_deleted,
+                        lang,
+                    )
+                })
                 .ok_or_else(|| {
                     feedback_error(anyhow!(transcripts::configure_again(lang).to_string()))
                 })?;
@@ -102,6 +99,8 @@ pub async fn set_notion_token(
     msg: Message,
     token: String,
     database: Database,
+    client: Client,
+    notion_oauth: NotionOAuthApi,
 ) -> HandlerResult {
     BotWork::new(&bot, msg.chat.id)
         .do_it(async {
@@ -111,15 +110,35 @@ pub async fn set_notion_token(
 
             let lang = Language::default();
             if token.is_empty() {
+                if notion_oauth.is_enabled() {
+                    return send_notion_authorization_link(
+                        &bot,
+                        &msg,
+                        user_id,
+                        &database,
+                        &notion_oauth,
+                        lang,
+                    )
+                    .await;
+                }
+
                 return Err(feedback_error(anyhow!(
                     transcripts::input_empty_notion_token(lang)
                 )));
             }
 
+            let secret_deleted = delete_secret_message(&bot, &msg).await;
+            validate_notion_token(&client, &CONFIG.notion_api_url, token, lang).await?;
             let success = database.store_notion_token(user_id, token, lang).await?;
 
             let reply_text = success
-                .then(|| transcripts::notion_token_set_as(lang, token))
+                .then(|| {
+                    secret_confirmation(
+                        transcripts::notion_token_set_as(lang, &mask_secret(token)),
+                        secret_deleted,
+                        lang,
+                    )
+                })
                 .ok_or_else(|| {
                     feedback_error(anyhow!(transcripts::configure_again(lang).to_string()))
                 })?;
@@ -133,17 +152,91 @@ pub async fn set_notion_token(
         .await
 }
 
+/// Deletes the user's message with a token in it, so the token doesn't stay in the chat history.
+/// Returns whether it's gone, the bot isn't allowed to delete messages in every chat.
+pub async fn delete_secret_message(bot: &AutoSend<Bot>, msg: &Message) -> bool {
+    match bot.delete_message(msg.chat.id, msg.id).await {
+        Ok(_) => true,
+        Err(e) => {
+            log::warn!("failed to delete message with a token: {}", e);
+            false
+        }
+    }
+}
+
+/// The confirmation of a stored token, asking the user to delete their message if the bot couldn't.
+pub fn secret_confirmation(confirmation: String, secret_deleted: bool, lang: Language) -> String {
+    if secret_deleted {
+        confirmation
+    } else {
+        format!(
+            "{}\n\n{}",
+            confirmation,
+            transcripts::delete_token_message_yourself(lang)
+        )
+    }
+}
+
+/// Sends the link to the page where the user connects their workspace, the token arrives at the webhook server.
+async fn send_notion_authorization_link(
+    bot: &AutoSend<Bot>,
+    msg: &Message,
+    user_id: u64,
+    database: &Database,
+    notion_oauth: &NotionOAuthApi,
+    lang: Language,
+) -> Result<(), BotError> {
+    let url = notion_authorization_url(user_id, msg.chat.id, database, notion_oauth, lang).await?;
+    let keyboard = InlineKeyboardMarkup::new(vec![vec![InlineKeyboardButton::url(
+        transcripts::connect_notion(lang).to_string(),
+        url,
+    )]]);
+
+    bot.send_message(msg.chat.id, transcripts::connect_notion_workspace(lang))
+        .reply_markup(keyboard)
+        .await
+        .map_err(propagate_error)?;
+
+    Ok(())
+}
+
+/// A link to the authorization page for the user, which brings them back to `chat_id`.
+pub async fn notion_authorization_url(
+    user_id: u64,
+    chat_id: ChatId,
+    database: &Database,
+    notion_oauth: &NotionOAuthApi,
+    lang: Language,
+) -> Result<Url, BotError> {
+    // the user needs to be known before they can be sent back to us
+    database.user_tokens(user_id, lang).await?;
+
+    let state = notion_oauth::new_state();
+    let request = NotionOAuthRequest {
+        user_id,
+        chat_id: chat_id.0,
+    };
+    database
+        .store_notion_oauth_state(&state, &request, lang)
+        .await?;
+
+    notion_oauth.authorization_url(&state)
+}
+
 pub async fn handle_notion_page_link_or_id(
     bot: AutoSend<Bot>,
     msg: Message,
-    page_id: String,
+    input: String,
     database: Database,
-    client: Client,
 ) -> HandlerResult {
     let create_notion_database_on_page = BotWork::new(&bot, msg.chat.id);
     create_notion_database_on_page
         .do_it(async {
-            let page_id = page_id.trim();
+            // the page link has no spaces, everything after it is the title
+            let (page_id, title) = match input.trim().split_once(char::is_whitespace) {
+                Some((page_id, title)) => (page_id, Some(title)),
+                None => (input.trim(), None),
+            };
             let user = get_user_from_msg(&msg)?;
             let user_id = user.id.0;
 
@@ -163,13 +256,534 @@ pub async fn handle_notion_page_link_or_id(
             }
 
             let page_id = parse_notion_page_id_from_user_input(page_id, lang)?;
-            let db_id = create_database(&client, &notion_token, &page_id, lang).await?;
+            let mapping = database.property_mapping(user_id, lang).await?;
+            let template = DatabaseTemplate::new(&page_id, title, &mapping);
+            database
+                .store_database_template(user_id, &template, lang)
+                .await?;
+
+            bot.send_message(
+                msg.chat.id,
+                transcripts::database_template(lang, &template.icon, &template.title),
+            )
+            .reply_markup(database_template_keyboard(&template, lang))
+            .await
+            .map_err(propagate_error)?;
+
+            Ok(())
+        })
+        .await
+}
+
+pub async fn receive_database_template_action(
+    bot: AutoSend<Bot>,
+    q: CallbackQuery,
+    action: TemplateAction,
+    database: Database,
+    client: Client,
+    rate_limiter: ApiRateLimiter,
+) -> HandlerResult {
+    // stops the spinner on the button, the message itself is the answer
+    bot.answer_callback_query(q.id.clone()).await?;
+
+    if let Some(msg) = &q.message {
+        BotWork::new(&bot, msg.chat.id)
+            .do_it(async {
+                let user_id = q.from.id.0;
+
+                let lang = Language::default();
+                let mut template = database
+                    .database_template(user_id, lang)
+                    .await?
+                    .ok_or_else(|| {
+                        feedback_error(anyhow!(transcripts::database_template_expired(lang)))
+                    })?;
+
+                match action {
+                    TemplateAction::Toggle(field) => template.toggle(field),
+                    TemplateAction::Icon(index) => {
+                        if let Some(icon) = DATABASE_ICONS.get(index) {
+                            template.icon = icon.to_string();
+                        }
+                    }
+                    TemplateAction::Cancel => {
+                        database.remove_database_template(user_id, lang).await?;
+                        bot.edit_message_text(
+                            msg.chat.id,
+                            msg.id,
+                            transcripts::database_template_cancelled(lang),
+                        )
+                        .await
+                        .map_err(propagate_error)?;
+
+                        return Ok(());
+                    }
+                    TemplateAction::Create => {
+                        create_movie_list(
+                            user_id,
+                            &template,
+                            &database,
+                            &client,
+                            &rate_limiter,
+                            lang,
+                        )
+                        .await?;
+                        database.remove_database_template(user_id, lang).await?;
+
+                        bot.edit_message_text(
+                            msg.chat.id,
+                            msg.id,
+                            transcripts::notion_database_created(lang),
+                        )
+                        .await
+                        .map_err(propagate_error)?;
+
+                        return Ok(());
+                    }
+                }
+
+                database
+                    .store_database_template(user_id, &template, lang)
+                    .await?;
+                bot.edit_message_text(
+                    msg.chat.id,
+                    msg.id,
+                    transcripts::database_template(lang, &template.icon, &template.title),
+                )
+                .reply_markup(database_template_keyboard(&template, lang))
+                .await
+                .map_err(propagate_error)?;
+
+                Ok(())
+            })
+            .await?
+    }
+
+    Ok(())
+}
+
+/// Creates the database of `template` and makes it the user's movie list.
+pub async fn create_movie_list(
+    user_id: u64,
+    template: &DatabaseTemplate,
+    database: &Database,
+    client: &Client,
+    rate_limiter: &ApiRateLimiter,
+    lang: Language,
+) -> Result<(), BotError> {
+    let notion_token = database.notion_integration_token(user_id, lang).await?;
+
+    if notion_token.is_empty() {
+        return Err(feedback_error(anyhow!(
+            transcripts::need_notion_token_first(lang)
+        )));
+    }
+
+    let stored_mapping = database.property_mapping(user_id, lang).await?;
+    let mapping = template.apply(&stored_mapping);
+    rate_limiter.acquire(user_id, lang)?;
+    let db_id = create_database(client, &notion_token, template, &mapping, lang).await?;
+    let success = database
+        .store_notion_database_id(user_id, &db_id, lang)
+        .await?;
+
+    if !success {
+        return Err(feedback_error(anyhow!(transcripts::configure_again(lang))));
+    }
+
+    // the new movie list only has the properties of the template
+    for field in MovieField::ALL {
+        if mapping.get(field) != stored_mapping.get(field) {
+            database
+                .store_property_mapping(user_id, field, mapping.get(field), lang)
+                .await?;
+        }
+    }
+
+    Ok(())
+}
+
+fn database_template_keyboard(template: &DatabaseTemplate, lang: Language) -> InlineKeyboardMarkup {
+    let fields = MovieField::ALL
+        .into_iter()
+        .filter(|field| !field.is_required())
+        .map(|field| {
+            let mark = if template.includes(field) {
+                "✅"
+            } else {
+                "⬜"
+            };
+            InlineKeyboardButton::callback(
+                format!("{} {}", mark, field.default_name()),
+                TemplateAction::Toggle(field).encode(),
+            )
+        })
+        .collect::<Vec<_>>();
+    let icons = DATABASE_ICONS
+        .iter()
+        .enumerate()
+        .map(|(index, icon)| {
+            let text = if template.icon == *icon {
+                format!("[{}]", icon)
+            } else {
+                icon.to_string()
+            };
+            InlineKeyboardButton::callback(text, TemplateAction::Icon(index).encode())
+        })
+        .collect::<Vec<_>>();
+    let actions = vec![
+        InlineKeyboardButton::callback(
+            transcripts::create_database(lang),
+            TemplateAction::Create.encode(),
+        ),
+        InlineKeyboardButton::callback(transcripts::cancel(lang), TemplateAction::Cancel.encode()),
+    ];
+
+    let mut keyboard = fields.chunks(3).map(<[_]>::to_vec).collect::<Vec<_>>();
+    keyboard.push(icons);
+    keyboard.push(actions);
+
+    InlineKeyboardMarkup::new(keyboard)
+}
+
+pub async fn use_notion_database(
+    bot: AutoSend<Bot>,
+    msg: Message,
+    database_id: String,
+    database: Database,
+    client: Client,
+    rate_limiter: ApiRateLimiter,
+) -> HandlerResult {
+    BotWork::new(&bot, msg.chat.id)
+        .do_it(async {
+            let database_id = database_id.trim();
+            let user = get_user_from_msg(&msg)?;
+            let user_id = user.id.0;
+
+            let lang = Language::default();
+            if database_id.is_empty() {
+                return Err(feedback_error(anyhow!(
+                    transcripts::input_empty_notion_database_id(lang)
+                )));
+            }
+
+            let notion_token = database.notion_integration_token(user_id, lang).await?;
+
+            if notion_token.is_empty() {
+                return Err(feedback_error(anyhow!(
+                    transcripts::need_notion_token_first(lang)
+                )));
+            }
+
+            // database links look just like page links
+            let database_id = parse_notion_page_id_from_user_input(database_id, lang)?;
+            rate_limiter.acquire(user_id, lang)?;
+            let schema =
+                retrieve_database_schema(&client, &notion_token, &database_id, lang).await?;
+            let mut mapping = database.property_mapping(user_id, lang).await?;
+            let check = schema.check(&mapping);
+            if check.apply_title(&mut mapping) {
+                database
+                    .store_property_mapping(
+                        user_id,
+                        MovieField::Title,
+                        mapping.get(MovieField::Title),
+                        lang,
+                    )
+                    .await?;
+            }
             let success = database
-                .store_notion_database_id(user_id, &db_id, lang)
+                .store_notion_database_id(user_id, &schema.id, lang)
+                .await?;
+
+            let reply_text = success
+                .then(|| transcripts::notion_database_connected(lang))
+                .ok_or_else(|| feedback_error(anyhow!(transcripts::configure_again(lang))))?;
+
+            bot.send_message(msg.chat.id, reply_text)
+                .await
+                .map_err(propagate_error)?;
+
+            // the user may want to map their own properties before we add ours
+            if !check.is_compatible() {
+                let problems = [
+                    check.missing_properties(lang),
+                    check.mismatched_properties(lang),
+                ]
+                .into_iter()
+                .filter(|problems| !problems.is_empty())
+                .collect::<Vec<_>>()
+                .join("\n");
+
+                bot.send_message(
+                    msg.chat.id,
+                    transcripts::notion_database_needs_mapping(lang, &problems),
+                )
+                .await
+                .map_err(propagate_error)?;
+            }
+
+            Ok(())
+        })
+        .await
+}
+
+pub async fn check_notion_database(
+    bot: AutoSend<Bot>,
+    msg: Message,
+    database: Database,
+    client: Client,
+    rate_limiter: ApiRateLimiter,
+) -> HandlerResult {
+    BotWork::new(&bot, msg.chat.id)
+        .do_it(async {
+            let user = get_user_from_msg(&msg)?;
+            let user_id = user.id.0;
+
+            let lang = Language::default();
+            let user_tokens = database.user_tokens(user_id, lang).await?;
+
+            if !user_tokens.notion_token_is_good() {
+                return Err(feedback_error(anyhow!(user_tokens.user_hint(lang))));
+            }
+
+            let mut mapping = database.property_mapping(user_id, lang).await?;
+            rate_limiter.acquire(user_id, lang)?;
+            let check = check_and_repair_database(
+                &client,
+                &user_tokens.notion_token.integration_token,
+                &user_tokens.notion_token.database_id,
+                &mapping,
+                lang,
+            )
+            .await?;
+            // the user renamed the title property
+            if check.apply_title(&mut mapping) {
+                database
+                    .store_property_mapping(
+                        user_id,
+                        MovieField::Title,
+                        mapping.get(MovieField::Title),
+                        lang,
+                    )
+                    .await?;
+            }
+
+            bot.send_message(msg.chat.id, notion_database_report(&check, lang))
+                .await
+                .map_err(propagate_error)?;
+
+            Ok(())
+        })
+        .await
+}
+
+pub async fn map_property(
+    bot: AutoSend<Bot>,
+    msg: Message,
+    input: String,
+    database: Database,
+    client: Client,
+    rate_limiter: ApiRateLimiter,
+) -> HandlerResult {
+    BotWork::new(&bot, msg.chat.id)
+        .do_it(async {
+            let user = get_user_from_msg(&msg)?;
+            let user_id = user.id.0;
+
+            let lang = Language::default();
+            let reply_text = match MappingCommand::parse(&input, lang)? {
+                MappingCommand::Show => {
+                    let mapping = database.property_mapping(user_id, lang).await?;
+                    transcripts::property_mapping_summary(lang, &mapping.summary(lang))
+                }
+                MappingCommand::Map {
+                    field,
+                    name,
+                    property_type,
+                } => {
+                    let user_tokens = database.user_tokens(user_id, lang).await?;
+
+                    // the property is looked up in the movie list
+                    if !user_tokens.notion_token_is_good() {
+                        return Err(feedback_error(anyhow!(user_tokens.user_hint(lang))));
+                    }
+
+                    rate_limiter.acquire(user_id, lang)?;
+                    let schema = retrieve_database_schema(
+                        &client,
+                        &user_tokens.notion_token.integration_token,
+                        &user_tokens.notion_token.database_id,
+                        lang,
+                    )
+                    .await?;
+                    let property = resolve_property(field, &name, property_type, &schema, lang)?;
+                    database
+                        .store_property_mapping(user_id, field, Some(&property), lang)
+                        .await?;
+
+                    transcripts::property_mapped(
+                        lang,
+                        field.code(),
+                        &property.name,
+                        property.property_type.as_str(),
+                    )
+                }
+                MappingCommand::Omit(field) if field.is_required() => {
+                    return Err(feedback_error(anyhow!(transcripts::field_is_required(
+                        lang,
+                        field.code()
+                    ))));
+                }
+                MappingCommand::Omit(field) => {
+                    database
+                        .store_property_mapping(user_id, field, None, lang)
+                        .await?;
+
+                    transcripts::property_omitted(lang, field.code())
+                }
+                MappingCommand::Reset(field) => {
+                    database
+                        .reset_property_mapping(user_id, field, lang)
+                        .await?;
+
+                    match PropertyMapping::default().get(field) {
+                        Some(property) => {
+                            transcripts::property_mapping_reset(lang, field.code(), &property.name)
+                        }
+                        None => transcripts::property_omitted(lang, field.code()),
+                    }
+                }
+            };
+
+            bot.send_message(msg.chat.id, reply_text)
+                .await
+                .map_err(propagate_error)?;
+
+            Ok(())
+        })
+        .await
+}
+
+fn notion_database_report(check: &SchemaCheck, lang: Language) -> String {
+    if check.is_compatible() {
+        return transcripts::notion_database_is_fine(lang).to_string();
+    }
+
+    let mut report = Vec::new();
+
+    if !check.missing.is_empty() {
+        report.push(transcripts::notion_database_repaired(
+            lang,
+            &check.missing_properties(lang),
+        ));
+    }
+
+    if !check.mismatched.is_empty() {
+        report.push(transcripts::notion_database_incompatible(
+            lang,
+            &check.mismatched_properties(lang),
+        ));
+    }
+
+    report.join("\n\n")
+}
+
+pub async fn set_movie_info_provider(
+    bot: AutoSend<Bot>,
+    msg: Message,
+    input: String,
+    database: Database,
+    client: Client,
+    movie_info_api: MovieInfoApi,
+    imdb_usage_api: ImdbUsageApi,
+) -> HandlerResult {
+    BotWork::new(&bot, msg.chat.id)
+        .do_it(async {
+            let user = get_user_from_msg(&msg)?;
+            let user_id = user.id.0;
+
+            let lang = Language::default();
+            let available_providers = movie_info_api.providers();
+            let provider_list = provider_list(&available_providers);
+            let mut args = input.split_whitespace();
+
+            let provider = match args.next() {
+                Some(provider) => provider,
+                None => {
+                    let user_tokens = database.user_tokens(user_id, lang).await?;
+                    let current_provider = movie_info_api
+                        .prefer(user_tokens.movie_info_provider())
+                        .provider();
+
+                    bot.send_message(
+                        msg.chat.id,
+                        transcripts::movie_info_provider_summary(
+                            lang,
+                            current_provider.name(),
+                            &provider_list,
+                        ),
+                    )
+                    .await
+                    .map_err(propagate_error)?;
+
+                    return Ok(());
+                }
+            };
+
+            let provider = provider
+                .parse::<Provider>()
+                .ok()
+                .filter(|provider| available_providers.contains(provider))
+                .ok_or_else(|| {
+                    feedback_error(anyhow!(transcripts::unknown_movie_info_provider(
+                        lang,
+                        &provider_list
+                    )))
+                })?;
+
+            let token = args.next();
+
+            if token.is_some() && provider == Provider::ImdbDatasets {
+                return Err(feedback_error(anyhow!(
+                    transcripts::movie_info_provider_needs_no_token(lang, provider.name())
+                )));
+            }
+
+            // the key shouldn't stay in the chat either
+            let secret_deleted = match token {
+                Some(_) => delete_secret_message(&bot, &msg).await,
+                None => true,
+            };
+
+            if let (Some(token), Provider::ImdbApi) = (token, provider) {
+                imdb_usage_api
+                    .validate_api_key(&client, token, lang)
+                    .await?;
+            }
+
+            let mut success = database
+                .store_movie_info_provider(user_id, provider, lang)
                 .await?;
 
+            if let Some(token) = token {
+                success &= database
+                    .store_provider_token(user_id, provider, token, lang)
+                    .await?;
+            }
+
             let reply_text = success
-                .then(|| transcripts::notion_database_created(lang))
+                .then(|| {
+                    secret_confirmation(
+                        transcripts::movie_info_provider_set_as(
+                            lang,
+                            provider.name(),
+                            token.is_some(),
+                        ),
+                        secret_deleted,
+                        lang,
+                    )
+                })
                 .ok_or_else(|| feedback_error(anyhow!(transcripts::configure_again(lang))))?;
 
             bot.send_message(msg.chat.id, reply_text)
@@ -181,12 +795,55 @@ pub async fn handle_notion_page_link_or_id(
         .await
 }
 
+fn provider_list(providers: &[Provider]) -> String {
+    providers
+        .iter()
+        .map(|provider| format!("{} ({})", provider.code(), provider.name()))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+pub async fn imdb_api_usage(
+    bot: AutoSend<Bot>,
+    msg: Message,
+    database: Database,
+    client: Client,
+    imdb_usage_api: ImdbUsageApi,
+) -> HandlerResult {
+    BotWork::new(&bot, msg.chat.id)
+        .do_it(async {
+            let user = get_user_from_msg(&msg)?;
+            let user_id = user.id.0;
+
+            let lang = Language::default();
+            let imdb_token = database.imdb_token(user_id, lang).await?;
+            let usage = imdb_usage_api
+                .request_api_usage(&client, &imdb_token, lang)
+                .await?;
+
+            bot.send_message(
+                msg.chat.id,
+                transcripts::imdb_api_usage(lang, &usage.to_string(), !imdb_token.is_empty()),
+            )
+            .await
+            .map_err(propagate_error)?;
+
+            Ok(())
+        })
+        .await
+}
+
+// the dependencies of an endpoint come in as arguments
+#[allow(clippy::too_many_arguments)]
 pub async fn receive_keyword(
     bot: AutoSend<Bot>,
     msg: Message,
     database: Database,
     client: Client,
     movie_info_api: MovieInfoApi,
+    imdb_usage_api: ImdbUsageApi,
+    imdb_usage_warnings: ImdbUsageWarnings,
+    rate_limiter: ApiRateLimiter,
 ) -> HandlerResult {
     let search_on_imdb = BotWork::new(&bot, msg.chat.id);
 
@@ -206,8 +863,11 @@ pub async fn receive_keyword(
                 .text()
                 .ok_or_else(|| feedback_error(anyhow!(transcripts::input_empty_keyword(lang))))?
                 .trim();
+            let movie_info_api = movie_info_api.prefer(user_tokens.movie_info_provider());
+            let api_key = user_tokens.provider_token(movie_info_api.provider());
+            rate_limiter.acquire(user_id, lang)?;
             let search_results = movie_info_api
-                .search(&client, &user_tokens.imdb_token, title, 5)
+                .search(&client, api_key, title, 5, lang)
                 .await?;
 
             if search_results.is_empty() {
@@ -231,8 +891,8 @@ pub async fn receive_keyword(
                 bot.send_message(
                     msg.chat.id,
                     format!(
-                        "<a href=\"https://www.imdb.com/title/{}\"><b>{}</b></a>",
-                        search_result.id, search_result.title
+                        "<a href=\"{}\"><b>{}</b></a>",
+                        search_result.link, search_result.title
                     ),
                 )
                 .parse_mode(ParseMode::Html)
@@ -241,19 +901,570 @@ pub async fn receive_keyword(
                 .map_err(propagate_error)?;
             }
 
+            // the user's key is only used when IMDb-API is asked first
+            if movie_info_api.provider() == Provider::ImdbApi {
+                warn_about_imdb_api_usage(
+                    &bot,
+                    msg.chat.id,
+                    &client,
+                    &imdb_usage_api,
+                    &imdb_usage_warnings,
+                    api_key,
+                )
+                .await;
+            }
+
             Ok(())
         })
         .await
 }
 
+pub async fn refresh_movie_list(
+    bot: AutoSend<Bot>,
+    msg: Message,
+    database: Database,
+    client: Client,
+    movie_info_api: MovieInfoApi,
+    rate_limiter: ApiRateLimiter,
+) -> HandlerResult {
+    BotWork::new(&bot, msg.chat.id)
+        .do_it(async {
+            let user = get_user_from_msg(&msg)?;
+            let user_id = user.id.0;
+
+            let lang = Language::default();
+            let user_tokens = database.user_tokens(user_id, lang).await?;
+
+            if !user_tokens.notion_token_is_good() {
+                return Err(feedback_error(anyhow!(user_tokens.user_hint(lang))));
+            }
+
+            let movie_info_api = movie_info_api.prefer(user_tokens.movie_info_provider());
+            let api_key = user_tokens.provider_token(movie_info_api.provider());
+            let notion_token = &user_tokens.notion_token;
+            let mapping = database.property_mapping(user_id, lang).await?;
+
+            bot.send_message(msg.chat.id, transcripts::refreshing_movie_list(lang))
+                .await
+                .map_err(propagate_error)?;
+
+            let mut refreshed = 0;
+            let mut failed = 0;
+            // a refresh stopped by the rate limit goes on where it stopped
+            let mut start_cursor = database.refresh_cursor(user_id, lang).await?;
+
+            loop {
+                let query_result = query_movie_pages_in_notion_database(
+                    &client,
+                    &notion_token.integration_token,
+                    &notion_token.database_id,
+                    start_cursor.as_deref(),
+                    &mapping,
+                    lang,
+                )
+                .await?;
+
+                for page in &query_result.results {
+                    // stop where we are, the user can pick it up again later
+                    if let Err(e) = rate_limiter.acquire(user_id, lang) {
+                        database
+                            .store_refresh_cursor(user_id, start_cursor.as_deref(), lang)
+                            .await?;
+                        bot.send_message(
+                            msg.chat.id,
+                            transcripts::movie_list_refreshed(lang, refreshed, failed),
+                        )
+                        .await
+                        .map_err(propagate_error)?;
+
+                        return Err(e);
+                    }
+
+                    match refresh_movie_page(
+                        &client,
+                        &movie_info_api,
+                        api_key,
+                        &notion_token.integration_token,
+                        page,
+                        &mapping,
+                        lang,
+                    )
+                    .await
+                    {
+                        Ok(_) => refreshed += 1,
+                        Err(e) => {
+                            log::warn!("failed to refresh {}: {:?}", page.url, e.inner());
+                            failed += 1;
+                        }
+                    }
+                }
+
+                start_cursor = query_result.next_cursor;
+                database
+                    .store_refresh_cursor(user_id, start_cursor.as_deref(), lang)
+                    .await?;
+
+                if start_cursor.is_none() {
+                    break;
+                }
+            }
+
+            bot.send_message(
+                msg.chat.id,
+                transcripts::movie_list_refreshed(lang, refreshed, failed),
+            )
+            .await
+            .map_err(propagate_error)?;
+
+            Ok(())
+        })
+        .await
+}
+
+/// Fetches the title a page links to from its provider again and updates the page if it is
+/// outdated.
+async fn refresh_movie_page(
+    client: &Client,
+    movie_info_api: &ProviderChain,
+    api_key: &str,
+    notion_token: &str,
+    page: &PageObj,
+    mapping: &PropertyMapping,
+    lang: Language,
+) -> Result<MovieInfo, BotError> {
+    let id = page
+        .imdb_link(mapping)
+        .and_then(movie_id_from_link)
+        .ok_or_else(|| feedback_error(anyhow!(transcripts::invalid_movie_id(lang))))?;
+    let movie_info = movie_info_api
+        .refresh_movie_information(client, api_key, &id, lang)
+        .await?;
+
+    if page.is_outdated(&movie_info, mapping) {
+        refresh_movie_info_in_notion_page(
+            client,
+            notion_token,
+            &page.id,
+            &movie_info,
+            mapping,
+            lang,
+        )
+        .await?;
+    }
+
+    Ok(movie_info)
+}
+
+/// Inserts the title into the movie list, repairing the database first if Notion rejects it.
+async fn add_movie_to_notion_database(
+    client: &Client,
+    notion_token: &NotionToken,
+    movie_info: &MovieInfo,
+    mapping: &PropertyMapping,
+    lang: Language,
+) -> Result<PageObj, BotError> {
+    let result = insert_movie_info_to_notion_database(
+        client,
+        &notion_token.integration_token,
+        &notion_token.database_id,
+        movie_info,
+        mapping,
+        lang,
+    )
+    .await;
+
+    match result {
+        Err(e) => {
+            let mapping =
+                repair_rejected_movie_list(client, notion_token, mapping, e, lang).await?;
+
+            insert_movie_info_to_notion_database(
+                client,
+                &notion_token.integration_token,
+                &notion_token.database_id,
+                movie_info,
+                &mapping,
+                lang,
+            )
+            .await
+        }
+        result => result,
+    }
+}
+
+/// Writes `entry` to the page, repairing the database first if Notion rejects it.
+async fn write_diary_entry(
+    client: &Client,
+    notion_token: &NotionToken,
+    page_id: &str,
+    entry: &DiaryEntry,
+    mapping: &PropertyMapping,
+    lang: Language,
+) -> Result<(), BotError> {
+    // Watched and My Rating may be left out of the movie list
+    let field = entry.fields()[0];
+    if mapping.get(field).is_none() {
+        return Err(feedback_error(anyhow!(transcripts::field_left_out(
+            lang,
+            field.code()
+        ))));
+    }
+
+    let result = update_diary_in_notion_page(
+        client,
+        &notion_token.integration_token,
+        page_id,
+        entry,
+        mapping,
+        lang,
+    )
+    .await;
+
+    match result {
+        Err(e) => {
+            let mapping =
+                repair_rejected_movie_list(client, notion_token, mapping, e, lang).await?;
+
+            update_diary_in_notion_page(
+                client,
+                &notion_token.integration_token,
+                page_id,
+                entry,
+                &mapping,
+                lang,
+            )
+            .await
+        }
+        result => result,
+    }
+}
+
+/// Adds the missing properties after Notion rejected a request with `error`, and returns the mapping
+/// to try the request again with. Gives the error back unless something was fixed,
+/// so only then the request is worth another try.
+async fn repair_rejected_movie_list(
+    client: &Client,
+    notion_token: &NotionToken,
+    mapping: &PropertyMapping,
+    error: BotError,
+    lang: Language,
+) -> Result<PropertyMapping, BotError> {
+    if !error.inner().is::<NotionValidationError>() {
+        return Err(error);
+    }
+
+    let check = check_and_repair_database(
+        client,
+        &notion_token.integration_token,
+        &notion_token.database_id,
+        mapping,
+        lang,
+    )
+    .await?;
+
+    if !check.mismatched.is_empty() {
+        return Err(feedback_error(anyhow!(
+            transcripts::notion_database_incompatible(lang, &check.mismatched_properties(lang))
+        )));
+    }
+
+    let mut mapping = mapping.clone();
+    let retitled = check.apply_title(&mut mapping);
+
+    // nothing was missing, so it's not something we can fix
+    if check.missing.is_empty() && !retitled {
+        return Err(error);
+    }
+
+    Ok(mapping)
+}
+
+fn diary_entry_message(entry: &DiaryEntry, lang: Language) -> String {
+    match entry {
+        DiaryEntry::Watched(watched_on) => {
+            transcripts::marked_as_watched(lang, &watched_on.to_string())
+        }
+        DiaryEntry::Rating(rating) => transcripts::rated(lang, *rating, MAX_RATING),
+    }
+}
+
+/// The buttons below a title in the movie list.
+fn movie_page_keyboard(page_id: &str, lang: Language) -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::new([[
+        InlineKeyboardButton::callback(
+            transcripts::refresh(lang),
+            CallbackData::Refresh(page_id.to_string()).encode(),
+        ),
+        InlineKeyboardButton::callback(
+            transcripts::watched(lang),
+            DiaryAction::Watched(page_id.to_string()).encode(),
+        ),
+        InlineKeyboardButton::callback(
+            transcripts::rate(lang),
+            DiaryAction::Rate(page_id.to_string()).encode(),
+        ),
+    ]])
+}
+
+fn rating_keyboard(page_id: &str) -> InlineKeyboardMarkup {
+    let ratings = (1..=MAX_RATING)
+        .map(|rating| {
+            InlineKeyboardButton::callback(
+                rating.to_string(),
+                DiaryAction::Rating {
+                    page_id: page_id.to_string(),
+                    rating,
+                }
+                .encode(),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    InlineKeyboardMarkup::new(ratings.chunks(5).map(<[_]>::to_vec))
+}
+
+pub async fn mark_as_watched(
+    bot: AutoSend<Bot>,
+    msg: Message,
+    input: String,
+    database: Database,
+    client: Client,
+    rate_limiter: ApiRateLimiter,
+) -> HandlerResult {
+    BotWork::new(&bot, msg.chat.id)
+        .do_it(async {
+            let lang = Language::default();
+            let mut args = input.split_whitespace();
+            let link = args
+                .next()
+                .and_then(movie_link_from_user_input)
+                .ok_or_else(|| {
+                    feedback_error(anyhow!(transcripts::input_movie_link(lang, "watched")))
+                })?;
+            let watched_on = match args.next() {
+                Some(date) => date
+                    .parse::<NaiveDate>()
+                    .map_err(|_| feedback_error(anyhow!(transcripts::invalid_date(lang))))?,
+                None => Utc::now().date_naive(),
+            };
+
+            record_diary_entry(
+                &bot,
+                &msg,
+                &link,
+                &DiaryEntry::Watched(watched_on),
+                &database,
+                &client,
+                &rate_limiter,
+            )
+            .await
+        })
+        .await
+}
+
+pub async fn rate_movie(
+    bot: AutoSend<Bot>,
+    msg: Message,
+    input: String,
+    database: Database,
+    client: Client,
+    rate_limiter: ApiRateLimiter,
+) -> HandlerResult {
+    BotWork::new(&bot, msg.chat.id)
+        .do_it(async {
+            let lang = Language::default();
+            let mut args = input.split_whitespace();
+            let link = args
+                .next()
+                .and_then(movie_link_from_user_input)
+                .ok_or_else(|| {
+                    feedback_error(anyhow!(transcripts::input_movie_link(lang, "rate")))
+                })?;
+            let rating = args
+                .next()
+                .and_then(|rating| rating.parse::<u32>().ok())
+                .filter(|rating| (1..=MAX_RATING).contains(rating))
+                .ok_or_else(|| {
+                    feedback_error(anyhow!(transcripts::invalid_rating(lang, MAX_RATING)))
+                })?;
+
+            record_diary_entry(
+                &bot,
+                &msg,
+                &link,
+                &DiaryEntry::Rating(rating),
+                &database,
+                &client,
+                &rate_limiter,
+            )
+            .await
+        })
+        .await
+}
+
+/// Finds the title with `link` in the movie list and writes `entry` to its page.
+async fn record_diary_entry(
+    bot: &AutoSend<Bot>,
+    msg: &Message,
+    link: &str,
+    entry: &DiaryEntry,
+    database: &Database,
+    client: &Client,
+    rate_limiter: &ApiRateLimiter,
+) -> Result<(), BotError> {
+    let user = get_user_from_msg(msg)?;
+    let user_id = user.id.0;
+
+    let lang = Language::default();
+    let user_tokens = database.user_tokens(user_id, lang).await?;
+
+    if !user_tokens.notion_token_is_good() {
+        return Err(feedback_error(anyhow!(user_tokens.user_hint(lang))));
+    }
+
+    let notion_token = &user_tokens.notion_token;
+    let mapping = database.property_mapping(user_id, lang).await?;
+    rate_limiter.acquire(user_id, lang)?;
+    let page = find_movie_in_notion_database(
+        client,
+        &notion_token.integration_token,
+        &notion_token.database_id,
+        link,
+        &mapping,
+        lang,
+    )
+    .await?
+    .ok_or_else(|| feedback_error(anyhow!(transcripts::not_in_movie_list(lang))))?;
+
+    write_diary_entry(client, notion_token, &page.id, entry, &mapping, lang).await?;
+
+    bot.send_message(msg.chat.id, diary_entry_message(entry, lang))
+        .await
+        .map_err(propagate_error)?;
+
+    Ok(())
+}
+
+pub async fn receive_diary_action(
+    bot: AutoSend<Bot>,
+    q: CallbackQuery,
+    action: DiaryAction,
+    database: Database,
+    client: Client,
+    rate_limiter: ApiRateLimiter,
+) -> HandlerResult {
+    // stops the spinner on the button, the message itself is the answer
+    bot.answer_callback_query(q.id.clone()).await?;
+
+    if let Some(msg) = &q.message {
+        BotWork::new(&bot, msg.chat.id)
+            .do_it(async {
+                let user_id = q.from.id.0;
+
+                let lang = Language::default();
+                let (page_id, entry) = match &action {
+                    DiaryAction::Rate(page_id) => {
+                        bot.edit_message_reply_markup(msg.chat.id, msg.id)
+                            .reply_markup(rating_keyboard(page_id))
+                            .await
+                            .map_err(propagate_error)?;
+
+                        return Ok(());
+                    }
+                    DiaryAction::Watched(page_id) => {
+                        (page_id, DiaryEntry::Watched(Utc::now().date_naive()))
+                    }
+                    DiaryAction::Rating { page_id, rating } => {
+                        (page_id, DiaryEntry::Rating((*rating).clamp(1, MAX_RATING)))
+                    }
+                };
+
+                let user_tokens = database.user_tokens(user_id, lang).await?;
+
+                if !user_tokens.notion_token_is_good() {
+                    return Err(feedback_error(anyhow!(user_tokens.user_hint(lang))));
+                }
+
+                let mapping = database.property_mapping(user_id, lang).await?;
+                rate_limiter.acquire(user_id, lang)?;
+                write_diary_entry(
+                    &client,
+                    &user_tokens.notion_token,
+                    page_id,
+                    &entry,
+                    &mapping,
+                    lang,
+                )
+                .await?;
+
+                // the ratings make way for the usual buttons again
+                if let DiaryAction::Rating { .. } = action {
+                    bot.edit_message_reply_markup(msg.chat.id, msg.id)
+                        .reply_markup(movie_page_keyboard(page_id, lang))
+                        .await
+                        .map_err(propagate_error)?;
+                }
+
+                bot.send_message(msg.chat.id, diary_entry_message(&entry, lang))
+                    .reply_to_message_id(msg.id)
+                    .await
+                    .map_err(propagate_error)?;
+
+                Ok(())
+            })
+            .await?
+    }
+
+    Ok(())
+}
+
+async fn warn_about_imdb_api_usage(
+    bot: &AutoSend<Bot>,
+    chat_id: ChatId,
+    client: &Client,
+    imdb_usage_api: &ImdbUsageApi,
+    imdb_usage_warnings: &ImdbUsageWarnings,
+    api_key: &str,
+) {
+    let lang = Language::default();
+
+    if !imdb_usage_warnings.start_check(api_key) {
+        return;
+    }
+
+    // the search already succeeded, so failing to check the quota is not worth bothering the user
+    let usage = match imdb_usage_api
+        .request_api_usage(client, api_key, lang)
+        .await
+    {
+        Ok(usage) => usage,
+        Err(e) => {
+            log::warn!("failed to request IMDb-API usage: {:?}", e.inner());
+            return;
+        }
+    };
+
+    let above_threshold =
+        usage.percent().unwrap_or_default() >= CONFIG.imdb_usage_warning_threshold;
+    if imdb_usage_warnings.finish_check(api_key, above_threshold) {
+        let warning =
+            transcripts::imdb_api_usage_warning(lang, &usage.to_string(), !api_key.is_empty());
+
+        if let Err(e) = bot.send_message(chat_id, warning).await {
+            log::warn!("failed to send IMDb-API usage warning: {:?}", e);
+        }
+    }
+}
+
 pub async fn receive_item_selection(
     bot: AutoSend<Bot>,
     q: CallbackQuery,
     database: Database,
     client: Client,
     movie_info_api: MovieInfoApi,
+    rate_limiter: ApiRateLimiter,
 ) -> HandlerResult {
-    if let Some(imdb_id) = &q.data {
+    if let Some(data) = &q.data {
         if let Some(msg) = &q.message {
             let add_to_movie_list = BotWork::new(&bot, msg.chat.id);
             add_to_movie_list
@@ -268,23 +1479,149 @@ pub async fn receive_item_selection(
                         return Err(feedback_error(anyhow!(user_tokens.user_hint(lang))));
                     }
 
-                    let movie_info = movie_info_api
-                        .request_movie_information(&client, &user_tokens.imdb_token, imdb_id, lang)
-                        .await?;
+                    let callback_data = CallbackData::parse(data);
+                    let movie_info_api = movie_info_api.prefer(user_tokens.movie_info_provider());
+                    let api_key = user_tokens.provider_token(movie_info_api.provider());
+                    rate_limiter.acquire(user_id, lang)?;
 
-                    insert_movie_info_to_notion_database(
-                        &client,
-                        &user_tokens.notion_token.integration_token,
-                        &user_tokens.notion_token.database_id,
-                        &movie_info,
-                        lang,
-                    )
-                    .await?;
+                    let notion_token = &user_tokens.notion_token;
+                    let mapping = database.property_mapping(user_id, lang).await?;
+                    let (message, page_id) = match &callback_data {
+                        CallbackData::Add(id) => {
+                            let movie_info = movie_info_api
+                                .request_movie_information(&client, api_key, id, lang)
+                                .await?;
+                            let existing_page = find_movie_in_notion_database(
+                                &client,
+                                &notion_token.integration_token,
+                                &notion_token.database_id,
+                                &movie_info.imdb_link,
+                                &mapping,
+                                lang,
+                            )
+                            .await?;
+
+                            if let Some(page) = existing_page {
+                                let add_anyway = InlineKeyboardButton::callback(
+                                    transcripts::add_anyway(lang),
+                                    CallbackData::AddAnyway(id.to_string()).encode(),
+                                );
+                                let update_existing = InlineKeyboardButton::callback(
+                                    transcripts::update_existing(lang),
+                                    CallbackData::UpdateExisting {
+                                        page_id: page.id,
+                                        id: id.to_string(),
+                                    }
+                                    .encode(),
+                                );
+
+                                bot.send_message(
+                                    msg.chat.id,
+                                    transcripts::already_in_movie_list(
+                                        lang,
+                                        &movie_info.title,
+                                        &page.url,
+                                    ),
+                                )
+                                .parse_mode(ParseMode::Html)
+                                .reply_markup(InlineKeyboardMarkup::new([[
+                                    add_anyway,
+                                    update_existing,
+                                ]]))
+                                .await
+                                .map_err(propagate_error)?;
+
+                                return Ok(());
+                            }
+
+                            let page = add_movie_to_notion_database(
+                                &client,
+                                notion_token,
+                                &movie_info,
+                                &mapping,
+                                lang,
+                            )
+                            .await?;
+
+                            let message = transcripts::add_to_movie_list_successfully(
+                                lang,
+                                &movie_info.title,
+                                movie_info.provider.name(),
+                            );
+                            (message, page.id)
+                        }
+                        CallbackData::AddAnyway(id) => {
+                            let movie_info = movie_info_api
+                                .request_movie_information(&client, api_key, id, lang)
+                                .await?;
+                            let page = add_movie_to_notion_database(
+                                &client,
+                                notion_token,
+                                &movie_info,
+                                &mapping,
+                                lang,
+                            )
+                            .await?;
+
+                            let message = transcripts::add_to_movie_list_successfully(
+                                lang,
+                                &movie_info.title,
+                                movie_info.provider.name(),
+                            );
+                            (message, page.id)
+                        }
+                        CallbackData::UpdateExisting { page_id, id } => {
+                            let movie_info = movie_info_api
+                                .request_movie_information(&client, api_key, id, lang)
+                                .await?;
+                            update_movie_info_in_notion_page(
+                                &client,
+                                &notion_token.integration_token,
+                                page_id,
+                                &movie_info,
+                                &mapping,
+                                lang,
+                            )
+                            .await?;
+
+                            let message = transcripts::update_movie_info_successfully(
+                                lang,
+                                &movie_info.title,
+                                movie_info.provider.name(),
+                            );
+                            (message, page_id.to_string())
+                        }
+                        CallbackData::Refresh(page_id) => {
+                            let page = retrieve_notion_page(
+                                &client,
+                                &notion_token.integration_token,
+                                page_id,
+                                lang,
+                            )
+                            .await?;
+                            let movie_info = refresh_movie_page(
+                                &client,
+                                &movie_info_api,
+                                api_key,
+                                &notion_token.integration_token,
+                                &page,
+                                &mapping,
+                                lang,
+                            )
+                            .await?;
+
+                            let message = transcripts::refresh_movie_info_successfully(
+                                lang,
+                                &movie_info.title,
+                                movie_info.provider.name(),
+                            );
+                            (message, page.id)
+                        }
+                    };
 
-                    let message =
-                        transcripts::add_to_movie_list_successfully(lang, &movie_info.title);
                     bot.send_message(msg.chat.id, message)
                         .parse_mode(ParseMode::Html)
+                        .reply_markup(movie_page_keyboard(&page_id, lang))
                         .await
                         .map_err(propagate_error)?;
 
@@ -0,0 +1,118 @@
+use crate::api::{MovieField, PropertyType};
+use crate::error::{feedback_error, BotError};
+use crate::{transcripts, Language};
+
+use anyhow::anyhow;
+
+/// What the user asks for with `/map_property`.
+#[derive(Debug, PartialEq)]
+pub enum MappingCommand {
+    Show,
+    Map {
+        field: MovieField,
+        name: String,
+        property_type: Option<PropertyType>,
+    },
+    Omit(MovieField),
+    Reset(MovieField),
+}
+
+impl MappingCommand {
+    /// Parses `<field> <property name>[, <type>]`, `<field> off` or `<field> reset`.
+    pub fn parse(input: &str, fb_lang: Language) -> Result<Self, BotError> {
+        let input = input.trim();
+
+        if input.is_empty() {
+            return Ok(Self::Show);
+        }
+
+        let (field, property) = input.split_once(char::is_whitespace).unwrap_or((input, ""));
+        let field = field.parse::<MovieField>().map_err(|_| {
+            feedback_error(anyhow!(transcripts::unknown_movie_field(
+                fb_lang,
+                &movie_fields_to_string()
+            )))
+        })?;
+        let property = property.trim();
+
+        match property {
+            "" => Err(feedback_error(anyhow!(
+                transcripts::input_empty_property_name(fb_lang)
+            ))),
+            "off" => Ok(Self::Omit(field)),
+            "reset" => Ok(Self::Reset(field)),
+            _ => {
+                let (name, property_type) = match property.rsplit_once(',') {
+                    Some((name, property_type)) => match property_type.parse() {
+                        Ok(property_type) => (name.trim(), Some(property_type)),
+                        Err(_) => (property, None),
+                    },
+                    None => (property, None),
+                };
+
+                Ok(Self::Map {
+                    field,
+                    name: name.to_string(),
+                    property_type,
+                })
+            }
+        }
+    }
+}
+
+fn movie_fields_to_string() -> String {
+    MovieField::ALL
+        .iter()
+        .map(MovieField::code)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_mapping_command() {
+        let lang = Language::default();
+
+        assert_eq!(
+            MappingCommand::parse(" ", lang).unwrap(),
+            MappingCommand::Show
+        );
+        assert_eq!(
+            MappingCommand::parse("director Regisseur", lang).unwrap(),
+            MappingCommand::Map {
+                field: MovieField::Director,
+                name: "Regisseur".to_string(),
+                property_type: None
+            }
+        );
+        assert_eq!(
+            MappingCommand::parse("genre  Film Genre, select", lang).unwrap(),
+            MappingCommand::Map {
+                field: MovieField::Genre,
+                name: "Film Genre".to_string(),
+                property_type: Some(PropertyType::Select)
+            }
+        );
+        assert_eq!(
+            MappingCommand::parse("plot Story, told briefly", lang).unwrap(),
+            MappingCommand::Map {
+                field: MovieField::Plot,
+                name: "Story, told briefly".to_string(),
+                property_type: None
+            }
+        );
+        assert_eq!(
+            MappingCommand::parse("plot off", lang).unwrap(),
+            MappingCommand::Omit(MovieField::Plot)
+        );
+        assert_eq!(
+            MappingCommand::parse("star reset", lang).unwrap(),
+            MappingCommand::Reset(MovieField::Star)
+        );
+        assert!(MappingCommand::parse("plot", lang).is_err());
+        assert!(MappingCommand::parse("rating Rating", lang).is_err());
+    }
+}
@@ -1,41 +1,99 @@
+mod callback_data;
+mod dialogue_storage;
 mod handler;
+mod mapping_command;
+mod notion_oauth;
+mod refresh_job;
+mod setup_wizard;
 
-use crate::api::{ImdbApi, RequestMovieInfo};
+use crate::api::{
+    clean_up_movie_info_cache, CacheBackend, CachedApi, ImdbApi, ImdbDatasets, MemoryCacheStore,
+    MovieInfoCache, NotionOAuth, OmdbApi, PgCacheStore, Provider, ProviderChain, RequestMovieInfo,
+    TmdbApi, NOTION_OAUTH_REDIRECT_PATH,
+};
+use crate::bot::callback_data::{DiaryAction, SetupAction, TemplateAction};
+use crate::bot::dialogue_storage::PgDialogueStorage;
 use crate::bot::handler::*;
+use crate::bot::notion_oauth::notion_oauth_router;
+use crate::bot::refresh_job::RefreshJob;
+use crate::bot::setup_wizard::{
+    receive_setup_action, receive_setup_imdb_key, receive_setup_notion_page,
+    receive_setup_notion_token, start, SetupState,
+};
 use crate::config::CONFIG;
 use crate::db::{BotDatabase, PgBotDatabase};
 use crate::error::{feedback_error, BotError};
+use crate::rate_limit::{Limit, RateLimiter, SharedImdbKeyLimitedApi};
+use crate::secret::TokenCipher;
+use crate::usage_warning::UsageWarnings;
 use crate::{transcripts, Language};
 
 use anyhow::anyhow;
 use reqwest::{Client, Url};
 use std::future::Future;
 use std::sync::Arc;
-use teloxide::dispatching::update_listeners::webhooks;
+use std::time::Duration;
+use teloxide::dispatching::stop_token::StopToken;
+use teloxide::dispatching::update_listeners::{webhooks, UpdateListener};
 use teloxide::types::User;
 use teloxide::{dispatching::UpdateHandler, prelude::*, utils::command::BotCommands};
 
 type HandlerResult = Result<(), anyhow::Error>;
 type Database = Arc<dyn BotDatabase + Send + Sync>;
-type MovieInfoApi = Arc<dyn RequestMovieInfo + Send + Sync>;
+type MovieInfoApi = Arc<ProviderChain>;
+type ProviderApi = Arc<dyn RequestMovieInfo + Send + Sync>;
+type ImdbUsageApi = Arc<ImdbApi>;
+type ApiRateLimiter = Arc<RateLimiter>;
+type ImdbUsageWarnings = Arc<UsageWarnings>;
+type NotionOAuthApi = Arc<NotionOAuth>;
+
+const MEMORY_CACHE_CAPACITY: usize = 1000;
+const CACHE_CLEAN_UP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+const IMDB_USAGE_CHECK_INTERVAL: Duration = Duration::from_secs(15 * 60);
 
 #[derive(BotCommands, Clone)]
 #[command(rename = "snake_case", description = "These commands are supported:")]
 enum Command {
     #[command(description = "display this text.")]
     Help,
-    #[command(description = "start to do some work with me.")]
+    #[command(description = "set me up step by step.")]
     Start,
     #[command(description = "see your tokens information.")]
     Settings,
     #[command(description = "set the IMDb api token for getting movie information.")]
     SetImdbToken(String),
-    #[command(description = "set the Notion internal integration token.")]
+    #[command(
+        description = "connect your Notion workspace, or set the token of an internal integration."
+    )]
     SetNotionToken(String),
     #[command(
-        description = "create a database for your movie list. Give me your page ID or its link"
+        description = "create a database for your movie list. Give me your page ID or its link, optionally followed by its title"
     )]
     CreateNotionDb(String),
+    #[command(
+        description = "use one of your databases as the movie list. Give me its ID or its link"
+    )]
+    UseNotionDb(String),
+    #[command(description = "check your movie list and add the properties it's missing.")]
+    CheckNotionDb,
+    #[command(description = "choose which property of your movie list a field goes to.")]
+    MapProperty(String),
+    #[command(
+        description = "choose where I get movie information from, optionally with your own API key."
+    )]
+    Provider(String),
+    #[command(description = "check how much of the IMDb-API quota is used today.")]
+    Usage,
+    #[command(
+        description = "update the ratings and information of the titles in your movie list."
+    )]
+    Refresh,
+    #[command(
+        description = "mark a title in your movie list as watched. Give me its IMDb link or ID"
+    )]
+    Watched(String),
+    #[command(description = "rate a title in your movie list, e.g. /rate tt0133093 8")]
+    Rate(String),
 }
 
 pub async fn start_bot() {
@@ -44,27 +102,181 @@ pub async fn start_bot() {
     let addr = ([0, 0, 0, 0], CONFIG.port).into();
     let url = Url::parse(&format!("https://{}/webhooks/{bot_token}", &CONFIG.host)).unwrap();
 
-    let pool = Arc::new(
-        PgBotDatabase::connect(&CONFIG.database_url)
-            .await
-            .expect("failed to connect to database"),
-    ) as Database;
+    let token_cipher =
+        TokenCipher::new(&CONFIG.token_encryption_keys).expect("failed to set up token encryption");
+    let current_key_id = token_cipher.current_key_id().to_string();
+    let pg_database = PgBotDatabase::connect(&CONFIG.database_url, token_cipher)
+        .await
+        .expect("failed to connect to database");
+    // tokens from before the encryption, or encrypted with a key that has been rotated out
+    let encrypted_users = pg_database
+        .encrypt_user_tokens()
+        .await
+        .expect("failed to encrypt user tokens");
+    if encrypted_users > 0 {
+        log::info!(
+            "Encrypted the tokens of {} users with the token key `{}`",
+            encrypted_users,
+            current_key_id
+        );
+    }
+    let movie_info_cache = movie_info_cache(CONFIG.movie_info_cache, &pg_database);
+    if let Some(cache) = &movie_info_cache {
+        tokio::spawn(clean_up_movie_info_cache(
+            cache.clone(),
+            CACHE_CLEAN_UP_INTERVAL,
+        ));
+    }
+    let rate_limiter = Arc::new(RateLimiter::new(
+        Limit::new(CONFIG.user_rate_limit, Duration::from_secs(60)),
+        Limit::new(
+            CONFIG.shared_imdb_key_rate_limit,
+            Duration::from_secs(60 * 60),
+        ),
+    ));
+    let cached = |provider: Provider, api: ProviderApi| {
+        // the datasets are already in our database
+        match &movie_info_cache {
+            Some(cache) if provider != Provider::ImdbDatasets => Arc::new(CachedApi::new(
+                api,
+                cache.clone(),
+                Duration::from_secs(CONFIG.movie_info_cache_ttl),
+            )) as ProviderApi,
+            _ => api,
+        }
+    };
+    // the background refresh leaves the shared IMDb-API key to the searches of the users
+    let refresh_movie_info_api = Arc::new(ProviderChain::new(
+        CONFIG
+            .movie_info_providers
+            .iter()
+            .map(|provider| {
+                let api = match provider {
+                    Provider::ImdbApi => {
+                        Arc::new(ImdbApi::new(String::new()).with_url(&CONFIG.imdb_api_url))
+                            as ProviderApi
+                    }
+                    _ => movie_info_api(*provider, &pg_database, &rate_limiter),
+                };
+
+                cached(*provider, api)
+            })
+            .collect(),
+    ));
+    let movie_info_api = Arc::new(ProviderChain::new(
+        CONFIG
+            .movie_info_providers
+            .iter()
+            .map(|provider| {
+                cached(
+                    *provider,
+                    movie_info_api(*provider, &pg_database, &rate_limiter),
+                )
+            })
+            .collect(),
+    ));
+    let imdb_usage_api = Arc::new(
+        ImdbApi::new(CONFIG.default_imdb_api_key.to_string()).with_url(&CONFIG.imdb_api_url),
+    );
+    let imdb_usage_warnings = Arc::new(UsageWarnings::new(IMDB_USAGE_CHECK_INTERVAL));
+    let notion_oauth = Arc::new(
+        NotionOAuth::new(
+            CONFIG.notion_client_id.to_string(),
+            CONFIG.notion_client_secret.to_string(),
+            format!("https://{}{}", &CONFIG.host, NOTION_OAUTH_REDIRECT_PATH),
+        )
+        .with_url(&CONFIG.notion_oauth_url),
+    );
+    // a restart doesn't interrupt the setup
+    let dialogue_storage = Arc::new(PgDialogueStorage::new(pg_database.pg_pool().clone()));
+    let pool = Arc::new(pg_database) as Database;
     let client = Client::new();
-    let movie_info_api =
-        Arc::new(ImdbApi::new(CONFIG.default_imdb_api_key.to_string())) as MovieInfoApi;
 
-    let listener = webhooks::axum(bot.clone(), webhooks::Options::new(addr, url))
-        .await
-        .expect("Couldn't setup webhook");
+    // an interval of 0 turns the background refresh off
+    if CONFIG.refresh_interval > 0 {
+        let refresh_job = RefreshJob::new(
+            pool.clone(),
+            client.clone(),
+            refresh_movie_info_api,
+            CONFIG.refresh_rate_limit,
+            Duration::from_secs(CONFIG.refresh_interval),
+        );
+        tokio::spawn(refresh_job.run());
+    }
+
+    let (mut listener, stop_flag, webhook_router) =
+        webhooks::axum_to_router(bot.clone(), webhooks::Options::new(addr, url))
+            .await
+            .expect("Couldn't setup webhook");
+    let stop_token = listener.stop_token();
+    // Notion redirects to the same server after the user connected their workspace
+    let app = webhook_router.merge(notion_oauth_router(
+        bot.clone(),
+        pool.clone(),
+        client.clone(),
+        notion_oauth.clone(),
+        dialogue_storage.clone(),
+    ));
+
+    tokio::spawn(async move {
+        axum::Server::bind(&addr)
+            .serve(app.into_make_service())
+            .with_graceful_shutdown(stop_flag)
+            .await
+            .inspect_err(|_| stop_token.stop())
+            .expect("Axum server error");
+    });
 
     Dispatcher::builder(bot, schema())
-        .dependencies(dptree::deps![pool, client, movie_info_api])
+        .dependencies(dptree::deps![
+            pool,
+            client,
+            movie_info_api,
+            imdb_usage_api,
+            imdb_usage_warnings,
+            rate_limiter,
+            notion_oauth,
+            dialogue_storage
+        ])
         .enable_ctrlc_handler()
         .build()
         .dispatch_with_listener(listener, LoggingErrorHandler::new())
         .await;
 }
 
+fn movie_info_api(
+    provider: Provider,
+    pg_database: &PgBotDatabase,
+    rate_limiter: &ApiRateLimiter,
+) -> ProviderApi {
+    match provider {
+        Provider::ImdbApi => Arc::new(SharedImdbKeyLimitedApi::new(
+            Arc::new(
+                ImdbApi::new(CONFIG.default_imdb_api_key.to_string())
+                    .with_url(&CONFIG.imdb_api_url),
+            ),
+            rate_limiter.clone(),
+        )) as ProviderApi,
+        Provider::Tmdb => Arc::new(
+            TmdbApi::new(CONFIG.default_tmdb_api_key.to_string()).with_url(&CONFIG.tmdb_api_url),
+        ) as ProviderApi,
+        Provider::Omdb => Arc::new(
+            OmdbApi::new(CONFIG.default_omdb_api_key.to_string()).with_url(&CONFIG.omdb_api_url),
+        ) as ProviderApi,
+        Provider::ImdbDatasets => {
+            Arc::new(ImdbDatasets::new(pg_database.pg_pool().clone())) as ProviderApi
+        }
+    }
+}
+
+fn movie_info_cache(backend: CacheBackend, pg_database: &PgBotDatabase) -> Option<MovieInfoCache> {
+    match backend {
+        CacheBackend::Postgres => Some(Arc::new(PgCacheStore::new(pg_database.pg_pool().clone()))),
+        CacheBackend::Memory => Some(Arc::new(MemoryCacheStore::new(MEMORY_CACHE_CAPACITY))),
+        CacheBackend::Off => None,
+    }
+}
+
 fn schema() -> UpdateHandler<anyhow::Error> {
     use dptree::case;
 
@@ -74,14 +286,41 @@ fn schema() -> UpdateHandler<anyhow::Error> {
         .branch(case![Command::Settings].endpoint(settings))
         .branch(case![Command::SetImdbToken(imdb_token)].endpoint(set_imdb_token))
         .branch(case![Command::SetNotionToken(notion_token)].endpoint(set_notion_token))
-        .branch(case![Command::CreateNotionDb(page_link)].endpoint(handle_notion_page_link_or_id));
+        .branch(case![Command::CreateNotionDb(page_link)].endpoint(handle_notion_page_link_or_id))
+        .branch(case![Command::UseNotionDb(database_link)].endpoint(use_notion_database))
+        .branch(case![Command::CheckNotionDb].endpoint(check_notion_database))
+        .branch(case![Command::MapProperty(input)].endpoint(map_property))
+        .branch(case![Command::Provider(provider)].endpoint(set_movie_info_provider))
+        .branch(case![Command::Usage].endpoint(imdb_api_usage))
+        .branch(case![Command::Refresh].endpoint(refresh_movie_list))
+        .branch(case![Command::Watched(input)].endpoint(mark_as_watched))
+        .branch(case![Command::Rate(input)].endpoint(rate_movie));
 
+    // commands work in the middle of the setup too, `/start` begins it again
     let message_handler = Update::filter_message()
+        .enter_dialogue::<Message, PgDialogueStorage, SetupState>()
         .branch(command_handler)
+        .branch(case![SetupState::NotionToken].endpoint(receive_setup_notion_token))
+        .branch(case![SetupState::NotionPage].endpoint(receive_setup_notion_page))
+        .branch(case![SetupState::ImdbKey].endpoint(receive_setup_imdb_key))
         .branch(dptree::endpoint(receive_keyword));
 
-    let callback_query_handler =
-        Update::filter_callback_query().branch(dptree::endpoint(receive_item_selection));
+    let callback_query_handler = Update::filter_callback_query()
+        .branch(
+            dptree::filter_map(|q: CallbackQuery| q.data.as_deref().and_then(SetupAction::parse))
+                .endpoint(receive_setup_action),
+        )
+        .branch(
+            dptree::filter_map(|q: CallbackQuery| {
+                q.data.as_deref().and_then(TemplateAction::parse)
+            })
+            .endpoint(receive_database_template_action),
+        )
+        .branch(
+            dptree::filter_map(|q: CallbackQuery| q.data.as_deref().and_then(DiaryAction::parse))
+                .endpoint(receive_diary_action),
+        )
+        .branch(dptree::endpoint(receive_item_selection));
 
     dptree::entry()
         .branch(message_handler)
@@ -0,0 +1,174 @@
+use crate::api::NOTION_OAUTH_REDIRECT_PATH;
+use crate::bot::dialogue_storage::PgDialogueStorage;
+use crate::bot::setup_wizard::{ask_for_notion_page, setup_state, SetupDialogue, SetupState};
+use crate::bot::{BotWork, Database, NotionOAuthApi};
+use crate::error::{feedback_error, propagate_error};
+use crate::{transcripts, Language};
+
+use anyhow::anyhow;
+use axum::extract::{Extension, Query};
+use axum::response::Html;
+use axum::routing::get;
+use axum::Router;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use reqwest::Client;
+use serde::Deserialize;
+use teloxide::prelude::*;
+
+use std::sync::Arc;
+
+const STATE_LENGTH: usize = 32;
+
+#[derive(Clone)]
+struct OAuthContext {
+    bot: AutoSend<Bot>,
+    database: Database,
+    client: Client,
+    notion_oauth: NotionOAuthApi,
+    dialogue_storage: Arc<PgDialogueStorage>,
+}
+
+/// The query of the redirect from the authorization page, `error` is there if the user said no.
+#[derive(Debug, Deserialize)]
+struct AuthorizationQuery {
+    state: String,
+    code: Option<String>,
+    error: Option<String>,
+}
+
+/// A value nobody can guess, which ties the redirect to the user who asked for the link.
+pub fn new_state() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(STATE_LENGTH)
+        .map(char::from)
+        .collect()
+}
+
+/// Receives the redirect from the authorization page, next to the webhook.
+pub fn notion_oauth_router(
+    bot: AutoSend<Bot>,
+    database: Database,
+    client: Client,
+    notion_oauth: NotionOAuthApi,
+    dialogue_storage: Arc<PgDialogueStorage>,
+) -> Router {
+    let context = OAuthContext {
+        bot,
+        database,
+        client,
+        notion_oauth,
+        dialogue_storage,
+    };
+
+    Router::new()
+        .route(
+            NOTION_OAUTH_REDIRECT_PATH,
+            get(receive_notion_authorization),
+        )
+        .layer(Extension(context))
+}
+
+async fn receive_notion_authorization(
+    Query(query): Query<AuthorizationQuery>,
+    Extension(context): Extension<OAuthContext>,
+) -> Html<String> {
+    let lang = Language::default();
+
+    let request = match context
+        .database
+        .take_notion_oauth_state(&query.state, lang)
+        .await
+    {
+        Ok(Some(request)) => request,
+        Ok(None) => return authorization_page(transcripts::notion_authorization_expired(lang)),
+        Err(e) => {
+            log::error!("failed to look up Notion authorization: {:?}", e.inner());
+            return authorization_page(transcripts::database_error(lang));
+        }
+    };
+
+    let chat_id = ChatId(request.chat_id);
+    let result = BotWork::new(&context.bot, chat_id)
+        .do_it(async {
+            let code = query
+                .code
+                .filter(|_| query.error.is_none())
+                .ok_or_else(|| {
+                    feedback_error(anyhow!(transcripts::notion_authorization_denied(lang)))
+                })?;
+
+            let access_token = context
+                .notion_oauth
+                .exchange_code(&context.client, &code, lang)
+                .await?;
+
+            let success = context
+                .database
+                .store_notion_token(request.user_id, &access_token.access_token, lang)
+                .await?;
+
+            if !success {
+                return Err(feedback_error(anyhow!(transcripts::configure_again(lang))));
+            }
+
+            let mut reply_text =
+                transcripts::notion_connected(lang, access_token.workspace_name.as_deref());
+
+            // the authorization page was the first step of the setup
+            let dialogue = SetupDialogue::new(context.dialogue_storage.clone(), chat_id);
+            if setup_state(&dialogue, lang).await? == SetupState::NotionToken {
+                return ask_for_notion_page(&context.bot, chat_id, &dialogue, reply_text, lang)
+                    .await;
+            }
+
+            let user_tokens = context.database.user_tokens(request.user_id, lang).await?;
+            if !user_tokens.notion_token_is_good() {
+                reply_text += "\n\n";
+                reply_text += &user_tokens.user_hint(lang);
+            }
+
+            context
+                .bot
+                .send_message(chat_id, reply_text)
+                .await
+                .map_err(propagate_error)?;
+
+            Ok(())
+        })
+        .await;
+
+    // the user finds out the details in the chat
+    match result {
+        Ok(()) => authorization_page(transcripts::notion_authorization_finished(lang)),
+        Err(e) => {
+            log::error!("failed to connect Notion workspace: {:?}", e);
+            authorization_page(transcripts::notion_authorization_failed(lang))
+        }
+    }
+}
+
+fn authorization_page(text: &str) -> Html<String> {
+    Html(format!(
+        "<!DOCTYPE html>\n\
+        <html>\n\
+        <head><meta charset=\"utf-8\"><meta name=\"viewport\" content=\"width=device-width\"><title>NMLBot</title></head>\n\
+        <body><p>{}</p></body>\n\
+        </html>",
+        text
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new_state() {
+        let state = new_state();
+        assert_eq!(state.len(), STATE_LENGTH);
+        assert!(state.chars().all(|c| c.is_ascii_alphanumeric()));
+        assert_ne!(state, new_state());
+    }
+}
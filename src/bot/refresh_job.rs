@@ -0,0 +1,208 @@
+use crate::api::{
+    movie_id_from_link, query_movie_pages_in_notion_database, refresh_movie_info_in_notion_page,
+    QuotaExceeded, RequestMovieInfo,
+};
+use crate::bot::{Database, MovieInfoApi};
+use crate::error::BotError;
+use crate::Language;
+
+use reqwest::Client;
+use tokio::time::{Interval, MissedTickBehavior};
+
+use std::time::Duration;
+
+const RETRY_DELAY: Duration = Duration::from_secs(60 * 60);
+
+/// Keeps the ratings and release dates in every user's movie list up to date.
+///
+/// The job has a budget of its own instead of counting against the users' rate limits. Its
+/// `movie_info_api` shouldn't call IMDb-API with the shared default key, searches depend on it.
+pub struct RefreshJob {
+    database: Database,
+    client: Client,
+    movie_info_api: MovieInfoApi,
+    /// Paces the requests to the providers, if they are limited.
+    throttle: Option<Interval>,
+    interval: Duration,
+}
+
+impl RefreshJob {
+    pub fn new(
+        database: Database,
+        client: Client,
+        movie_info_api: MovieInfoApi,
+        requests_per_minute: usize,
+        interval: Duration,
+    ) -> Self {
+        let throttle = (requests_per_minute > 0).then(|| {
+            let mut throttle =
+                tokio::time::interval(Duration::from_secs(60) / requests_per_minute as u32);
+            // a slow Notion doesn't earn the job a burst of requests afterwards
+            throttle.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            throttle
+        });
+
+        Self {
+            database,
+            client,
+            movie_info_api,
+            throttle,
+            interval,
+        }
+    }
+
+    pub async fn run(mut self) {
+        let mut wait = self.time_until_next_refresh().await.unwrap_or_else(|e| {
+            log::warn!("failed to read refresh progress: {:?}", e.inner());
+            self.interval
+        });
+
+        loop {
+            tokio::time::sleep(wait).await;
+
+            wait = match self.refresh_all().await {
+                Ok(()) => self.interval,
+                Err(e) => {
+                    log::warn!("failed to refresh movie lists: {:?}", e.inner());
+                    self.interval.min(RETRY_DELAY)
+                }
+            };
+        }
+    }
+
+    async fn time_until_next_refresh(&self) -> Result<Duration, BotError> {
+        let progress = self.database.refresh_progress(Language::default()).await?;
+
+        // an unfinished refresh is resumed right away
+        if progress.user_id.is_some() {
+            return Ok(Duration::ZERO);
+        }
+
+        Ok(progress
+            .secs_since_finished
+            .map(|secs| self.interval.saturating_sub(Duration::from_secs(secs)))
+            .unwrap_or(Duration::ZERO))
+    }
+
+    async fn refresh_all(&mut self) -> Result<(), BotError> {
+        let lang = Language::default();
+        let progress = self.database.refresh_progress(lang).await?;
+        let from_user_id = progress.user_id.unwrap_or_default();
+        let mut start_cursor = progress.start_cursor;
+
+        for user_id in self
+            .database
+            .users_with_notion_database(from_user_id, lang)
+            .await?
+        {
+            if user_id != from_user_id {
+                start_cursor = None;
+            }
+
+            self.database
+                .store_refresh_progress(user_id, start_cursor.as_deref(), lang)
+                .await?;
+
+            match self.refresh_user(user_id, start_cursor.take()).await {
+                Ok(()) => {}
+                // a default key is shared by everyone, keep the progress and let the next run pick
+                // it up once the quota is reset
+                Err(e) if is_quota_exceeded(&e) => return Err(e),
+                Err(e) => log::warn!(
+                    "failed to refresh movie list of {}: {:?}",
+                    user_id,
+                    e.inner()
+                ),
+            }
+        }
+
+        self.database.finish_refresh_progress(lang).await
+    }
+
+    async fn refresh_user(
+        &mut self,
+        user_id: u64,
+        start_cursor: Option<String>,
+    ) -> Result<(), BotError> {
+        let lang = Language::default();
+        let user_tokens = self.database.user_tokens(user_id, lang).await?;
+        let movie_info_api = self
+            .movie_info_api
+            .prefer(user_tokens.movie_info_provider());
+        let api_key = user_tokens.provider_token(movie_info_api.provider());
+        let notion_token = &user_tokens.notion_token;
+        let mapping = self.database.property_mapping(user_id, lang).await?;
+
+        let mut start_cursor = start_cursor;
+
+        loop {
+            let query_result = query_movie_pages_in_notion_database(
+                &self.client,
+                &notion_token.integration_token,
+                &notion_token.database_id,
+                start_cursor.as_deref(),
+                &mapping,
+                lang,
+            )
+            .await?;
+
+            for page in &query_result.results {
+                let id = match page.imdb_link(&mapping).and_then(movie_id_from_link) {
+                    Some(id) => id,
+                    None => continue,
+                };
+
+                if let Some(throttle) = &mut self.throttle {
+                    throttle.tick().await;
+                }
+
+                let movie_info = match movie_info_api
+                    .refresh_movie_information(&self.client, api_key, &id, lang)
+                    .await
+                {
+                    Ok(movie_info) => movie_info,
+                    // only this user's own key is used up, the others go on
+                    Err(e) if is_quota_exceeded(&e) && !api_key.is_empty() => {
+                        log::warn!(
+                            "the API key of {} ran out of quota, skipped: {:?}",
+                            user_id,
+                            e.inner()
+                        );
+                        return Ok(());
+                    }
+                    Err(e) if is_quota_exceeded(&e) => return Err(e),
+                    Err(e) => {
+                        log::warn!("failed to refresh {}: {:?}", page.url, e.inner());
+                        continue;
+                    }
+                };
+
+                if page.is_outdated(&movie_info, &mapping) {
+                    refresh_movie_info_in_notion_page(
+                        &self.client,
+                        &notion_token.integration_token,
+                        &page.id,
+                        &movie_info,
+                        &mapping,
+                        lang,
+                    )
+                    .await?;
+                }
+            }
+
+            match query_result.next_cursor {
+                Some(next_cursor) => {
+                    self.database
+                        .store_refresh_progress(user_id, Some(&next_cursor), lang)
+                        .await?;
+                    start_cursor = Some(next_cursor);
+                }
+                None => return Ok(()),
+            }
+        }
+    }
+}
+
+fn is_quota_exceeded(error: &BotError) -> bool {
+    error.inner().chain().any(|e| e.is::<QuotaExceeded>())
+}
@@ -0,0 +1,457 @@
+use crate::api::*;
+use crate::bot::callback_data::SetupAction;
+use crate::bot::dialogue_storage::PgDialogueStorage;
+use crate::bot::handler::{
+    create_movie_list, delete_secret_message, notion_authorization_url, secret_confirmation,
+};
+use crate::bot::{
+    get_user_from_msg, ApiRateLimiter, BotWork, Database, HandlerResult, ImdbUsageApi,
+    NotionOAuthApi,
+};
+use crate::config::CONFIG;
+use crate::db::UserTokens;
+use crate::error::{feedback_error, feedback_propagate_error, propagate_error, BotError};
+use crate::secret::mask_secret;
+use crate::{transcripts, Language};
+
+use anyhow::anyhow;
+use reqwest::{Client, Url};
+use serde::{Deserialize, Serialize};
+use teloxide::dispatching::dialogue::Dialogue;
+use teloxide::prelude::*;
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup};
+
+use std::sync::Arc;
+
+pub type SetupDialogue = Dialogue<SetupState, PgDialogueStorage>;
+
+/// Where the user is in the setup after `/start`, it's kept in the database between messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum SetupState {
+    /// Not setting anything up, messages are searches.
+    #[default]
+    Idle,
+    NotionToken,
+    NotionPage,
+    /// The last step, it can be skipped.
+    ImdbKey,
+}
+
+impl SetupState {
+    /// The first step the user hasn't done yet, `Idle` if there is nothing left to do.
+    pub fn first_missing_step(user_tokens: &UserTokens) -> Self {
+        if user_tokens.notion_token.integration_token.is_empty() {
+            Self::NotionToken
+        } else if user_tokens.notion_token.database_id.is_empty() {
+            Self::NotionPage
+        } else {
+            Self::Idle
+        }
+    }
+}
+
+pub async fn start(
+    bot: AutoSend<Bot>,
+    msg: Message,
+    database: Database,
+    notion_oauth: NotionOAuthApi,
+    dialogue: SetupDialogue,
+) -> HandlerResult {
+    BotWork::new(&bot, msg.chat.id)
+        .do_it(async {
+            let user = get_user_from_msg(&msg)?;
+            let user_id = user.id.0;
+
+            let lang = Language::default();
+            // check if user's config is already in database
+            let user_tokens = match database.get_user_tokens(user_id, lang).await? {
+                Some(user_tokens) => user_tokens,
+                None => {
+                    database.init_user_tokens(user_id).await?;
+                    database.user_tokens(user_id, lang).await?
+                }
+            };
+
+            let state = SetupState::first_missing_step(&user_tokens);
+            if state == SetupState::Idle {
+                exit_setup(&dialogue, lang).await?;
+                bot.send_message(msg.chat.id, transcripts::welcome_back(lang))
+                    .await
+                    .map_err(propagate_error)?;
+
+                return Ok(());
+            }
+
+            update_setup(&dialogue, state, lang).await?;
+
+            let (prompt, keyboard) = match state {
+                SetupState::NotionToken => {
+                    // without a public integration the user can only send a token
+                    let url = if notion_oauth.is_enabled() {
+                        Some(
+                            notion_authorization_url(
+                                user_id,
+                                msg.chat.id,
+                                &database,
+                                &notion_oauth,
+                                lang,
+                            )
+                            .await?,
+                        )
+                    } else {
+                        None
+                    };
+
+                    (
+                        transcripts::setup_notion_token(lang, url.is_some()),
+                        notion_token_keyboard(url, lang),
+                    )
+                }
+                _ => (
+                    transcripts::setup_notion_page(lang),
+                    setup_keyboard(false, lang),
+                ),
+            };
+
+            bot.send_message(
+                msg.chat.id,
+                format!("{}\n\n{}", transcripts::transcript_welcome(lang), prompt),
+            )
+            .reply_markup(keyboard)
+            .await
+            .map_err(propagate_error)?;
+
+            Ok(())
+        })
+        .await
+}
+
+pub async fn receive_setup_notion_token(
+    bot: AutoSend<Bot>,
+    msg: Message,
+    database: Database,
+    client: Client,
+    dialogue: SetupDialogue,
+) -> HandlerResult {
+    BotWork::new(&bot, msg.chat.id)
+        .do_it(async {
+            let user = get_user_from_msg(&msg)?;
+            let user_id = user.id.0;
+
+            let lang = Language::default();
+            let token = setup_input(&msg, lang)?;
+
+            let secret_deleted = delete_secret_message(&bot, &msg).await;
+            validate_notion_token(&client, &CONFIG.notion_api_url, token, lang).await?;
+            let success = database.store_notion_token(user_id, token, lang).await?;
+
+            if !success {
+                return Err(feedback_error(anyhow!(transcripts::configure_again(lang))));
+            }
+
+            let confirmation = secret_confirmation(
+                transcripts::notion_token_set_as(lang, &mask_secret(token)),
+                secret_deleted,
+                lang,
+            );
+            ask_for_notion_page(&bot, msg.chat.id, &dialogue, confirmation, lang).await
+        })
+        .await
+}
+
+/// Moves on to the movie list once the user's Notion token is there, it may come from the authorization page.
+pub async fn ask_for_notion_page(
+    bot: &AutoSend<Bot>,
+    chat_id: ChatId,
+    dialogue: &SetupDialogue,
+    confirmation: String,
+    lang: Language,
+) -> Result<(), BotError> {
+    update_setup(dialogue, SetupState::NotionPage, lang).await?;
+
+    bot.send_message(
+        chat_id,
+        format!(
+            "{}\n\n{}",
+            confirmation,
+            transcripts::setup_notion_page(lang)
+        ),
+    )
+    .reply_markup(setup_keyboard(false, lang))
+    .await
+    .map_err(propagate_error)?;
+
+    Ok(())
+}
+
+pub async fn receive_setup_notion_page(
+    bot: AutoSend<Bot>,
+    msg: Message,
+    database: Database,
+    client: Client,
+    rate_limiter: ApiRateLimiter,
+    dialogue: SetupDialogue,
+) -> HandlerResult {
+    BotWork::new(&bot, msg.chat.id)
+        .do_it(async {
+            let user = get_user_from_msg(&msg)?;
+            let user_id = user.id.0;
+
+            let lang = Language::default();
+            let input = setup_input(&msg, lang)?;
+
+            // the page link has no spaces, everything after it is the title
+            let (page_id, title) = match input.split_once(char::is_whitespace) {
+                Some((page_id, title)) => (page_id, Some(title)),
+                None => (input, None),
+            };
+            let page_id = parse_notion_page_id_from_user_input(page_id, lang)?;
+            let mapping = database.property_mapping(user_id, lang).await?;
+            let template = DatabaseTemplate::new(&page_id, title, &mapping);
+
+            create_movie_list(user_id, &template, &database, &client, &rate_limiter, lang).await?;
+
+            update_setup(&dialogue, SetupState::ImdbKey, lang).await?;
+
+            bot.send_message(
+                msg.chat.id,
+                format!(
+                    "{}\n\n{}",
+                    transcripts::notion_database_created(lang),
+                    transcripts::setup_imdb_key(lang)
+                ),
+            )
+            .reply_markup(setup_keyboard(true, lang))
+            .await
+            .map_err(propagate_error)?;
+
+            Ok(())
+        })
+        .await
+}
+
+pub async fn receive_setup_imdb_key(
+    bot: AutoSend<Bot>,
+    msg: Message,
+    database: Database,
+    client: Client,
+    imdb_usage_api: ImdbUsageApi,
+    dialogue: SetupDialogue,
+) -> HandlerResult {
+    BotWork::new(&bot, msg.chat.id)
+        .do_it(async {
+            let user = get_user_from_msg(&msg)?;
+            let user_id = user.id.0;
+
+            let lang = Language::default();
+            let token = setup_input(&msg, lang)?;
+
+            let secret_deleted = delete_secret_message(&bot, &msg).await;
+            imdb_usage_api
+                .validate_api_key(&client, token, lang)
+                .await?;
+            let success = database.store_imdb_token(user_id, token, lang).await?;
+
+            if !success {
+                return Err(feedback_error(anyhow!(transcripts::configure_again(lang))));
+            }
+
+            exit_setup(&dialogue, lang).await?;
+
+            let confirmation = secret_confirmation(
+                format!(
+                    "{}\n\n{}",
+                    transcripts::imdb_token_set_as(lang, &mask_secret(token)),
+                    transcripts::setup_finished(lang)
+                ),
+                secret_deleted,
+                lang,
+            );
+            bot.send_message(msg.chat.id, confirmation)
+                .await
+                .map_err(propagate_error)?;
+
+            Ok(())
+        })
+        .await
+}
+
+pub async fn receive_setup_action(
+    bot: AutoSend<Bot>,
+    q: CallbackQuery,
+    action: SetupAction,
+    dialogue_storage: Arc<PgDialogueStorage>,
+) -> HandlerResult {
+    // stops the spinner on the button, the message itself is the answer
+    bot.answer_callback_query(q.id.clone()).await?;
+
+    if let Some(msg) = &q.message {
+        BotWork::new(&bot, msg.chat.id)
+            .do_it(async {
+                let lang = Language::default();
+                let dialogue = SetupDialogue::new(dialogue_storage, msg.chat.id);
+                let state = setup_state(&dialogue, lang).await?;
+
+                let reply_text = match action_reply(state, action, lang) {
+                    Some(reply_text) => reply_text,
+                    None => return Ok(()),
+                };
+
+                exit_setup(&dialogue, lang).await?;
+                bot.edit_message_reply_markup(msg.chat.id, msg.id)
+                    .await
+                    .map_err(propagate_error)?;
+                bot.send_message(msg.chat.id, reply_text)
+                    .await
+                    .map_err(propagate_error)?;
+
+                Ok(())
+            })
+            .await?
+    }
+
+    Ok(())
+}
+
+/// What the setup answers to `action` before it ends, `None` if the button belongs to an earlier
+/// step and is ignored.
+fn action_reply(state: SetupState, action: SetupAction, lang: Language) -> Option<&'static str> {
+    match action {
+        SetupAction::Skip if state != SetupState::ImdbKey => None,
+        SetupAction::Skip => Some(transcripts::setup_finished(lang)),
+        SetupAction::Cancel => Some(transcripts::setup_cancelled(lang)),
+    }
+}
+
+/// The text the user sent for the current step.
+fn setup_input(msg: &Message, lang: Language) -> Result<&str, BotError> {
+    msg.text()
+        .map(str::trim)
+        .filter(|text| !text.is_empty())
+        .ok_or_else(|| feedback_error(anyhow!(transcripts::setup_needs_text(lang))))
+}
+
+pub async fn setup_state(dialogue: &SetupDialogue, lang: Language) -> Result<SetupState, BotError> {
+    dialogue
+        .get_or_default()
+        .await
+        .map_err(|e| feedback_propagate_error(e.context(transcripts::database_error(lang))))
+}
+
+async fn update_setup(
+    dialogue: &SetupDialogue,
+    state: SetupState,
+    lang: Language,
+) -> Result<(), BotError> {
+    dialogue
+        .update(state)
+        .await
+        .map_err(|e| feedback_propagate_error(e.context(transcripts::database_error(lang))))
+}
+
+async fn exit_setup(dialogue: &SetupDialogue, lang: Language) -> Result<(), BotError> {
+    dialogue
+        .exit()
+        .await
+        .map_err(|e| feedback_propagate_error(e.context(transcripts::database_error(lang))))
+}
+
+fn notion_token_keyboard(url: Option<Url>, lang: Language) -> InlineKeyboardMarkup {
+    let mut keyboard = setup_keyboard(false, lang);
+
+    if let Some(url) = url {
+        keyboard.inline_keyboard.insert(
+            0,
+            vec![InlineKeyboardButton::url(
+                transcripts::connect_notion(lang).to_string(),
+                url,
+            )],
+        );
+    }
+
+    keyboard
+}
+
+fn setup_keyboard(can_skip: bool, lang: Language) -> InlineKeyboardMarkup {
+    let mut buttons = Vec::new();
+
+    if can_skip {
+        buttons.push(InlineKeyboardButton::callback(
+            transcripts::skip(lang).to_string(),
+            SetupAction::Skip.encode(),
+        ));
+    }
+
+    buttons.push(InlineKeyboardButton::callback(
+        transcripts::cancel(lang).to_string(),
+        SetupAction::Cancel.encode(),
+    ));
+
+    InlineKeyboardMarkup::new(vec![buttons])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::NotionToken;
+
+    fn user_tokens(integration_token: &str, database_id: &str) -> UserTokens {
+        UserTokens {
+            imdb_token: String::new(),
+            tmdb_token: String::new(),
+            omdb_token: String::new(),
+            movie_info_provider: String::new(),
+            notion_token: NotionToken {
+                integration_token: integration_token.to_string(),
+                database_id: database_id.to_string(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_first_missing_step() {
+        assert_eq!(
+            SetupState::first_missing_step(&user_tokens("", "")),
+            SetupState::NotionToken
+        );
+        assert_eq!(
+            SetupState::first_missing_step(&user_tokens("secret_abc", "")),
+            SetupState::NotionPage
+        );
+        // the IMDb-API key is optional
+        assert_eq!(
+            SetupState::first_missing_step(&user_tokens("secret_abc", "db")),
+            SetupState::Idle
+        );
+    }
+
+    #[test]
+    fn test_action_reply() {
+        let lang = Language::En;
+
+        assert_eq!(
+            action_reply(SetupState::ImdbKey, SetupAction::Skip, lang),
+            Some(transcripts::setup_finished(lang))
+        );
+        // only the last step can be skipped
+        assert_eq!(
+            action_reply(SetupState::NotionPage, SetupAction::Skip, lang),
+            None
+        );
+        assert_eq!(
+            action_reply(SetupState::Idle, SetupAction::Skip, lang),
+            None
+        );
+
+        for state in [
+            SetupState::NotionToken,
+            SetupState::NotionPage,
+            SetupState::ImdbKey,
+        ] {
+            assert_eq!(
+                action_reply(state, SetupAction::Cancel, lang),
+                Some(transcripts::setup_cancelled(lang))
+            );
+        }
+    }
+}
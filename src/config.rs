@@ -1,3 +1,6 @@
+use crate::api::{CacheBackend, Provider};
+use crate::secret::TokenKey;
+
 use lazy_static::lazy_static;
 
 lazy_static! {
@@ -7,9 +10,29 @@ lazy_static! {
 pub struct Config {
     pub bot_token: String,
     pub database_url: String,
+    /// The first key encrypts the tokens users give us, the others are only there to decrypt.
+    pub token_encryption_keys: Vec<TokenKey>,
     pub host: String,
     pub port: u16,
     pub default_imdb_api_key: String,
+    pub default_tmdb_api_key: String,
+    pub default_omdb_api_key: String,
+    pub imdb_api_url: String,
+    pub tmdb_api_url: String,
+    pub omdb_api_url: String,
+    pub notion_client_id: String,
+    pub notion_client_secret: String,
+    pub notion_oauth_url: String,
+    pub notion_api_url: String,
+    pub movie_info_providers: Vec<Provider>,
+    pub movie_info_cache: CacheBackend,
+    pub movie_info_cache_ttl: u64,
+    pub imdb_usage_warning_threshold: f64,
+    pub user_rate_limit: usize,
+    pub shared_imdb_key_rate_limit: usize,
+    pub refresh_interval: u64,
+    /// How many titles per minute the background refresh may look up.
+    pub refresh_rate_limit: usize,
     pub help_page: String,
 }
 
@@ -19,6 +42,15 @@ impl Config {
             .expect("can't find `TG_BOT_TOKEN` in environment variables.");
         let database_url = std::env::var("DATABASE_URL")
             .expect("can't find `DATABASE_URL` in environment variables.");
+        let token_encryption_keys = std::env::var("TOKEN_ENCRYPTION_KEYS")
+            .expect("can't find `TOKEN_ENCRYPTION_KEYS` in environment variables.")
+            .split(',')
+            .filter(|key| !key.trim().is_empty())
+            .map(|key| {
+                key.parse()
+                    .expect("`TOKEN_ENCRYPTION_KEYS` contains an invalid key")
+            })
+            .collect::<Vec<_>>();
         let host = std::env::var("HOST").expect("`HOST` env variable is not set");
         let port = std::env::var("PORT")
             .expect("can't find `PORT` in environment variables")
@@ -26,6 +58,67 @@ impl Config {
             .expect("`PORT` is not an integer");
         let default_imdb_api_key = std::env::var("DEFAULT_IMDB_API_KEY")
             .expect("can't find `DEFAULT_IMDB_API_KEY` in environment variables.");
+        let default_tmdb_api_key = std::env::var("DEFAULT_TMDB_API_KEY").unwrap_or_default();
+        let default_omdb_api_key = std::env::var("DEFAULT_OMDB_API_KEY").unwrap_or_default();
+        let imdb_api_url =
+            std::env::var("IMDB_API_URL").unwrap_or_else(|_| "https://imdb-api.com".into());
+        let tmdb_api_url =
+            std::env::var("TMDB_API_URL").unwrap_or_else(|_| "https://api.themoviedb.org".into());
+        let omdb_api_url =
+            std::env::var("OMDB_API_URL").unwrap_or_else(|_| "https://www.omdbapi.com".into());
+        let notion_client_id = std::env::var("NOTION_CLIENT_ID").unwrap_or_default();
+        let notion_client_secret = std::env::var("NOTION_CLIENT_SECRET").unwrap_or_default();
+        let notion_oauth_url = std::env::var("NOTION_OAUTH_URL")
+            .unwrap_or_else(|_| "https://api.notion.com/v1/oauth".into());
+        let notion_api_url =
+            std::env::var("NOTION_API_URL").unwrap_or_else(|_| "https://api.notion.com/v1".into());
+        let movie_info_providers = std::env::var("MOVIE_INFO_PROVIDERS")
+            .map(|providers| parse_movie_info_providers(&providers))
+            .unwrap_or_else(|_| vec![Provider::ImdbApi]);
+        let movie_info_cache = std::env::var("MOVIE_INFO_CACHE")
+            .map(|cache| {
+                cache
+                    .parse()
+                    .expect("`MOVIE_INFO_CACHE` is not a supported cache backend")
+            })
+            .unwrap_or(CacheBackend::Postgres);
+        let movie_info_cache_ttl = std::env::var("MOVIE_INFO_CACHE_TTL")
+            .map(|ttl| {
+                ttl.parse()
+                    .expect("`MOVIE_INFO_CACHE_TTL` is not an integer")
+            })
+            .unwrap_or(24 * 60 * 60);
+        let imdb_usage_warning_threshold = std::env::var("IMDB_USAGE_WARNING_THRESHOLD")
+            .map(|threshold| {
+                threshold
+                    .parse()
+                    .expect("`IMDB_USAGE_WARNING_THRESHOLD` is not a number")
+            })
+            .unwrap_or(80.0);
+        let user_rate_limit = std::env::var("USER_RATE_LIMIT")
+            .map(|limit| limit.parse().expect("`USER_RATE_LIMIT` is not an integer"))
+            .unwrap_or(20);
+        let shared_imdb_key_rate_limit = std::env::var("SHARED_IMDB_KEY_RATE_LIMIT")
+            .map(|limit| {
+                limit
+                    .parse()
+                    .expect("`SHARED_IMDB_KEY_RATE_LIMIT` is not an integer")
+            })
+            .unwrap_or(30);
+        let refresh_interval = std::env::var("REFRESH_INTERVAL")
+            .map(|interval| {
+                interval
+                    .parse()
+                    .expect("`REFRESH_INTERVAL` is not an integer")
+            })
+            .unwrap_or(24 * 60 * 60);
+        let refresh_rate_limit = std::env::var("REFRESH_RATE_LIMIT")
+            .map(|limit| {
+                limit
+                    .parse()
+                    .expect("`REFRESH_RATE_LIMIT` is not an integer")
+            })
+            .unwrap_or(10);
         let help_page = std::env::var("HELP_PAGE").unwrap_or_else(|_| {
             "https://www.notion.so/octocat/ca61deb6472a4c73b9b43b0ecd549397".into()
         });
@@ -33,33 +126,135 @@ impl Config {
         Self {
             bot_token,
             database_url,
+            token_encryption_keys,
             host,
             port,
             default_imdb_api_key,
+            default_tmdb_api_key,
+            default_omdb_api_key,
+            imdb_api_url,
+            tmdb_api_url,
+            omdb_api_url,
+            notion_client_id,
+            notion_client_secret,
+            notion_oauth_url,
+            notion_api_url,
+            movie_info_providers,
+            movie_info_cache,
+            movie_info_cache_ttl,
+            imdb_usage_warning_threshold,
+            user_rate_limit,
+            shared_imdb_key_rate_limit,
+            refresh_interval,
+            refresh_rate_limit,
             help_page,
         }
     }
 }
 
+/// Searches need a provider to go to, so the list can't be empty.
+fn parse_movie_info_providers(providers: &str) -> Vec<Provider> {
+    let providers = providers
+        .split(',')
+        .filter(|provider| !provider.trim().is_empty())
+        .map(|provider| {
+            provider
+                .parse()
+                .expect("`MOVIE_INFO_PROVIDERS` contains an unsupported provider")
+        })
+        .collect::<Vec<_>>();
+
+    if providers.is_empty() {
+        panic!("`MOVIE_INFO_PROVIDERS` is empty, leave it out to use imdb_api only");
+    }
+
+    providers
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::config::Config;
+    use crate::api::{CacheBackend, Provider};
+    use crate::config::{parse_movie_info_providers, Config};
 
     #[test]
     fn test_parse_config() {
         std::env::set_var("TG_BOT_TOKEN", "a");
         std::env::set_var("DATABASE_URL", "b");
+        std::env::set_var(
+            "TOKEN_ENCRYPTION_KEYS",
+            "new:AQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQE=, old:AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=",
+        );
         std::env::set_var("HOST", "c");
         std::env::set_var("PORT", "80");
         std::env::set_var("DEFAULT_IMDB_API_KEY", "d");
         std::env::set_var("HELP_PAGE", "e");
+        std::env::set_var("DEFAULT_TMDB_API_KEY", "f");
+        std::env::set_var("TMDB_API_URL", "http://127.0.0.1:8080");
+        std::env::set_var("DEFAULT_OMDB_API_KEY", "g");
+        std::env::set_var("NOTION_CLIENT_ID", "h");
+        std::env::set_var("NOTION_CLIENT_SECRET", "i");
+        std::env::set_var("MOVIE_INFO_PROVIDERS", "tmdb, imdb_api");
+        std::env::set_var("MOVIE_INFO_CACHE", "memory");
+        std::env::set_var("MOVIE_INFO_CACHE_TTL", "3600");
+        std::env::set_var("IMDB_USAGE_WARNING_THRESHOLD", "90.5");
+        std::env::set_var("USER_RATE_LIMIT", "5");
+        std::env::set_var("REFRESH_INTERVAL", "0");
+        std::env::set_var("REFRESH_RATE_LIMIT", "30");
 
         let config = Config::from_env();
         assert_eq!(config.bot_token, "a".to_string());
         assert_eq!(config.database_url, "b".to_string());
+        assert_eq!(
+            config
+                .token_encryption_keys
+                .iter()
+                .map(|key| key.id.as_str())
+                .collect::<Vec<_>>(),
+            vec!["new", "old"]
+        );
         assert_eq!(config.host, "c".to_string());
         assert_eq!(config.port, 80);
         assert_eq!(config.default_imdb_api_key, "d".to_string());
         assert_eq!(config.help_page, "e".to_string());
+        assert_eq!(config.default_tmdb_api_key, "f".to_string());
+        assert_eq!(config.default_omdb_api_key, "g".to_string());
+        assert_eq!(config.omdb_api_url, "https://www.omdbapi.com".to_string());
+        assert_eq!(config.imdb_api_url, "https://imdb-api.com".to_string());
+        assert_eq!(config.tmdb_api_url, "http://127.0.0.1:8080".to_string());
+        assert_eq!(config.notion_client_id, "h".to_string());
+        assert_eq!(config.notion_client_secret, "i".to_string());
+        assert_eq!(
+            config.notion_oauth_url,
+            "https://api.notion.com/v1/oauth".to_string()
+        );
+        assert_eq!(
+            config.notion_api_url,
+            "https://api.notion.com/v1".to_string()
+        );
+        assert_eq!(
+            config.movie_info_providers,
+            vec![Provider::Tmdb, Provider::ImdbApi]
+        );
+        assert_eq!(config.movie_info_cache, CacheBackend::Memory);
+        assert_eq!(config.movie_info_cache_ttl, 3600);
+        assert_eq!(config.imdb_usage_warning_threshold, 90.5);
+        assert_eq!(config.user_rate_limit, 5);
+        assert_eq!(config.shared_imdb_key_rate_limit, 30);
+        assert_eq!(config.refresh_interval, 0);
+        assert_eq!(config.refresh_rate_limit, 30);
+    }
+
+    #[test]
+    fn test_parse_movie_info_providers() {
+        assert_eq!(
+            parse_movie_info_providers("omdb,,imdb_datasets, "),
+            vec![Provider::Omdb, Provider::ImdbDatasets]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "`MOVIE_INFO_PROVIDERS` is empty")]
+    fn test_parse_empty_movie_info_providers() {
+        parse_movie_info_providers(" , ");
     }
 }
@@ -1,4 +1,6 @@
+use crate::api::{DatabaseTemplate, MovieField, PropertyMapping, PropertySpec, Provider};
 use crate::error::{feedback_error, feedback_propagate_error, BotError};
+use crate::secret::{mask_secret, TokenCipher};
 use crate::{transcripts, Language};
 
 use anyhow::anyhow;
@@ -60,22 +62,179 @@ pub trait BotDatabase {
         fb_lang: Language,
     ) -> Result<bool, BotError>;
 
+    async fn store_movie_info_provider(
+        &self,
+        user_id: u64,
+        provider: Provider,
+        fb_lang: Language,
+    ) -> Result<bool, BotError>;
+
+    async fn store_provider_token(
+        &self,
+        user_id: u64,
+        provider: Provider,
+        token: &str,
+        fb_lang: Language,
+    ) -> Result<bool, BotError>;
+
     async fn remove_user_tokens(&self, user_id: u64, fb_lang: Language) -> Result<bool, BotError>;
+
+    async fn users_with_notion_database(
+        &self,
+        from_user_id: u64,
+        fb_lang: Language,
+    ) -> Result<Vec<u64>, BotError>;
+
+    async fn refresh_progress(&self, fb_lang: Language) -> Result<RefreshProgress, BotError>;
+
+    async fn store_refresh_progress(
+        &self,
+        user_id: u64,
+        start_cursor: Option<&str>,
+        fb_lang: Language,
+    ) -> Result<(), BotError>;
+
+    async fn finish_refresh_progress(&self, fb_lang: Language) -> Result<(), BotError>;
+
+    /// The page of the movie list a throttled `/refresh` of the user continues from.
+    async fn refresh_cursor(
+        &self,
+        user_id: u64,
+        fb_lang: Language,
+    ) -> Result<Option<String>, BotError>;
+
+    async fn store_refresh_cursor(
+        &self,
+        user_id: u64,
+        start_cursor: Option<&str>,
+        fb_lang: Language,
+    ) -> Result<(), BotError>;
+
+    async fn property_mapping(
+        &self,
+        user_id: u64,
+        fb_lang: Language,
+    ) -> Result<PropertyMapping, BotError>;
+
+    async fn store_property_mapping(
+        &self,
+        user_id: u64,
+        field: MovieField,
+        property: Option<&PropertySpec>,
+        fb_lang: Language,
+    ) -> Result<(), BotError>;
+
+    async fn reset_property_mapping(
+        &self,
+        user_id: u64,
+        field: MovieField,
+        fb_lang: Language,
+    ) -> Result<(), BotError>;
+
+    async fn database_template(
+        &self,
+        user_id: u64,
+        fb_lang: Language,
+    ) -> Result<Option<DatabaseTemplate>, BotError>;
+
+    async fn store_database_template(
+        &self,
+        user_id: u64,
+        template: &DatabaseTemplate,
+        fb_lang: Language,
+    ) -> Result<(), BotError>;
+
+    async fn remove_database_template(
+        &self,
+        user_id: u64,
+        fb_lang: Language,
+    ) -> Result<(), BotError>;
+
+    async fn store_notion_oauth_state(
+        &self,
+        state: &str,
+        request: &NotionOAuthRequest,
+        fb_lang: Language,
+    ) -> Result<(), BotError>;
+
+    /// The request `state` was handed out for, it can only be taken once and expires after a while.
+    async fn take_notion_oauth_state(
+        &self,
+        state: &str,
+        fb_lang: Language,
+    ) -> Result<Option<NotionOAuthRequest>, BotError>;
 }
 
 #[derive(Debug, Clone)]
 pub struct PgBotDatabase {
     pg_pool: PgPool,
+    /// Encrypts the tokens in `user_tokens`, empty tokens stay empty.
+    cipher: TokenCipher,
 }
 
 impl PgBotDatabase {
-    pub async fn connect(db_url: &str) -> anyhow::Result<Self> {
+    pub async fn connect(db_url: &str, cipher: TokenCipher) -> anyhow::Result<Self> {
         let pg_pool = PgPoolOptions::new()
             .max_connections(5)
             .connect(db_url)
             .await?;
 
-        Ok(Self { pg_pool })
+        Ok(Self { pg_pool, cipher })
+    }
+
+    /// Encrypts the tokens stored before they were encrypted and the ones encrypted with an older key.
+    /// Returns how many users had their tokens encrypted again.
+    pub async fn encrypt_user_tokens(&self) -> anyhow::Result<usize> {
+        let rows = sqlx::query_as::<_, (Decimal, String, String, String, String)>(
+            r#"
+SELECT user_id, imdb_token, tmdb_token, omdb_token, notion_token
+FROM user_tokens
+        "#,
+        )
+        .fetch_all(&self.pg_pool)
+        .await?;
+
+        let mut encrypted_users = 0;
+
+        for (user_id, imdb_token, tmdb_token, omdb_token, notion_token) in rows {
+            let mut changed = false;
+            let mut tokens = Vec::new();
+
+            for token in [imdb_token, tmdb_token, omdb_token, notion_token] {
+                match self.cipher.rewrap(&token)? {
+                    Some(encrypted) => {
+                        changed = true;
+                        tokens.push(encrypted);
+                    }
+                    None => tokens.push(token),
+                }
+            }
+
+            if !changed {
+                continue;
+            }
+
+            let mut query = sqlx::query(
+                r#"
+UPDATE user_tokens
+SET imdb_token = $1, tmdb_token = $2, omdb_token = $3, notion_token = $4
+WHERE user_id = $5
+        "#,
+            );
+            for token in tokens {
+                query = query.bind(token);
+            }
+
+            query.bind(user_id).execute(&self.pg_pool).await?;
+
+            encrypted_users += 1;
+        }
+
+        Ok(encrypted_users)
+    }
+
+    pub fn pg_pool(&self) -> &PgPool {
+        &self.pg_pool
     }
 
     async fn insert_default_user_tokens(&self, user_id: Decimal) -> anyhow::Result<()> {
@@ -96,7 +255,7 @@ VALUES ( $1, $2, $3, $4 )
     }
 
     async fn select_user_tokens(&self, user_id: Decimal) -> anyhow::Result<Option<UserTokens>> {
-        let user_token = sqlx::query_as(
+        let user_token: Option<UserTokens> = sqlx::query_as(
             r#"
 SELECT *
 FROM user_tokens
@@ -107,7 +266,17 @@ WHERE user_id = $1
         .fetch_optional(&self.pg_pool)
         .await?;
 
-        Ok(user_token)
+        user_token
+            .map(|mut tokens| {
+                tokens.imdb_token = self.cipher.decrypt(&tokens.imdb_token)?;
+                tokens.tmdb_token = self.cipher.decrypt(&tokens.tmdb_token)?;
+                tokens.omdb_token = self.cipher.decrypt(&tokens.omdb_token)?;
+                tokens.notion_token.integration_token = self
+                    .cipher
+                    .decrypt(&tokens.notion_token.integration_token)?;
+                Ok(tokens)
+            })
+            .transpose()
     }
 
     async fn select_imdb_token(&self, user_id: Decimal) -> anyhow::Result<Option<String>> {
@@ -121,7 +290,8 @@ WHERE user_id = $1
         .bind(user_id)
         .fetch_optional(&self.pg_pool)
         .await?
-        .map(|t| t.0);
+        .map(|t| self.cipher.decrypt(&t.0))
+        .transpose()?;
 
         Ok(imdb_token)
     }
@@ -140,7 +310,8 @@ WHERE user_id = $1
         .bind(user_id)
         .fetch_optional(&self.pg_pool)
         .await?
-        .map(|t| t.0);
+        .map(|t| self.cipher.decrypt(&t.0))
+        .transpose()?;
 
         Ok(imdb_token)
     }
@@ -153,7 +324,7 @@ SET imdb_token = $1
 WHERE user_id = $2
         "#,
         )
-        .bind(token)
+        .bind(self.cipher.encrypt(token)?)
         .bind(user_id)
         .execute(&self.pg_pool)
         .await?
@@ -170,7 +341,7 @@ SET notion_token = $1
 WHERE user_id = $2
         "#,
         )
-        .bind(token)
+        .bind(self.cipher.encrypt(token)?)
         .bind(user_id)
         .execute(&self.pg_pool)
         .await?
@@ -187,7 +358,7 @@ WHERE user_id = $2
         let rows_affected = sqlx::query(
             r#"
 UPDATE user_tokens
-SET notion_database_id = $1
+SET notion_database_id = $1, refresh_cursor = NULL
 WHERE user_id = $2
         "#,
         )
@@ -200,6 +371,53 @@ WHERE user_id = $2
         Ok(rows_affected > 0)
     }
 
+    async fn update_movie_info_provider(
+        &self,
+        user_id: Decimal,
+        provider: Provider,
+    ) -> anyhow::Result<bool> {
+        let rows_affected = sqlx::query(
+            r#"
+UPDATE user_tokens
+SET movie_info_provider = $1
+WHERE user_id = $2
+        "#,
+        )
+        .bind(provider.code())
+        .bind(user_id)
+        .execute(&self.pg_pool)
+        .await?
+        .rows_affected();
+
+        Ok(rows_affected > 0)
+    }
+
+    async fn update_provider_token(
+        &self,
+        user_id: Decimal,
+        provider: Provider,
+        token: &str,
+    ) -> anyhow::Result<bool> {
+        let column = provider_token_column(provider)
+            .ok_or_else(|| anyhow!("{} doesn't need a token", provider.name()))?;
+
+        let rows_affected = sqlx::query(&format!(
+            r#"
+UPDATE user_tokens
+SET {} = $1
+WHERE user_id = $2
+        "#,
+            column
+        ))
+        .bind(self.cipher.encrypt(token)?)
+        .bind(user_id)
+        .execute(&self.pg_pool)
+        .await?
+        .rows_affected();
+
+        Ok(rows_affected > 0)
+    }
+
     async fn delete_user_tokens(&self, user_id: Decimal) -> anyhow::Result<bool> {
         let rows_affected = sqlx::query(
             r#"
@@ -215,6 +433,324 @@ WHERE user_id = $1
         Ok(rows_affected > 0)
     }
 
+    async fn select_users_with_notion_database(
+        &self,
+        from_user_id: Decimal,
+    ) -> anyhow::Result<Vec<u64>> {
+        let user_ids = sqlx::query_as::<_, (i64,)>(
+            r#"
+SELECT user_id::BIGINT
+FROM user_tokens
+WHERE notion_token <> '' AND notion_database_id <> '' AND user_id >= $1
+ORDER BY user_id
+        "#,
+        )
+        .bind(from_user_id)
+        .fetch_all(&self.pg_pool)
+        .await?
+        .into_iter()
+        .map(|(user_id,)| user_id as u64)
+        .collect();
+
+        Ok(user_ids)
+    }
+
+    async fn select_refresh_progress(&self) -> anyhow::Result<RefreshProgress> {
+        let progress = sqlx::query_as::<_, (Option<i64>, Option<String>, Option<i64>)>(
+            r#"
+SELECT user_id::BIGINT, start_cursor, EXTRACT(EPOCH FROM now() - finished_at)::BIGINT
+FROM refresh_progress
+        "#,
+        )
+        .fetch_optional(&self.pg_pool)
+        .await?
+        .map(
+            |(user_id, start_cursor, secs_since_finished)| RefreshProgress {
+                user_id: user_id.map(|user_id| user_id as u64),
+                start_cursor,
+                secs_since_finished: secs_since_finished.map(|secs| secs.max(0) as u64),
+            },
+        )
+        .unwrap_or_default();
+
+        Ok(progress)
+    }
+
+    async fn upsert_refresh_progress(
+        &self,
+        user_id: Decimal,
+        start_cursor: Option<&str>,
+    ) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+INSERT INTO refresh_progress ( id, user_id, start_cursor )
+VALUES ( TRUE, $1, $2 )
+ON CONFLICT ( id ) DO UPDATE
+SET user_id = EXCLUDED.user_id, start_cursor = EXCLUDED.start_cursor
+        "#,
+        )
+        .bind(user_id)
+        .bind(start_cursor)
+        .execute(&self.pg_pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn finish_refresh(&self) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+INSERT INTO refresh_progress ( id, finished_at )
+VALUES ( TRUE, now() )
+ON CONFLICT ( id ) DO UPDATE
+SET user_id = NULL, start_cursor = NULL, finished_at = EXCLUDED.finished_at
+        "#,
+        )
+        .execute(&self.pg_pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn select_refresh_cursor(&self, user_id: Decimal) -> anyhow::Result<Option<String>> {
+        let start_cursor = sqlx::query_as::<_, (Option<String>,)>(
+            r#"
+SELECT refresh_cursor
+FROM user_tokens
+WHERE user_id = $1
+        "#,
+        )
+        .bind(user_id)
+        .fetch_optional(&self.pg_pool)
+        .await?
+        .and_then(|(start_cursor,)| start_cursor);
+
+        Ok(start_cursor)
+    }
+
+    async fn update_refresh_cursor(
+        &self,
+        user_id: Decimal,
+        start_cursor: Option<&str>,
+    ) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+UPDATE user_tokens
+SET refresh_cursor = $1
+WHERE user_id = $2
+        "#,
+        )
+        .bind(start_cursor)
+        .bind(user_id)
+        .execute(&self.pg_pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn select_property_mapping(&self, user_id: Decimal) -> anyhow::Result<PropertyMapping> {
+        let rows = sqlx::query_as::<_, (String, Option<String>, Option<String>)>(
+            r#"
+SELECT field, property_name, property_type
+FROM property_mappings
+WHERE user_id = $1
+        "#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pg_pool)
+        .await?;
+
+        let mut mapping = PropertyMapping::default();
+
+        for (field, property_name, property_type) in rows {
+            let field = match field.parse() {
+                Ok(field) => field,
+                Err(_) => continue,
+            };
+            let property = match (property_name, property_type) {
+                (Some(name), Some(property_type)) => match property_type.parse() {
+                    Ok(property_type) => Some(PropertySpec {
+                        name,
+                        property_type,
+                    }),
+                    Err(_) => continue,
+                },
+                _ => None,
+            };
+
+            mapping.set(field, property);
+        }
+
+        Ok(mapping)
+    }
+
+    async fn upsert_property_mapping(
+        &self,
+        user_id: Decimal,
+        field: MovieField,
+        property: Option<&PropertySpec>,
+    ) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+INSERT INTO property_mappings ( user_id, field, property_name, property_type )
+VALUES ( $1, $2, $3, $4 )
+ON CONFLICT ( user_id, field ) DO UPDATE
+SET property_name = EXCLUDED.property_name, property_type = EXCLUDED.property_type
+        "#,
+        )
+        .bind(user_id)
+        .bind(field.code())
+        .bind(property.map(|property| property.name.as_str()))
+        .bind(property.map(|property| property.property_type.as_str()))
+        .execute(&self.pg_pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn delete_property_mapping(
+        &self,
+        user_id: Decimal,
+        field: MovieField,
+    ) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+DELETE FROM property_mappings
+WHERE user_id = $1 AND field = $2
+        "#,
+        )
+        .bind(user_id)
+        .bind(field.code())
+        .execute(&self.pg_pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn select_database_template(
+        &self,
+        user_id: Decimal,
+    ) -> anyhow::Result<Option<DatabaseTemplate>> {
+        let template = sqlx::query_as::<_, (String, String, String, String)>(
+            r#"
+SELECT page_id, title, icon, fields
+FROM database_templates
+WHERE user_id = $1
+        "#,
+        )
+        .bind(user_id)
+        .fetch_optional(&self.pg_pool)
+        .await?
+        .map(|(page_id, title, icon, fields)| DatabaseTemplate {
+            page_id,
+            title,
+            icon,
+            fields: fields
+                .split(',')
+                .filter_map(|field| field.parse().ok())
+                .collect(),
+        });
+
+        Ok(template)
+    }
+
+    async fn upsert_database_template(
+        &self,
+        user_id: Decimal,
+        template: &DatabaseTemplate,
+    ) -> anyhow::Result<()> {
+        let fields = template
+            .fields
+            .iter()
+            .map(MovieField::code)
+            .collect::<Vec<_>>()
+            .join(",");
+
+        sqlx::query(
+            r#"
+INSERT INTO database_templates ( user_id, page_id, title, icon, fields )
+VALUES ( $1, $2, $3, $4, $5 )
+ON CONFLICT ( user_id ) DO UPDATE
+SET page_id = EXCLUDED.page_id, title = EXCLUDED.title, icon = EXCLUDED.icon, fields = EXCLUDED.fields
+        "#,
+        )
+        .bind(user_id)
+        .bind(&template.page_id)
+        .bind(&template.title)
+        .bind(&template.icon)
+        .bind(fields)
+        .execute(&self.pg_pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn delete_database_template(&self, user_id: Decimal) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+DELETE FROM database_templates
+WHERE user_id = $1
+        "#,
+        )
+        .bind(user_id)
+        .execute(&self.pg_pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn insert_notion_oauth_state(
+        &self,
+        state: &str,
+        request: &NotionOAuthRequest,
+    ) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+INSERT INTO notion_oauth_states ( state, user_id, chat_id )
+VALUES ( $1, $2, $3 )
+        "#,
+        )
+        .bind(state)
+        .bind(Decimal::from(request.user_id))
+        .bind(request.chat_id)
+        .execute(&self.pg_pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn delete_notion_oauth_state(
+        &self,
+        state: &str,
+    ) -> anyhow::Result<Option<NotionOAuthRequest>> {
+        // nobody takes that long on the authorization page
+        sqlx::query(
+            r#"
+DELETE FROM notion_oauth_states
+WHERE created_at < now() - INTERVAL '15 minutes'
+        "#,
+        )
+        .execute(&self.pg_pool)
+        .await?;
+
+        let request = sqlx::query_as::<_, (i64, i64)>(
+            r#"
+DELETE FROM notion_oauth_states
+WHERE state = $1
+RETURNING user_id::BIGINT, chat_id
+        "#,
+        )
+        .bind(state)
+        .fetch_optional(&self.pg_pool)
+        .await?
+        .map(|(user_id, chat_id)| NotionOAuthRequest {
+            user_id: user_id as u64,
+            chat_id,
+        });
+
+        Ok(request)
+    }
+
     #[allow(dead_code)]
     pub async fn reset_user_tokens(&self, user_id: u64, fb_lang: Language) -> Result<(), BotError> {
         self.remove_user_tokens(user_id, fb_lang).await?;
@@ -319,22 +855,210 @@ impl BotDatabase for PgBotDatabase {
             .map_err(|e| feedback_propagate_error(e.context(transcripts::database_error(fb_lang))))
     }
 
+    async fn store_movie_info_provider(
+        &self,
+        user_id: u64,
+        provider: Provider,
+        fb_lang: Language,
+    ) -> Result<bool, BotError> {
+        self.update_movie_info_provider(user_id.into(), provider)
+            .await
+            .map_err(|e| feedback_propagate_error(e.context(transcripts::database_error(fb_lang))))
+    }
+
+    async fn store_provider_token(
+        &self,
+        user_id: u64,
+        provider: Provider,
+        token: &str,
+        fb_lang: Language,
+    ) -> Result<bool, BotError> {
+        self.update_provider_token(user_id.into(), provider, token)
+            .await
+            .map_err(|e| feedback_propagate_error(e.context(transcripts::database_error(fb_lang))))
+    }
+
     async fn remove_user_tokens(&self, user_id: u64, fb_lang: Language) -> Result<bool, BotError> {
         self.delete_user_tokens(user_id.into())
             .await
             .map_err(|e| feedback_propagate_error(e.context(transcripts::database_error(fb_lang))))
     }
+
+    async fn users_with_notion_database(
+        &self,
+        from_user_id: u64,
+        fb_lang: Language,
+    ) -> Result<Vec<u64>, BotError> {
+        self.select_users_with_notion_database(from_user_id.into())
+            .await
+            .map_err(|e| feedback_propagate_error(e.context(transcripts::database_error(fb_lang))))
+    }
+
+    async fn refresh_progress(&self, fb_lang: Language) -> Result<RefreshProgress, BotError> {
+        self.select_refresh_progress()
+            .await
+            .map_err(|e| feedback_propagate_error(e.context(transcripts::database_error(fb_lang))))
+    }
+
+    async fn store_refresh_progress(
+        &self,
+        user_id: u64,
+        start_cursor: Option<&str>,
+        fb_lang: Language,
+    ) -> Result<(), BotError> {
+        self.upsert_refresh_progress(user_id.into(), start_cursor)
+            .await
+            .map_err(|e| feedback_propagate_error(e.context(transcripts::database_error(fb_lang))))
+    }
+
+    async fn finish_refresh_progress(&self, fb_lang: Language) -> Result<(), BotError> {
+        self.finish_refresh()
+            .await
+            .map_err(|e| feedback_propagate_error(e.context(transcripts::database_error(fb_lang))))
+    }
+
+    async fn refresh_cursor(
+        &self,
+        user_id: u64,
+        fb_lang: Language,
+    ) -> Result<Option<String>, BotError> {
+        self.select_refresh_cursor(user_id.into())
+            .await
+            .map_err(|e| feedback_propagate_error(e.context(transcripts::database_error(fb_lang))))
+    }
+
+    async fn store_refresh_cursor(
+        &self,
+        user_id: u64,
+        start_cursor: Option<&str>,
+        fb_lang: Language,
+    ) -> Result<(), BotError> {
+        self.update_refresh_cursor(user_id.into(), start_cursor)
+            .await
+            .map_err(|e| feedback_propagate_error(e.context(transcripts::database_error(fb_lang))))
+    }
+
+    async fn property_mapping(
+        &self,
+        user_id: u64,
+        fb_lang: Language,
+    ) -> Result<PropertyMapping, BotError> {
+        self.select_property_mapping(user_id.into())
+            .await
+            .map_err(|e| feedback_propagate_error(e.context(transcripts::database_error(fb_lang))))
+    }
+
+    async fn store_property_mapping(
+        &self,
+        user_id: u64,
+        field: MovieField,
+        property: Option<&PropertySpec>,
+        fb_lang: Language,
+    ) -> Result<(), BotError> {
+        self.upsert_property_mapping(user_id.into(), field, property)
+            .await
+            .map_err(|e| feedback_propagate_error(e.context(transcripts::database_error(fb_lang))))
+    }
+
+    async fn reset_property_mapping(
+        &self,
+        user_id: u64,
+        field: MovieField,
+        fb_lang: Language,
+    ) -> Result<(), BotError> {
+        self.delete_property_mapping(user_id.into(), field)
+            .await
+            .map_err(|e| feedback_propagate_error(e.context(transcripts::database_error(fb_lang))))
+    }
+
+    async fn database_template(
+        &self,
+        user_id: u64,
+        fb_lang: Language,
+    ) -> Result<Option<DatabaseTemplate>, BotError> {
+        self.select_database_template(user_id.into())
+            .await
+            .map_err(|e| feedback_propagate_error(e.context(transcripts::database_error(fb_lang))))
+    }
+
+    async fn store_database_template(
+        &self,
+        user_id: u64,
+        template: &DatabaseTemplate,
+        fb_lang: Language,
+    ) -> Result<(), BotError> {
+        self.upsert_database_template(user_id.into(), template)
+            .await
+            .map_err(|e| feedback_propagate_error(e.context(transcripts::database_error(fb_lang))))
+    }
+
+    async fn remove_database_template(
+        &self,
+        user_id: u64,
+        fb_lang: Language,
+    ) -> Result<(), BotError> {
+        self.delete_database_template(user_id.into())
+            .await
+            .map_err(|e| feedback_propagate_error(e.context(transcripts::database_error(fb_lang))))
+    }
+
+    async fn store_notion_oauth_state(
+        &self,
+        state: &str,
+        request: &NotionOAuthRequest,
+        fb_lang: Language,
+    ) -> Result<(), BotError> {
+        self.insert_notion_oauth_state(state, request)
+            .await
+            .map_err(|e| feedback_propagate_error(e.context(transcripts::database_error(fb_lang))))
+    }
+
+    async fn take_notion_oauth_state(
+        &self,
+        state: &str,
+        fb_lang: Language,
+    ) -> Result<Option<NotionOAuthRequest>, BotError> {
+        self.delete_notion_oauth_state(state)
+            .await
+            .map_err(|e| feedback_propagate_error(e.context(transcripts::database_error(fb_lang))))
+    }
+}
+
+/// Column of `user_tokens` holding the user's own key for `provider`.
+fn provider_token_column(provider: Provider) -> Option<&'static str> {
+    match provider {
+        Provider::ImdbApi => Some("imdb_token"),
+        Provider::Tmdb => Some("tmdb_token"),
+        Provider::Omdb => Some("omdb_token"),
+        Provider::ImdbDatasets => None,
+    }
 }
 
 #[derive(sqlx::FromRow)]
 pub struct UserTokens {
-    pub user_id: Decimal,
     pub imdb_token: String,
+    pub tmdb_token: String,
+    pub omdb_token: String,
+    pub movie_info_provider: String,
     #[sqlx(flatten)]
     pub notion_token: NotionToken,
 }
 
 impl UserTokens {
+    /// The provider the user picked with `/provider`, if any.
+    pub fn movie_info_provider(&self) -> Option<Provider> {
+        self.movie_info_provider.parse().ok()
+    }
+
+    pub fn provider_token(&self, provider: Provider) -> &str {
+        match provider {
+            Provider::ImdbApi => &self.imdb_token,
+            Provider::Tmdb => &self.tmdb_token,
+            Provider::Omdb => &self.omdb_token,
+            Provider::ImdbDatasets => "",
+        }
+    }
+
     pub fn notion_token_is_good(&self) -> bool {
         !(self.notion_token.integration_token.is_empty()
             || self.notion_token.database_id.is_empty())
@@ -345,12 +1069,12 @@ impl UserTokens {
 
         if self.notion_token.integration_token.is_empty() {
             hint_msg += "\n";
-            hint_msg += transcripts::user_hint_set_notion_token(lang);
+            hint_msg += transcripts::user_hint_missing_notion_token(lang);
         }
 
         if self.notion_token.database_id.is_empty() {
             hint_msg += "\n";
-            hint_msg += transcripts::user_hint_create_notion_database(lang);
+            hint_msg += transcripts::user_hint_missing_movie_list(lang);
         }
 
         hint_msg
@@ -358,10 +1082,15 @@ impl UserTokens {
 
     pub fn summary(&self, lang: Language) -> String {
         format!(
-            "Tokens:\nIMDb token: {}\nNotion token:{}\nNotion database ID: {}",
-            token_to_string(&self.imdb_token, lang),
-            token_to_string(&self.notion_token.integration_token, lang),
-            token_to_string(&self.notion_token.database_id, lang)
+            "Tokens:\nIMDb token: {}\nTMDb token: {}\nOMDb token: {}\nNotion token:{}\nNotion database ID: {}\n\nMovie information provider: {}",
+            secret_to_string(&self.imdb_token, lang),
+            secret_to_string(&self.tmdb_token, lang),
+            secret_to_string(&self.omdb_token, lang),
+            secret_to_string(&self.notion_token.integration_token, lang),
+            token_to_string(&self.notion_token.database_id, lang),
+            self.movie_info_provider()
+                .map(|p| p.name())
+                .unwrap_or_else(|| transcripts::not_set(lang))
         )
     }
 }
@@ -374,6 +1103,14 @@ fn token_to_string(s: &str, lang: Language) -> &str {
     }
 }
 
+fn secret_to_string(s: &str, lang: Language) -> String {
+    if s.is_empty() {
+        transcripts::not_set(lang).to_string()
+    } else {
+        mask_secret(s)
+    }
+}
+
 #[derive(sqlx::FromRow)]
 pub struct NotionToken {
     #[sqlx(rename = "notion_token")]
@@ -381,3 +1118,21 @@ pub struct NotionToken {
     #[sqlx(rename = "notion_database_id")]
     pub database_id: String,
 }
+
+/// A user on their way through the Notion authorization page.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NotionOAuthRequest {
+    pub user_id: u64,
+    /// Where to tell the user how it went.
+    pub chat_id: i64,
+}
+
+/// Where the background refresh of all movie lists is, so it can resume after a restart.
+#[derive(Debug, Default)]
+pub struct RefreshProgress {
+    /// The user whose movie list is being refreshed, if a refresh is running.
+    pub user_id: Option<u64>,
+    /// The page of the user's movie list to continue from.
+    pub start_cursor: Option<String>,
+    pub secs_since_finished: Option<u64>,
+}
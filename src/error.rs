@@ -11,6 +11,14 @@ pub enum BotError {
     Propagate(anyhow::Error),
 }
 
+impl BotError {
+    pub fn inner(&self) -> &anyhow::Error {
+        match self {
+            BotError::FeedBack(e) | BotError::FeedBackPropagate(e) | BotError::Propagate(e) => e,
+        }
+    }
+}
+
 pub fn feedback_error(error: impl Into<anyhow::Error>) -> BotError {
     BotError::FeedBack(anyhow!(error))
 }
@@ -3,7 +3,11 @@ mod bot;
 mod config;
 mod db;
 mod error;
+mod rate_limit;
+mod secret;
 mod transcripts;
+mod usage_warning;
 
 use crate::transcripts::Language;
+pub use api::import_imdb_datasets;
 pub use bot::start_bot;
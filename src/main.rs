@@ -1,7 +1,27 @@
+use std::path::PathBuf;
+
 #[tokio::main]
 async fn main() {
     pretty_env_logger::init();
-    log::info!("Starting bot...");
 
-    nmlbot::start_bot().await;
+    let mut args = std::env::args().skip(1);
+
+    match args.next().as_deref() {
+        Some("import-imdb-datasets") => {
+            let dir = args.next().map(PathBuf::from).unwrap_or_else(|| ".".into());
+            let database_url = std::env::var("DATABASE_URL")
+                .expect("can't find `DATABASE_URL` in environment variables.");
+
+            log::info!("Importing IMDb datasets from {}...", dir.display());
+
+            nmlbot::import_imdb_datasets(&database_url, &dir)
+                .await
+                .expect("failed to import IMDb datasets");
+        }
+        _ => {
+            log::info!("Starting bot...");
+
+            nmlbot::start_bot().await;
+        }
+    }
 }
@@ -0,0 +1,378 @@
+use crate::api::{MovieInfo, Provider, QuotaExceeded, RequestMovieInfo, SearchResult};
+use crate::error::{feedback_error, BotError};
+use crate::{transcripts, Language};
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use reqwest::Client;
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+type Api = Arc<dyn RequestMovieInfo + Send + Sync>;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Limit {
+    pub requests: usize,
+    pub period: Duration,
+}
+
+impl Limit {
+    pub fn new(requests: usize, period: Duration) -> Self {
+        Self { requests, period }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+enum Bucket {
+    User(u64),
+    SharedImdbKey,
+}
+
+struct Requests {
+    buckets: HashMap<Bucket, VecDeque<Instant>>,
+    pruned_at: Instant,
+}
+
+/// Throttles outgoing API calls per Telegram user and globally for calls with the shared default
+/// IMDb-API key, so that nobody can burn the quota for everyone else.
+///
+/// Limits with zero requests are disabled.
+pub struct RateLimiter {
+    user_limit: Limit,
+    shared_imdb_key_limit: Limit,
+    requests: Mutex<Requests>,
+}
+
+impl RateLimiter {
+    pub fn new(user_limit: Limit, shared_imdb_key_limit: Limit) -> Self {
+        Self {
+            user_limit,
+            shared_imdb_key_limit,
+            requests: Mutex::new(Requests {
+                buckets: HashMap::new(),
+                pruned_at: Instant::now(),
+            }),
+        }
+    }
+
+    /// Records a request of `user_id`, or tells the user how long to wait when the limit is reached.
+    pub fn acquire(&self, user_id: u64, fb_lang: Language) -> Result<(), BotError> {
+        self.acquire_at(Bucket::User(user_id), Instant::now())
+            .map_err(|retry_after| {
+                feedback_error(anyhow!(transcripts::too_many_requests(
+                    fb_lang,
+                    retry_after_secs(retry_after)
+                )))
+            })
+    }
+
+    /// Records a call with the shared default IMDb-API key. Reaching the limit counts as an
+    /// exceeded quota, so that the provider chain can fall back to another provider.
+    pub fn acquire_shared_imdb_key(&self, fb_lang: Language) -> Result<(), BotError> {
+        self.acquire_at(Bucket::SharedImdbKey, Instant::now())
+            .map_err(|retry_after| {
+                feedback_error(anyhow!(QuotaExceeded(transcripts::too_many_requests(
+                    fb_lang,
+                    retry_after_secs(retry_after)
+                ))))
+            })
+    }
+
+    fn limit(&self, bucket: Bucket) -> Limit {
+        match bucket {
+            Bucket::User(_) => self.user_limit,
+            Bucket::SharedImdbKey => self.shared_imdb_key_limit,
+        }
+    }
+
+    fn acquire_at(&self, bucket: Bucket, now: Instant) -> Result<(), Duration> {
+        let limit = self.limit(bucket);
+
+        if limit.requests == 0 {
+            return Ok(());
+        }
+
+        let mut requests = self.requests.lock().unwrap_or_else(|e| e.into_inner());
+
+        // users who stopped sending requests would otherwise keep their bucket forever
+        if now.duration_since(requests.pruned_at) >= self.user_limit.period {
+            requests.buckets.retain(|bucket, timestamps| {
+                timestamps
+                    .back()
+                    .map(|t| now.duration_since(*t) < self.limit(*bucket).period)
+                    .unwrap_or(false)
+            });
+            requests.pruned_at = now;
+        }
+
+        let timestamps = requests.buckets.entry(bucket).or_default();
+
+        while timestamps
+            .front()
+            .map(|t| now.duration_since(*t) >= limit.period)
+            .unwrap_or(false)
+        {
+            timestamps.pop_front();
+        }
+
+        if timestamps.len() >= limit.requests {
+            return Err(timestamps
+                .front()
+                .map(|t| limit.period - now.duration_since(*t))
+                .unwrap_or(limit.period));
+        }
+
+        timestamps.push_back(now);
+
+        Ok(())
+    }
+
+    #[cfg(test)]
+    fn bucket_count(&self) -> usize {
+        self.requests
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .buckets
+            .len()
+    }
+}
+
+// round up, "try again in 0 seconds" is not helpful
+fn retry_after_secs(retry_after: Duration) -> u64 {
+    retry_after.as_secs() + 1
+}
+
+/// Counts the calls the wrapped provider makes with its default key against the shared IMDb-API
+/// limit, wherever in the provider chain it is.
+pub struct SharedImdbKeyLimitedApi {
+    inner: Api,
+    rate_limiter: Arc<RateLimiter>,
+}
+
+impl SharedImdbKeyLimitedApi {
+    pub fn new(inner: Api, rate_limiter: Arc<RateLimiter>) -> Self {
+        Self {
+            inner,
+            rate_limiter,
+        }
+    }
+
+    fn acquire(&self, api_key: &str, lang: Language) -> Result<(), BotError> {
+        // an empty key makes the provider fall back to the default key
+        if api_key.is_empty() {
+            self.rate_limiter.acquire_shared_imdb_key(lang)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl RequestMovieInfo for SharedImdbKeyLimitedApi {
+    async fn search(
+        &self,
+        client: &Client,
+        api_key: &str,
+        keyword: &str,
+        limits: usize,
+        lang: Language,
+    ) -> Result<Vec<SearchResult>, BotError> {
+        self.acquire(api_key, lang)?;
+        self.inner
+            .search(client, api_key, keyword, limits, lang)
+            .await
+    }
+
+    async fn request_movie_information(
+        &self,
+        client: &Client,
+        api_key: &str,
+        id: &str,
+        lang: Language,
+    ) -> Result<MovieInfo, BotError> {
+        self.acquire(api_key, lang)?;
+        self.inner
+            .request_movie_information(client, api_key, id, lang)
+            .await
+    }
+
+    async fn refresh_movie_information(
+        &self,
+        client: &Client,
+        api_key: &str,
+        id: &str,
+        lang: Language,
+    ) -> Result<MovieInfo, BotError> {
+        self.acquire(api_key, lang)?;
+        self.inner
+            .refresh_movie_information(client, api_key, id, lang)
+            .await
+    }
+
+    fn provider(&self) -> Provider {
+        self.inner.provider()
+    }
+
+    fn accepts_id(&self, id: &str) -> bool {
+        self.inner.accepts_id(id)
+    }
+
+    fn has_api_key(&self, api_key: &str) -> bool {
+        self.inner.has_api_key(api_key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rate_limiter() -> RateLimiter {
+        RateLimiter::new(
+            Limit::new(2, Duration::from_secs(60)),
+            Limit::new(3, Duration::from_secs(3600)),
+        )
+    }
+
+    #[test]
+    fn test_user_limit() {
+        let rate_limiter = rate_limiter();
+        let now = Instant::now();
+
+        assert!(rate_limiter.acquire_at(Bucket::User(1), now).is_ok());
+        assert!(rate_limiter.acquire_at(Bucket::User(1), now).is_ok());
+        assert_eq!(
+            rate_limiter.acquire_at(Bucket::User(1), now + Duration::from_secs(20)),
+            Err(Duration::from_secs(40))
+        );
+
+        // other users are not affected
+        assert!(rate_limiter.acquire_at(Bucket::User(2), now).is_ok());
+
+        assert!(rate_limiter
+            .acquire_at(Bucket::User(1), now + Duration::from_secs(60))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_shared_imdb_key_limit() {
+        let rate_limiter = rate_limiter();
+        let now = Instant::now();
+
+        for _ in 0..3 {
+            assert!(rate_limiter.acquire_at(Bucket::SharedImdbKey, now).is_ok());
+        }
+        assert_eq!(
+            rate_limiter.acquire_at(Bucket::SharedImdbKey, now),
+            Err(Duration::from_secs(3600))
+        );
+
+        // users don't share their own limit
+        assert!(rate_limiter.acquire_at(Bucket::User(1), now).is_ok());
+    }
+
+    #[test]
+    fn test_throttled_requests_are_not_counted() {
+        let rate_limiter = rate_limiter();
+        let now = Instant::now();
+
+        assert!(rate_limiter.acquire_at(Bucket::User(1), now).is_ok());
+        assert!(rate_limiter.acquire_at(Bucket::User(1), now).is_ok());
+        assert!(rate_limiter.acquire_at(Bucket::User(1), now).is_err());
+
+        assert!(rate_limiter
+            .acquire_at(Bucket::User(1), now + Duration::from_secs(60))
+            .is_ok());
+        assert!(rate_limiter
+            .acquire_at(Bucket::User(1), now + Duration::from_secs(60))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_idle_buckets_are_pruned() {
+        let rate_limiter = rate_limiter();
+        let now = Instant::now();
+
+        assert!(rate_limiter.acquire_at(Bucket::User(1), now).is_ok());
+        assert!(rate_limiter.acquire_at(Bucket::SharedImdbKey, now).is_ok());
+        assert_eq!(rate_limiter.bucket_count(), 2);
+
+        // user 1 has been idle for a whole period, the shared key's period isn't over yet
+        assert!(rate_limiter
+            .acquire_at(Bucket::User(2), now + Duration::from_secs(60))
+            .is_ok());
+        assert_eq!(rate_limiter.bucket_count(), 2);
+    }
+
+    #[test]
+    fn test_disabled_limit() {
+        let rate_limiter = RateLimiter::new(
+            Limit::new(0, Duration::from_secs(60)),
+            Limit::new(0, Duration::from_secs(3600)),
+        );
+        let now = Instant::now();
+
+        for _ in 0..100 {
+            assert!(rate_limiter.acquire_at(Bucket::User(1), now).is_ok());
+            assert!(rate_limiter.acquire_at(Bucket::SharedImdbKey, now).is_ok());
+        }
+        assert_eq!(rate_limiter.bucket_count(), 0);
+    }
+
+    struct NotFoundApi;
+
+    #[async_trait]
+    impl RequestMovieInfo for NotFoundApi {
+        async fn search(
+            &self,
+            _client: &Client,
+            _api_key: &str,
+            _keyword: &str,
+            _limits: usize,
+            _lang: Language,
+        ) -> Result<Vec<SearchResult>, BotError> {
+            Ok(Vec::new())
+        }
+
+        async fn request_movie_information(
+            &self,
+            _client: &Client,
+            _api_key: &str,
+            id: &str,
+            _lang: Language,
+        ) -> Result<MovieInfo, BotError> {
+            Err(feedback_error(anyhow!("{} not found", id)))
+        }
+
+        fn provider(&self) -> Provider {
+            Provider::ImdbApi
+        }
+    }
+
+    #[tokio::test]
+    async fn test_shared_imdb_key_limited_api() {
+        let rate_limiter = Arc::new(RateLimiter::new(
+            Limit::new(0, Duration::from_secs(60)),
+            Limit::new(1, Duration::from_secs(3600)),
+        ));
+        let api = SharedImdbKeyLimitedApi::new(Arc::new(NotFoundApi), rate_limiter);
+        let client = Client::new();
+        let lang = Language::default();
+
+        assert!(api.search(&client, "", "The Matrix", 5, lang).await.is_ok());
+
+        // the shared key is used up, so the chain may fall back to another provider
+        let error = api
+            .request_movie_information(&client, "", "tt0133093", lang)
+            .await
+            .unwrap_err();
+        assert!(error.inner().chain().any(|e| e.is::<QuotaExceeded>()));
+
+        // users with their own token don't count against it
+        assert!(api
+            .search(&client, "own_key", "The Matrix", 5, lang)
+            .await
+            .is_ok());
+    }
+}
@@ -0,0 +1,308 @@
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Nonce};
+use anyhow::{anyhow, bail};
+
+use std::fmt;
+use std::str::FromStr;
+
+/// Stored tokens starting with it are encrypted, older ones are still plaintext.
+const ENCRYPTED_PREFIX: &str = "enc:v1:";
+const NONCE_LENGTH: usize = 12;
+const KEY_LENGTH: usize = 32;
+
+/// A key encrypting the keys of the stored tokens, given as `id:base64 of 32 bytes`.
+#[derive(Clone)]
+pub struct TokenKey {
+    pub id: String,
+    key: Vec<u8>,
+}
+
+impl FromStr for TokenKey {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (id, key) = s
+            .trim()
+            .split_once(':')
+            .ok_or_else(|| anyhow!("a token key looks like `id:base64 key`"))?;
+
+        if id.is_empty() {
+            bail!("a token key needs an id");
+        }
+
+        let key = base64::decode(key)?;
+        if key.len() != KEY_LENGTH {
+            bail!("the token key `{}` isn't {} bytes long", id, KEY_LENGTH);
+        }
+
+        Ok(Self {
+            id: id.to_string(),
+            key,
+        })
+    }
+}
+
+/// Envelope encryption of the tokens users give us: every token gets its own data key,
+/// which is stored next to it encrypted by a `TokenKey`.
+#[derive(Clone)]
+pub struct TokenCipher {
+    /// The first key encrypts, the others are only there to decrypt what they encrypted before.
+    keys: Vec<(String, Aes256Gcm)>,
+}
+
+impl TokenCipher {
+    pub fn new(keys: &[TokenKey]) -> anyhow::Result<Self> {
+        if keys.is_empty() {
+            bail!("there is no key to encrypt tokens with");
+        }
+
+        let keys = keys
+            .iter()
+            .map(|key| {
+                let cipher = Aes256Gcm::new_from_slice(&key.key)
+                    .map_err(|_| anyhow!("the token key `{}` isn't usable", key.id))?;
+                Ok((key.id.clone(), cipher))
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        Ok(Self { keys })
+    }
+
+    pub fn current_key_id(&self) -> &str {
+        &self.keys[0].0
+    }
+
+    fn key_ids(&self) -> impl Iterator<Item = &str> {
+        self.keys.iter().map(|(id, _)| id.as_str())
+    }
+
+    /// Empty tokens stay empty, they mean the token isn't set.
+    pub fn encrypt(&self, token: &str) -> anyhow::Result<String> {
+        if token.is_empty() {
+            return Ok(String::new());
+        }
+
+        let data_key = Aes256Gcm::generate_key(&mut OsRng);
+        let ciphertext = seal(&Aes256Gcm::new(&data_key), token.as_bytes())?;
+
+        let (key_id, key) = &self.keys[0];
+        let wrapped_key = seal(key, data_key.as_slice())?;
+
+        Ok(encrypted_token(key_id, &wrapped_key, &ciphertext))
+    }
+
+    pub fn decrypt(&self, stored: &str) -> anyhow::Result<String> {
+        if !stored.starts_with(ENCRYPTED_PREFIX) {
+            return Ok(stored.to_string());
+        }
+
+        let encrypted = EncryptedToken::parse(stored)?;
+        let data_key = self.unwrap_data_key(&encrypted)?;
+        let data_cipher = Aes256Gcm::new_from_slice(&data_key)
+            .map_err(|_| anyhow!("the stored token is damaged"))?;
+        let token = open(&data_cipher, &encrypted.ciphertext)?;
+
+        Ok(String::from_utf8(token)?)
+    }
+
+    /// `stored` with its data key encrypted by the current key, `None` if it is already.
+    /// Plaintext tokens get encrypted.
+    pub fn rewrap(&self, stored: &str) -> anyhow::Result<Option<String>> {
+        if stored.is_empty() {
+            return Ok(None);
+        }
+
+        if !stored.starts_with(ENCRYPTED_PREFIX) {
+            return self.encrypt(stored).map(Some);
+        }
+
+        let encrypted = EncryptedToken::parse(stored)?;
+        let (key_id, key) = &self.keys[0];
+        if encrypted.key_id == key_id {
+            return Ok(None);
+        }
+
+        // the token itself stays as it is, only its data key changes hands
+        let data_key = self.unwrap_data_key(&encrypted)?;
+        let wrapped_key = seal(key, &data_key)?;
+
+        Ok(Some(encrypted_token(
+            key_id,
+            &wrapped_key,
+            &encrypted.ciphertext,
+        )))
+    }
+
+    fn unwrap_data_key(&self, encrypted: &EncryptedToken) -> anyhow::Result<Vec<u8>> {
+        let (_, key) = self
+            .keys
+            .iter()
+            .find(|(id, _)| id == encrypted.key_id)
+            .ok_or_else(|| anyhow!("the token key `{}` is gone", encrypted.key_id))?;
+
+        open(key, &encrypted.wrapped_key)
+    }
+}
+
+/// Only the key ids, the keys themselves stay out of the logs.
+impl fmt::Debug for TokenCipher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TokenCipher")
+            .field("key_ids", &self.key_ids().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+struct EncryptedToken<'a> {
+    key_id: &'a str,
+    wrapped_key: Vec<u8>,
+    ciphertext: Vec<u8>,
+}
+
+impl<'a> EncryptedToken<'a> {
+    fn parse(stored: &'a str) -> anyhow::Result<Self> {
+        let parts = stored
+            .strip_prefix(ENCRYPTED_PREFIX)
+            .map(|rest| rest.split(':').collect::<Vec<_>>())
+            .unwrap_or_default();
+
+        match parts.as_slice() {
+            [key_id, wrapped_key, ciphertext] => Ok(Self {
+                key_id: *key_id,
+                wrapped_key: base64::decode(wrapped_key)?,
+                ciphertext: base64::decode(ciphertext)?,
+            }),
+            _ => bail!("the stored token is damaged"),
+        }
+    }
+}
+
+fn encrypted_token(key_id: &str, wrapped_key: &[u8], ciphertext: &[u8]) -> String {
+    format!(
+        "{}{}:{}:{}",
+        ENCRYPTED_PREFIX,
+        key_id,
+        base64::encode(wrapped_key),
+        base64::encode(ciphertext)
+    )
+}
+
+/// Encrypts `plaintext` with a new nonce, which goes in front of the ciphertext.
+fn seal(cipher: &Aes256Gcm, plaintext: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext)
+        .map_err(|_| anyhow!("failed to encrypt token"))?;
+
+    let mut sealed = nonce.to_vec();
+    sealed.extend(ciphertext);
+
+    Ok(sealed)
+}
+
+fn open(cipher: &Aes256Gcm, sealed: &[u8]) -> anyhow::Result<Vec<u8>> {
+    if sealed.len() < NONCE_LENGTH {
+        bail!("the stored token is damaged");
+    }
+
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LENGTH);
+    cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| anyhow!("failed to decrypt token, it doesn't fit the token key"))
+}
+
+/// Just enough of `secret` for its owner to recognize it, e.g. `secret_ab…f9`.
+pub fn mask_secret(secret: &str) -> String {
+    let chars = secret.chars().collect::<Vec<_>>();
+    let shown = match chars.len() {
+        0 => return String::new(),
+        1..=6 => return "…".to_string(),
+        7..=23 => 2,
+        _ => 9,
+    };
+
+    format!(
+        "{}…{}",
+        chars[..shown].iter().collect::<String>(),
+        chars[chars.len() - 2..].iter().collect::<String>()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token_key(id: &str, byte: u8) -> TokenKey {
+        format!("{}:{}", id, base64::encode([byte; KEY_LENGTH]))
+            .parse()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_parse_token_key() {
+        let key = token_key("2022-11", 7);
+        assert_eq!(key.id, "2022-11");
+        assert!("2022-11".parse::<TokenKey>().is_err());
+        assert!(":AAAA".parse::<TokenKey>().is_err());
+        assert!("short:AAAA".parse::<TokenKey>().is_err());
+    }
+
+    #[test]
+    fn test_encrypt_token() {
+        let cipher = TokenCipher::new(&[token_key("a", 1)]).unwrap();
+        let token = "secret_abcdefghijklmnopqrstuvwxyz0123456789ABCDEFf9";
+
+        let encrypted = cipher.encrypt(token).unwrap();
+        assert!(encrypted.starts_with("enc:v1:a:"));
+        assert!(!encrypted.contains(token));
+        // every token gets its own data key and nonce
+        assert_ne!(encrypted, cipher.encrypt(token).unwrap());
+        assert_eq!(cipher.decrypt(&encrypted).unwrap(), token);
+
+        assert_eq!(cipher.encrypt("").unwrap(), "");
+        // tokens from before the encryption
+        assert_eq!(cipher.decrypt("k_12345678").unwrap(), "k_12345678");
+
+        let other_cipher = TokenCipher::new(&[token_key("a", 2)]).unwrap();
+        assert!(other_cipher.decrypt(&encrypted).is_err());
+        assert!(cipher.decrypt("enc:v1:a:AAAA").is_err());
+    }
+
+    #[test]
+    fn test_rotate_token_key() {
+        let old_cipher = TokenCipher::new(&[token_key("old", 1)]).unwrap();
+        let encrypted = old_cipher.encrypt("k_12345678").unwrap();
+
+        let cipher = TokenCipher::new(&[token_key("new", 2), token_key("old", 1)]).unwrap();
+        assert_eq!(cipher.current_key_id(), "new");
+        assert_eq!(
+            format!("{:?}", cipher),
+            r#"TokenCipher { key_ids: ["new", "old"] }"#
+        );
+        assert_eq!(cipher.decrypt(&encrypted).unwrap(), "k_12345678");
+
+        let rewrapped = cipher.rewrap(&encrypted).unwrap().unwrap();
+        assert!(rewrapped.starts_with("enc:v1:new:"));
+        assert!(cipher.rewrap(&rewrapped).unwrap().is_none());
+        assert!(cipher.rewrap("").unwrap().is_none());
+
+        // the old key can go once everything is rewrapped
+        let new_cipher = TokenCipher::new(&[token_key("new", 2)]).unwrap();
+        assert_eq!(new_cipher.decrypt(&rewrapped).unwrap(), "k_12345678");
+        assert!(new_cipher.decrypt(&encrypted).is_err());
+
+        let plaintext = cipher.rewrap("k_12345678").unwrap().unwrap();
+        assert_eq!(new_cipher.decrypt(&plaintext).unwrap(), "k_12345678");
+    }
+
+    #[test]
+    fn test_mask_secret() {
+        assert_eq!(
+            mask_secret("secret_abcdefghijklmnopqrstuvwxyz0123456789ABCDEFf9"),
+            "secret_ab…f9"
+        );
+        assert_eq!(mask_secret("k_12345678"), "k_…78");
+        assert_eq!(mask_secret("abc"), "…");
+        assert_eq!(mask_secret(""), "");
+    }
+}
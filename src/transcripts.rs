@@ -1,23 +1,81 @@
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Default)]
 pub enum Language {
+    #[default]
     En,
 }
 
-impl Default for Language {
-    fn default() -> Self {
-        Self::En
+pub fn transcript_welcome(lang: Language) -> &'static str {
+    match lang {
+        Language::En => {
+            "Welcome to work with me! But we need to configure something first, \
+            I'll walk you through it step by step."
+        }
     }
 }
 
-pub fn transcript_welcome(lang: Language) -> &'static str {
+pub fn welcome_back(lang: Language) -> &'static str {
+    match lang {
+        Language::En => {
+            "Welcome back! Everything is set up, send me the name of a movie to find it.\n\
+            Use /settings to see your tokens and /help to check more details."
+        }
+    }
+}
+
+pub fn setup_notion_token(lang: Language, can_connect: bool) -> &'static str {
+    match lang {
+        Language::En => {
+            if can_connect {
+                "Step 1 of 3: connect your Notion workspace with the button below and pick the page \
+                for your movie list, or send me the token of your internal integration."
+            } else {
+                "Step 1 of 3: send me the token of your Notion internal integration."
+            }
+        }
+    }
+}
+
+pub fn setup_notion_page(lang: Language) -> &'static str {
     match lang {
         Language::En => {
-            "Welcome to work with me! But we need to configure something first.\n\
-            Please use:\n\n/help to check more details."
+            "Step 2 of 3: send me the link of the Notion page where I should create your movie list, \
+            optionally followed by its title. Make sure the page is shared with the integration."
         }
     }
 }
 
+pub fn setup_imdb_key(lang: Language) -> &'static str {
+    match lang {
+        Language::En => {
+            "Step 3 of 3: send me your IMDb-API key if you have one, or skip this step to use the shared key."
+        }
+    }
+}
+
+pub fn setup_finished(lang: Language) -> &'static str {
+    match lang {
+        Language::En => "All set! Send me the name of a movie to add it to your movie list.",
+    }
+}
+
+pub fn setup_cancelled(lang: Language) -> &'static str {
+    match lang {
+        Language::En => "Setup cancelled, /start again whenever you like.",
+    }
+}
+
+pub fn setup_needs_text(lang: Language) -> &'static str {
+    match lang {
+        Language::En => "Please answer with a text message, or cancel the setup.",
+    }
+}
+
+pub fn skip(lang: Language) -> &'static str {
+    match lang {
+        Language::En => "Skip",
+    }
+}
+
 pub fn database_error(lang: Language) -> &'static str {
     match lang {
         Language::En => "I got a bad memory...\nPlease let me stay alone for a while.",
@@ -54,6 +112,12 @@ pub fn input_empty_notion_page_id(lang: Language) -> &'static str {
     }
 }
 
+pub fn input_empty_notion_database_id(lang: Language) -> &'static str {
+    match lang {
+        Language::En => "Notion database ID or its link should follow the /use_notion_db command.\n e.g. /use_notion_db abc123",
+    }
+}
+
 pub fn input_empty_keyword(lang: Language) -> &'static str {
     match lang {
         Language::En => "Please send me the title",
@@ -72,12 +136,452 @@ pub fn notion_token_set_as(lang: Language, token: &str) -> String {
     }
 }
 
+pub fn imdb_token_rejected(lang: Language, reason: &str) -> String {
+    match lang {
+        Language::En => format!(
+            "IMDb-API doesn't accept this API key ({}), please check it and /set_imdb_token again.",
+            reason
+        ),
+    }
+}
+
+pub fn notion_token_rejected(lang: Language) -> &'static str {
+    match lang {
+        Language::En => {
+            "Notion doesn't accept this token, please check it and /set_notion_token again."
+        }
+    }
+}
+
+pub fn delete_token_message_yourself(lang: Language) -> &'static str {
+    match lang {
+        Language::En => "I couldn't delete your message, please delete it yourself so your token doesn't stay in the chat.",
+    }
+}
+
+pub fn connect_notion_workspace(lang: Language) -> &'static str {
+    match lang {
+        Language::En => {
+            "Please connect your Notion workspace and pick the pages I can work with.\n\
+            The link is only good for 15 minutes."
+        }
+    }
+}
+
+pub fn connect_notion(lang: Language) -> &'static str {
+    match lang {
+        Language::En => "Connect Notion",
+    }
+}
+
+pub fn notion_connected(lang: Language, workspace_name: Option<&str>) -> String {
+    match lang {
+        Language::En => match workspace_name {
+            Some(name) => format!("Your Notion workspace {} is connected.", name),
+            None => "Your Notion workspace is connected.".to_string(),
+        },
+    }
+}
+
+pub fn notion_authorization_denied(lang: Language) -> &'static str {
+    match lang {
+        Language::En => {
+            "You didn't allow me to access your Notion workspace, /set_notion_token to try again."
+        }
+    }
+}
+
+pub fn notion_authorization_failed(lang: Language) -> &'static str {
+    match lang {
+        Language::En => {
+            "Notion didn't let me connect your workspace, please /set_notion_token again."
+        }
+    }
+}
+
+pub fn notion_authorization_expired(lang: Language) -> &'static str {
+    match lang {
+        Language::En => "This link has expired, please send /set_notion_token to the bot again.",
+    }
+}
+
+pub fn notion_authorization_finished(lang: Language) -> &'static str {
+    match lang {
+        Language::En => "All done, you can go back to Telegram now.",
+    }
+}
+
+pub fn movie_info_provider_summary(lang: Language, current: &str, available: &str) -> String {
+    match lang {
+        Language::En => format!(
+            "I'm getting movie information from {} for you.\n\
+            Available providers: {}\n e.g. /provider tmdb or /provider tmdb your_api_key",
+            current, available
+        ),
+    }
+}
+
+pub fn unknown_movie_info_provider(lang: Language, available: &str) -> String {
+    match lang {
+        Language::En => format!(
+            "I don't know this provider. Available providers: {}",
+            available
+        ),
+    }
+}
+
+pub fn movie_info_provider_needs_no_token(lang: Language, provider: &str) -> String {
+    match lang {
+        Language::En => format!("{} doesn't need an API token.", provider),
+    }
+}
+
+pub fn movie_info_provider_set_as(lang: Language, provider: &str, token_set: bool) -> String {
+    match lang {
+        Language::En => match token_set {
+            true => format!(
+                "Movie information provider has been set as: {}\nYour {} token has been saved.",
+                provider, provider
+            ),
+            false => format!("Movie information provider has been set as: {}", provider),
+        },
+    }
+}
+
+pub fn too_many_requests(lang: Language, retry_after_secs: u64) -> String {
+    match lang {
+        Language::En => format!(
+            "Slow down please, I can't keep up! 😵 Try again in {} seconds.",
+            retry_after_secs
+        ),
+    }
+}
+
+pub fn imdb_api_usage(lang: Language, usage: &str, own_token: bool) -> String {
+    match lang {
+        Language::En => {
+            if own_token {
+                format!("Your IMDb-API token today:\n{}", usage)
+            } else {
+                format!(
+                    "The shared IMDb-API token today:\n{}\n\n\
+                    You can /set_imdb_token to use your own quota.",
+                    usage
+                )
+            }
+        }
+    }
+}
+
+pub fn imdb_api_usage_warning(lang: Language, usage: &str, own_token: bool) -> String {
+    match lang {
+        Language::En => {
+            if own_token {
+                format!(
+                    "⚠️ Your IMDb-API token is running out of today's quota.\n{}",
+                    usage
+                )
+            } else {
+                format!(
+                    "⚠️ The shared IMDb-API token is running out of today's quota.\n{}\n\n\
+                    Please /set_imdb_token to use your own quota.",
+                    usage
+                )
+            }
+        }
+    }
+}
+
 pub fn notion_database_created(lang: Language) -> &'static str {
     match lang {
         Language::En => "Movie list database ID token has been created",
     }
 }
 
+pub fn cast(lang: Language) -> &'static str {
+    match lang {
+        Language::En => "Cast",
+    }
+}
+
+pub fn actor(lang: Language) -> &'static str {
+    match lang {
+        Language::En => "Actor",
+    }
+}
+
+pub fn character(lang: Language) -> &'static str {
+    match lang {
+        Language::En => "Character",
+    }
+}
+
+pub fn trailer(lang: Language) -> &'static str {
+    match lang {
+        Language::En => "Trailer",
+    }
+}
+
+pub fn watched(lang: Language) -> &'static str {
+    match lang {
+        Language::En => "Watched",
+    }
+}
+
+pub fn rate(lang: Language) -> &'static str {
+    match lang {
+        Language::En => "Rate",
+    }
+}
+
+pub fn marked_as_watched(lang: Language, watched_on: &str) -> String {
+    match lang {
+        Language::En => format!("Marked as watched on {}.", watched_on),
+    }
+}
+
+pub fn rated(lang: Language, rating: u32, max_rating: u32) -> String {
+    match lang {
+        Language::En => format!("Rated {}/{}.", rating, max_rating),
+    }
+}
+
+pub fn not_in_movie_list(lang: Language) -> &'static str {
+    match lang {
+        Language::En => "This title is not in your movie list yet, search for it to add it first.",
+    }
+}
+
+pub fn field_left_out(lang: Language, field: &str) -> String {
+    match lang {
+        Language::En => format!(
+            "Your movie list has no property for {0}, add one in Notion and map it with /map_property {0} `property name`.",
+            field
+        ),
+    }
+}
+
+pub fn input_movie_link(lang: Language, command: &str) -> String {
+    match lang {
+        Language::En => format!(
+            "The IMDb link or ID of the title should follow the /{0} command.\n e.g. /{0} tt0133093",
+            command
+        ),
+    }
+}
+
+pub fn invalid_date(lang: Language) -> &'static str {
+    match lang {
+        Language::En => "Please write the date like 2022-11-12.",
+    }
+}
+
+pub fn invalid_rating(lang: Language, max_rating: u32) -> String {
+    match lang {
+        Language::En => format!(
+            "The rating should be a number from 1 to {} after the IMDb link or ID.\n e.g. /rate tt0133093 8",
+            max_rating
+        ),
+    }
+}
+
+pub fn database_template(lang: Language, icon: &str, title: &str) -> String {
+    match lang {
+        Language::En => format!(
+            "Your movie list will be called {} {}.\n\n\
+            Tick the properties you want and pick an icon, then press Create.\n\
+            To choose another title, put it after the page link, e.g. /create_notion_db abc123 My Films",
+            icon, title
+        ),
+    }
+}
+
+pub fn create_database(lang: Language) -> &'static str {
+    match lang {
+        Language::En => "Create",
+    }
+}
+
+pub fn cancel(lang: Language) -> &'static str {
+    match lang {
+        Language::En => "Cancel",
+    }
+}
+
+pub fn database_template_expired(lang: Language) -> &'static str {
+    match lang {
+        Language::En => {
+            "This movie list is already created or cancelled, use /create_notion_db to start again."
+        }
+    }
+}
+
+pub fn database_template_cancelled(lang: Language) -> &'static str {
+    match lang {
+        Language::En => "Okay, no movie list was created.",
+    }
+}
+
+pub fn notion_database_connected(lang: Language) -> &'static str {
+    match lang {
+        Language::En => "Your Notion database will be used as your movie list from now on.",
+    }
+}
+
+pub fn notion_database_incompatible(lang: Language, mismatched: &str) -> String {
+    match lang {
+        Language::En => format!(
+            "These properties of your movie list have the wrong type:\n{}\n\nPlease change them in Notion and try again.",
+            mismatched
+        ),
+    }
+}
+
+pub fn notion_database_repaired(lang: Language, added: &str) -> String {
+    match lang {
+        Language::En => format!(
+            "I added the missing properties to your movie list:\n{}",
+            added
+        ),
+    }
+}
+
+pub fn notion_database_is_fine(lang: Language) -> &'static str {
+    match lang {
+        Language::En => "Your movie list has all the properties I need.",
+    }
+}
+
+pub fn notion_property_missing(lang: Language, name: &str, property_type: &str) -> String {
+    match lang {
+        Language::En => format!("- {} ({}) is missing", name, property_type),
+    }
+}
+
+pub fn notion_property_type_mismatch(
+    lang: Language,
+    name: &str,
+    expected: &str,
+    actual: &str,
+) -> String {
+    match lang {
+        Language::En => format!("- {} should be {}, but it is {}", name, expected, actual),
+    }
+}
+
+pub fn notion_database_needs_mapping(lang: Language, problems: &str) -> String {
+    match lang {
+        Language::En => format!(
+            "Some properties I write to don't match your database:\n{}\n\n\
+            Use /map_property to tell me which of your properties to use instead, or /check_notion_db to add the missing ones.",
+            problems
+        ),
+    }
+}
+
+pub fn property_mapping_summary(lang: Language, summary: &str) -> String {
+    match lang {
+        Language::En => format!(
+            "Where I write each piece of information:\n{}\n\n\
+            Change it with /map_property `field` `property name`, optionally followed by `, type`.\n\
+            Leave a field out with /map_property `field` off, or use the default property again with /map_property `field` reset.",
+            summary
+        ),
+    }
+}
+
+pub fn property_mapping_entry(
+    lang: Language,
+    field: &str,
+    name: &str,
+    property_type: &str,
+) -> String {
+    match lang {
+        Language::En => format!("{} -> {} ({})", field, name, property_type),
+    }
+}
+
+pub fn property_mapping_omitted_entry(lang: Language, field: &str) -> String {
+    match lang {
+        Language::En => format!("{} -> left out", field),
+    }
+}
+
+pub fn property_mapped(lang: Language, field: &str, name: &str, property_type: &str) -> String {
+    match lang {
+        Language::En => format!(
+            "From now on {} goes to the {} property {}.",
+            field, property_type, name
+        ),
+    }
+}
+
+pub fn property_omitted(lang: Language, field: &str) -> String {
+    match lang {
+        Language::En => format!("From now on {} is left out of your movie list.", field),
+    }
+}
+
+pub fn property_mapping_reset(lang: Language, field: &str, name: &str) -> String {
+    match lang {
+        Language::En => format!("From now on {} goes to the {} property again.", field, name),
+    }
+}
+
+pub fn field_is_required(lang: Language, field: &str) -> String {
+    match lang {
+        Language::En => format!(
+            "I can't work without {}, but you can map it to another property.",
+            field
+        ),
+    }
+}
+
+pub fn unknown_movie_field(lang: Language, fields: &str) -> String {
+    match lang {
+        Language::En => format!("Please choose one of these fields: {}", fields),
+    }
+}
+
+pub fn input_empty_property_name(lang: Language) -> &'static str {
+    match lang {
+        Language::En => {
+            "The property name should follow the field.\n e.g. /map_property director Regisseur"
+        }
+    }
+}
+
+pub fn notion_property_not_found(lang: Language, name: &str) -> String {
+    match lang {
+        Language::En => format!("There is no property named {} in your movie list.", name),
+    }
+}
+
+pub fn notion_property_type_differs(
+    lang: Language,
+    name: &str,
+    expected: &str,
+    actual: &str,
+) -> String {
+    match lang {
+        Language::En => format!("{} is a {} property, not {}.", name, actual, expected),
+    }
+}
+
+pub fn unsupported_property_type(
+    lang: Language,
+    field: &str,
+    property_type: &str,
+    supported: &str,
+) -> String {
+    match lang {
+        Language::En => format!(
+            "I can't write {} to a {} property, please use one of these types: {}",
+            field, property_type, supported
+        ),
+    }
+}
+
 pub fn need_notion_token_first(lang: Language) -> &'static str {
     match lang {
         Language::En => "Please /set_notion_token first.",
@@ -96,15 +600,86 @@ pub fn add_to_movie_list(lang: Language) -> &'static str {
     }
 }
 
-pub fn add_to_movie_list_successfully(lang: Language, title: &str) -> String {
+pub fn add_to_movie_list_successfully(lang: Language, title: &str, provider: &str) -> String {
     match lang {
         Language::En => format!(
-            "<b>{}</b> has been added to your movie list successfully!",
-            title
+            "<b>{}</b> has been added to your movie list successfully!\n<i>Information provided by {}</i>",
+            title, provider
         ),
     }
 }
 
+pub fn update_movie_info_successfully(lang: Language, title: &str, provider: &str) -> String {
+    match lang {
+        Language::En => format!(
+            "<b>{}</b> has been updated in your movie list successfully!\n<i>Information provided by {}</i>",
+            title, provider
+        ),
+    }
+}
+
+pub fn refresh_movie_info_successfully(lang: Language, title: &str, provider: &str) -> String {
+    match lang {
+        Language::En => format!(
+            "<b>{}</b> has been refreshed successfully!\n<i>Information provided by {}</i>",
+            title, provider
+        ),
+    }
+}
+
+pub fn refresh(lang: Language) -> &'static str {
+    match lang {
+        Language::En => "Refresh",
+    }
+}
+
+pub fn refreshing_movie_list(lang: Language) -> &'static str {
+    match lang {
+        Language::En => {
+            "Refreshing the ratings and information in your movie list, this may take a while..."
+        }
+    }
+}
+
+pub fn movie_list_refreshed(lang: Language, refreshed: usize, failed: usize) -> String {
+    match lang {
+        Language::En => {
+            if failed == 0 {
+                format!(
+                    "{} titles in your movie list have been refreshed.",
+                    refreshed
+                )
+            } else {
+                format!(
+                    "{} titles in your movie list have been refreshed, {} couldn't be refreshed.",
+                    refreshed, failed
+                )
+            }
+        }
+    }
+}
+
+pub fn already_in_movie_list(lang: Language, title: &str, page_url: &str) -> String {
+    match lang {
+        Language::En => format!(
+            "<a href=\"{}\"><b>{}</b></a> is already in your movie list.",
+            page_url, title
+        ),
+    }
+}
+
+pub fn add_anyway(lang: Language) -> &'static str {
+    match lang {
+        Language::En => "Add anyway",
+    }
+}
+
+pub fn update_existing(lang: Language) -> &'static str {
+    match lang {
+        Language::En => "Update existing",
+    }
+}
+
 pub fn not_set(lang: Language) -> &'static str {
     match lang {
         Language::En => "not set",
@@ -113,7 +688,19 @@ pub fn not_set(lang: Language) -> &'static str {
 
 pub fn user_hint_title(lang: Language) -> &'static str {
     match lang {
-        Language::En => "Please config required settings.",
+        Language::En => "Please config required settings, /start walks you through them.",
+    }
+}
+
+pub fn user_hint_missing_notion_token(lang: Language) -> &'static str {
+    match lang {
+        Language::En => "• Your Notion workspace isn't connected yet.",
+    }
+}
+
+pub fn user_hint_missing_movie_list(lang: Language) -> &'static str {
+    match lang {
+        Language::En => "• You don't have a movie list yet.",
     }
 }
 
@@ -145,16 +732,73 @@ pub fn user_hint_set_imdb_token(lang: Language) -> &'static str {
     }
 }
 
+pub fn user_hint_provider_command(lang: Language) -> &'static str {
+    match lang {
+        Language::En => {
+            "/provider `name` `token` - choose where I get movie information from (IMDb-API, TMDb, OMDb, ...), \
+            optionally with your own API token for it."
+        }
+    }
+}
+
+pub fn user_hint_usage_command(lang: Language) -> &'static str {
+    match lang {
+        Language::En => "/usage - check how much of the IMDb-API quota is used today.",
+    }
+}
+
+pub fn user_hint_refresh_command(lang: Language) -> &'static str {
+    match lang {
+        Language::En => {
+            "/refresh - update the ratings and information of the titles in your movie list."
+        }
+    }
+}
+
 pub fn user_hint_set_notion_token(lang: Language) -> &'static str {
     match lang {
-        Language::En => "/set_notion_token `token` - set the Notion internal integration token.",
+        Language::En => "/set_notion_token - connect your Notion workspace, or follow it with the token of your internal integration.",
     }
 }
 
 pub fn user_hint_create_notion_database(lang: Language) -> &'static str {
     match lang {
         Language::En => {
-            "/create_notion_db `page link or id` - create a Notion database as your movie list."
+            "/create_notion_db `page link or id` `title` - create a Notion database as your movie list, the title is optional."
+        }
+    }
+}
+
+pub fn user_hint_check_notion_database(lang: Language) -> &'static str {
+    match lang {
+        Language::En => {
+            "/check_notion_db - check your movie list and add the properties it's missing."
+        }
+    }
+}
+
+pub fn user_hint_watched_command(lang: Language) -> &'static str {
+    match lang {
+        Language::En => "/watched `IMDb link or id` `date` - mark a title in your movie list as watched, today if there is no date.",
+    }
+}
+
+pub fn user_hint_rate_command(lang: Language) -> &'static str {
+    match lang {
+        Language::En => "/rate `IMDb link or id` `rating` - give a title in your movie list your own rating from 1 to 10.",
+    }
+}
+
+pub fn user_hint_map_property_command(lang: Language) -> &'static str {
+    match lang {
+        Language::En => "/map_property `field` `property name` - choose which property of your movie list a field goes to.",
+    }
+}
+
+pub fn user_hint_use_notion_database(lang: Language) -> &'static str {
+    match lang {
+        Language::En => {
+            "/use_notion_db `database link or id` - use one of your Notion databases as your movie list."
         }
     }
 }
@@ -192,17 +836,55 @@ pub fn parse_imdb_api_response_failed(lang: Language) -> &'static str {
     }
 }
 
+pub fn parse_tmdb_api_error_message_failed(lang: Language) -> &'static str {
+    match lang {
+        Language::En => "Can't understand what's wrong with TMDb...",
+    }
+}
+
+pub fn parse_tmdb_api_response_failed(lang: Language) -> &'static str {
+    match lang {
+        Language::En => "TMDb told me bullshit...",
+    }
+}
+
+pub fn parse_omdb_api_error_message_failed(lang: Language) -> &'static str {
+    match lang {
+        Language::En => "Can't understand what's wrong with OMDb...",
+    }
+}
+
+pub fn parse_omdb_api_response_failed(lang: Language) -> &'static str {
+    match lang {
+        Language::En => "OMDb told me bullshit...",
+    }
+}
+
+pub fn invalid_movie_id(lang: Language) -> &'static str {
+    match lang {
+        Language::En => "I can't recognize this movie anymore, please search it again.",
+    }
+}
+
 pub fn help_message(lang: Language, help_page: &str) -> String {
     match lang {
         Language::En => format!(
-            "<b>Supported commands:</b>\n\n{}\n{}\n{}\n{}\n{}\n{}\n\n\
+            "<b>Supported commands:</b>\n\n{}\n{}\n{}\n{}\n{}\n{}\n{}\n{}\n{}\n{}\n{}\n{}\n{}\n{}\n\n\
             Please visit <a href=\"{}\"><b>this page</b></a> to get more help.",
             user_hint_help_command(lang),
             user_hint_start_command(lang),
             user_hint_settings_command(lang),
             user_hint_set_imdb_token(lang),
+            user_hint_provider_command(lang),
+            user_hint_usage_command(lang),
             user_hint_set_notion_token(lang),
             user_hint_create_notion_database(lang),
+            user_hint_use_notion_database(lang),
+            user_hint_check_notion_database(lang),
+            user_hint_map_property_command(lang),
+            user_hint_refresh_command(lang),
+            user_hint_watched_command(lang),
+            user_hint_rate_command(lang),
             help_page
         ),
     }
@@ -0,0 +1,121 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// IMDb-API counts the usage of a key per day.
+const QUOTA_PERIOD: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Decides when the IMDb-API usage of a key is worth checking after a search, and when its user
+/// needs to hear about it: one `Usage` call per `check_interval`, one warning per crossing of the
+/// threshold.
+pub struct UsageWarnings {
+    check_interval: Duration,
+    keys: Mutex<HashMap<String, KeyUsage>>,
+}
+
+#[derive(Debug, Copy, Clone)]
+struct KeyUsage {
+    checked_at: Instant,
+    above_threshold: bool,
+}
+
+impl UsageWarnings {
+    pub fn new(check_interval: Duration) -> Self {
+        Self {
+            check_interval,
+            keys: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Whether the usage of `api_key` is due to be checked, which counts as checking it.
+    pub fn start_check(&self, api_key: &str) -> bool {
+        self.start_check_at(api_key, Instant::now())
+    }
+
+    /// Records the checked usage of `api_key`, returns whether it just crossed the threshold.
+    pub fn finish_check(&self, api_key: &str, above_threshold: bool) -> bool {
+        let mut keys = self.keys.lock().unwrap_or_else(|e| e.into_inner());
+
+        match keys.get_mut(api_key) {
+            Some(usage) => {
+                let crossed = above_threshold && !usage.above_threshold;
+                usage.above_threshold = above_threshold;
+                crossed
+            }
+            None => false,
+        }
+    }
+
+    fn start_check_at(&self, api_key: &str, now: Instant) -> bool {
+        let mut keys = self.keys.lock().unwrap_or_else(|e| e.into_inner());
+        // the quota of keys nobody used for a day has been reset anyway
+        keys.retain(|_, usage| now.duration_since(usage.checked_at) < QUOTA_PERIOD);
+
+        match keys.get_mut(api_key) {
+            Some(usage) if now.duration_since(usage.checked_at) < self.check_interval => false,
+            Some(usage) => {
+                usage.checked_at = now;
+                true
+            }
+            None => {
+                keys.insert(
+                    api_key.to_string(),
+                    KeyUsage {
+                        checked_at: now,
+                        above_threshold: false,
+                    },
+                );
+                true
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_interval() {
+        let warnings = UsageWarnings::new(Duration::from_secs(600));
+        let now = Instant::now();
+
+        assert!(warnings.start_check_at("k_1", now));
+        assert!(!warnings.start_check_at("k_1", now + Duration::from_secs(599)));
+        // every key has its own interval
+        assert!(warnings.start_check_at("", now));
+        assert!(warnings.start_check_at("k_1", now + Duration::from_secs(600)));
+    }
+
+    #[test]
+    fn test_warn_once_per_crossing() {
+        let warnings = UsageWarnings::new(Duration::ZERO);
+        let now = Instant::now();
+
+        // nothing to warn about before the usage was checked
+        assert!(!warnings.finish_check("k_1", true));
+
+        assert!(warnings.start_check_at("k_1", now));
+        assert!(!warnings.finish_check("k_1", false));
+        assert!(warnings.start_check_at("k_1", now));
+        assert!(warnings.finish_check("k_1", true));
+        assert!(warnings.start_check_at("k_1", now));
+        assert!(!warnings.finish_check("k_1", true));
+
+        // the quota was reset
+        assert!(warnings.start_check_at("k_1", now));
+        assert!(!warnings.finish_check("k_1", false));
+        assert!(warnings.start_check_at("k_1", now));
+        assert!(warnings.finish_check("k_1", true));
+    }
+
+    #[test]
+    fn test_forget_idle_keys() {
+        let warnings = UsageWarnings::new(Duration::from_secs(600));
+        let now = Instant::now();
+
+        assert!(warnings.start_check_at("k_1", now));
+        assert!(warnings.start_check_at("k_2", now + QUOTA_PERIOD));
+        assert_eq!(warnings.keys.lock().unwrap().len(), 1);
+    }
+}